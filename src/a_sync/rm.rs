@@ -3,11 +3,53 @@
 //! Implementors can choose between implementing `ResourceManager` directly, or implementing
 //! `CResourceManager` (which is closer to the XA C API) and wrap it into `CRmWrapper` to get
 //! an implementation of the more idiomatic `ResourceManager` trait.
+//!
+//! `FileResourceManager` is a reference implementation of `CResourceManager`
+//! on top of a [`FileStore`](crate::FileStore).
+//! `PgResourceManager` maps the XA calls to the two-phase commit statements of `PostgreSQL`,
+//! `MySqlResourceManager` maps them to the XA statements of `MySQL` and `MariaDB`.
+//!
+//! `FaultInjector` wraps any `ResourceManager` and injects failures into its calls,
+//! for testing the error handling of applications.
+//! `MockResourceManager` records the calls it receives and returns scripted results.
+//! `StateValidator` checks that the calls follow the branch state table of XA.
+//!
+//! With the feature `tracing`, `TracingRm` emits a `tracing` span for each XA call.
+//! With the feature `metrics`, `MeteredRm` reports the latency and outcome of each XA call.
+//!
+//! Implementors can verify their implementation with `check_conformance`.
 mod c_resource_manager;
 mod c_rm_wrapper;
+mod conformance;
+mod fault_injector;
+mod file_resource_manager;
+#[cfg(feature = "metrics")]
+mod metered_rm;
+mod mock_resource_manager;
+mod mysql_resource_manager;
+mod pg_resource_manager;
 mod resource_manager;
+mod state_validator;
+#[cfg(feature = "tracing")]
+mod tracing_rm;
 
 pub use self::{
-    c_resource_manager::CResourceManager, c_rm_wrapper::CRmWrapper,
+    c_resource_manager::CResourceManager,
+    c_rm_wrapper::CRmWrapper,
+    conformance::check_conformance,
+    fault_injector::FaultInjector,
+    file_resource_manager::FileResourceManager,
+    mock_resource_manager::MockResourceManager,
+    mysql_resource_manager::{MySqlExecutor, MySqlResourceManager},
+    pg_resource_manager::{PgExecutor, PgResourceManager},
     resource_manager::ResourceManager,
+    state_validator::StateValidator,
 };
+
+#[cfg(feature = "tracing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+pub use self::tracing_rm::TracingRm;
+
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub use self::metered_rm::MeteredRm;
@@ -0,0 +1,266 @@
+use super::{FaultInjector, ResourceManager};
+use crate::{
+    conformance::Scenario, ConformanceReport, ErrorCode, Fault, FaultPlan, ReturnCode, RmMethod,
+};
+use std::future::Future;
+
+/// Runs a battery of XA scenarios against fresh instances of a resource manager
+/// implementation, and reports which behaviors deviate from the XA specification.
+///
+/// The factory must return (a future of) a new instance with each call,
+/// e.g. on a new connection, but backed by the same persistent storage,
+/// so that prepared branches survive a reconnect.
+/// Implementors of `CResourceManager` can check their implementation by wrapping it
+/// into `CRmWrapper`.
+///
+/// The scenarios create transaction branches with a distinct format id and leave
+/// no prepared branches behind, unless the implementation fails a scenario.
+///
+pub async fn check_conformance<R, F, Fut>(mut factory: F) -> ConformanceReport
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut + Send,
+    Fut: Future<Output = R> + Send,
+{
+    let mut scenarios = Vec::new();
+
+    let mut s = Scenario::new("one_phase_commit");
+    one_phase_commit(&mut s, &mut factory).await;
+    scenarios.push(s.finish());
+
+    let mut s = Scenario::new("two_phase_commit");
+    two_phase_commit(&mut s, &mut factory).await;
+    scenarios.push(s.finish());
+
+    let mut s = Scenario::new("rollback_after_prepare");
+    rollback_after_prepare(&mut s, &mut factory).await;
+    scenarios.push(s.finish());
+
+    let mut s = Scenario::new("recover_after_reconnect");
+    recover_after_reconnect(&mut s, &mut factory).await;
+    scenarios.push(s.finish());
+
+    let mut s = Scenario::new("forget");
+    forget(&mut s, &mut factory).await;
+    scenarios.push(s.finish());
+
+    let mut s = Scenario::new("forget_heuristic");
+    forget_heuristic(&mut s, &mut factory).await;
+    scenarios.push(s.finish());
+
+    let mut s = Scenario::new("duplicate_xid");
+    duplicate_xid(&mut s, &mut factory).await;
+    scenarios.push(s.finish());
+
+    let mut s = Scenario::new("unknown_xid");
+    unknown_xid(&mut s, &mut factory).await;
+    scenarios.push(s.finish());
+
+    ConformanceReport { scenarios }
+}
+
+async fn one_phase_commit<R, F, Fut>(s: &mut Scenario, factory: &mut F)
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = R>,
+{
+    let mut rm = factory().await;
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(id).await)
+        && s.expect_ok("end_success", &rm.end_success(id).await)
+        && s.expect_ok("commit_one_phase", &rm.commit_one_phase(id).await)
+    {
+        s.expect_err(
+            "commit_one_phase of committed branch",
+            &rm.commit_one_phase(id).await,
+            &[ErrorCode::InvalidTransactionId],
+        );
+    }
+}
+
+async fn two_phase_commit<R, F, Fut>(s: &mut Scenario, factory: &mut F)
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = R>,
+{
+    let mut rm = factory().await;
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(id).await)
+        && s.expect_ok("end_success", &rm.end_success(id).await)
+        && s.expect_prepared("prepare", &rm.prepare(id).await)
+    {
+        s.expect_recovered("recover after prepare", &rm.recover().await, &id, true);
+        if s.expect_ok("commit", &rm.commit(id).await) {
+            s.expect_recovered("recover after commit", &rm.recover().await, &id, false);
+        }
+    }
+}
+
+async fn rollback_after_prepare<R, F, Fut>(s: &mut Scenario, factory: &mut F)
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = R>,
+{
+    let mut rm = factory().await;
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(id).await)
+        && s.expect_ok("end_success", &rm.end_success(id).await)
+        && s.expect_prepared("prepare", &rm.prepare(id).await)
+        && s.expect_ok("rollback", &rm.rollback(id).await)
+    {
+        s.expect_recovered("recover after rollback", &rm.recover().await, &id, false);
+        s.expect_err(
+            "commit of rolled back branch",
+            &rm.commit(id).await,
+            &[ErrorCode::InvalidTransactionId],
+        );
+    }
+}
+
+async fn recover_after_reconnect<R, F, Fut>(s: &mut Scenario, factory: &mut F)
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = R>,
+{
+    let id = s.xid(1);
+    {
+        let mut rm = factory().await;
+        if !(s.expect_ok("start", &rm.start(id).await)
+            && s.expect_ok("end_success", &rm.end_success(id).await)
+            && s.expect_prepared("prepare", &rm.prepare(id).await))
+        {
+            return;
+        }
+    }
+    let mut rm = factory().await;
+    let recovered = rm.recover().await;
+    s.expect_recovered("recover after reconnect", &recovered, &id, true);
+    if s.expect_ok("commit of recovered branch", &rm.commit(id).await) {
+        s.expect_recovered("recover after commit", &rm.recover().await, &id, false);
+    }
+}
+
+async fn forget<R, F, Fut>(s: &mut Scenario, factory: &mut F)
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = R>,
+{
+    let mut rm = factory().await;
+    s.expect_err(
+        "forget of unknown branch",
+        &rm.forget(s.xid(2)).await,
+        &[ErrorCode::InvalidTransactionId],
+    );
+
+    // only heuristically completed branches can be forgotten
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(id).await)
+        && s.expect_ok("end_success", &rm.end_success(id).await)
+        && s.expect_prepared("prepare", &rm.prepare(id).await)
+    {
+        s.expect_err(
+            "forget of prepared branch",
+            &rm.forget(id).await,
+            &[ErrorCode::InvalidTransactionId, ErrorCode::ProtocolError],
+        );
+        s.expect_ok("rollback after forget", &rm.rollback(id).await);
+    }
+}
+
+// A heuristic decision cannot be provoked from outside, so the commit is answered with
+// XA_HEURCOM by a `FaultInjector`, which keeps the branch until it is forgotten.
+async fn forget_heuristic<R, F, Fut>(s: &mut Scenario, factory: &mut F)
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = R>,
+{
+    let plan = FaultPlan::new().on_call(
+        RmMethod::Commit,
+        1,
+        Fault::Return(ReturnCode::HeuristicallyCommitted),
+    );
+    let mut rm = FaultInjector::new(factory().await, plan);
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(id).await)
+        && s.expect_ok("end_success", &rm.end_success(id).await)
+        && s.expect_prepared("prepare", &rm.prepare(id).await)
+        && s.expect_return(
+            "commit",
+            &rm.commit(id).await,
+            &ReturnCode::HeuristicallyCommitted,
+        )
+    {
+        s.expect_recovered(
+            "recover after heuristic commit",
+            &rm.recover().await,
+            &id,
+            true,
+        );
+        if s.expect_ok("forget", &rm.forget(id).await) {
+            s.expect_recovered("recover after forget", &rm.recover().await, &id, false);
+        }
+    }
+}
+
+async fn duplicate_xid<R, F, Fut>(s: &mut Scenario, factory: &mut F)
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = R>,
+{
+    let mut rm = factory().await;
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(id).await) {
+        s.expect_err(
+            "start of active branch",
+            &rm.start(id).await,
+            &[ErrorCode::DuplicateTransactionId],
+        );
+        // cleanup, the outcome is not part of the scenario
+        rm.end_failure(id).await.ok();
+        rm.rollback(id).await.ok();
+    }
+}
+
+async fn unknown_xid<R, F, Fut>(s: &mut Scenario, factory: &mut F)
+where
+    R: ResourceManager,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = R>,
+{
+    let mut rm = factory().await;
+    let id = s.xid(1);
+    for (step, result) in [
+        ("prepare of unknown branch", rm.prepare(id).await),
+        ("commit of unknown branch", rm.commit(id).await),
+        ("rollback of unknown branch", rm.rollback(id).await),
+    ] {
+        s.expect_err(step, &result, &[ErrorCode::InvalidTransactionId]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_conformance;
+    use crate::{
+        a_sync::rm::{CRmWrapper, FileResourceManager},
+        file_store::tests::test_dir,
+        FileStore,
+    };
+
+    #[test]
+    fn test_file_resource_manager() {
+        let dir = test_dir("a_sync_conformance");
+        let report = futures::executor::block_on(check_conformance(|| async {
+            CRmWrapper(FileResourceManager::new(&FileStore::open(&dir).unwrap()))
+        }));
+        assert!(report.is_conformant(), "{report}");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,266 @@
+use super::ResourceManager;
+use crate::{
+    fault::injected_error, xa_outcome::typed, CompletionOutcome, Fault, FaultPlan, PrepareVote,
+    ReturnCode, RmError, RmMethod, XaTransactionId,
+};
+use async_trait::async_trait;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+/// Wraps a `ResourceManager` and injects failures into its calls, as decided by a
+/// [`FaultPlan`].
+///
+/// Register the wrapped resource manager at a transaction manager to verify that your
+/// application handles all failures correctly.
+///
+/// `Fault::Delay` does not block the executor; it is independent of the async runtime
+/// and uses a helper thread as timer.
+#[derive(Debug)]
+pub struct FaultInjector<T: ResourceManager> {
+    inner: T,
+    plan: FaultPlan,
+    // branches with an injected heuristic outcome, and the call that completes them
+    heuristic: Vec<(XaTransactionId, RmMethod)>,
+}
+impl<T: ResourceManager> FaultInjector<T> {
+    /// Wraps the given resource manager.
+    pub fn new(inner: T, plan: FaultPlan) -> FaultInjector<T> {
+        FaultInjector {
+            inner,
+            plan,
+            heuristic: Vec::new(),
+        }
+    }
+
+    /// Returns the wrapped resource manager.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Returns the result of the call if it is not to be forwarded.
+    async fn inject(&mut self, method: RmMethod) -> Option<Result<ReturnCode, RmError>> {
+        match self.plan.next_fault(method) {
+            None => None,
+            Some(Fault::Error(code)) => Some(Err(injected_error(method, code))),
+            Some(Fault::Return(rc)) => Some(Ok(rc)),
+            Some(Fault::Delay(duration)) => {
+                Delay::new(duration).await;
+                None
+            }
+        }
+    }
+
+    // Like `inject`, but an injected heuristic outcome keeps the branch until it is forgotten.
+    async fn inject_completion(
+        &mut self,
+        method: RmMethod,
+        id: XaTransactionId,
+    ) -> Option<Result<ReturnCode, RmError>> {
+        let result = self.inject(method).await;
+        if let Some(Ok(rc)) = &result {
+            if rc.is_heuristic() {
+                self.heuristic.push((id, method));
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<T: ResourceManager> ResourceManager for FaultInjector<T> {
+    async fn start(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject(RmMethod::Start).await {
+            Some(result) => result,
+            None => self.inner.start(id).await,
+        }
+    }
+
+    async fn start_by_joining(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject(RmMethod::StartByJoining).await {
+            Some(result) => result,
+            None => self.inner.start_by_joining(id).await,
+        }
+    }
+
+    async fn start_by_resuming(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject(RmMethod::StartByResuming).await {
+            Some(result) => result,
+            None => self.inner.start_by_resuming(id).await,
+        }
+    }
+
+    async fn end_success(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject(RmMethod::EndSuccess).await {
+            Some(result) => result,
+            None => self.inner.end_success(id).await,
+        }
+    }
+
+    async fn end_failure(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject(RmMethod::EndFailure).await {
+            Some(result) => result,
+            None => self.inner.end_failure(id).await,
+        }
+    }
+
+    async fn end_suspend(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject(RmMethod::EndSuspend).await {
+            Some(result) => result,
+            None => self.inner.end_suspend(id).await,
+        }
+    }
+
+    async fn prepare(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject(RmMethod::Prepare).await {
+            Some(result) => result,
+            None => self.inner.prepare(id).await,
+        }
+    }
+
+    async fn commit(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject_completion(RmMethod::Commit, id).await {
+            Some(result) => result,
+            None => self.inner.commit(id).await,
+        }
+    }
+
+    async fn commit_one_phase(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject_completion(RmMethod::CommitOnePhase, id).await {
+            Some(result) => result,
+            None => self.inner.commit_one_phase(id).await,
+        }
+    }
+
+    async fn rollback(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.inject_completion(RmMethod::Rollback, id).await {
+            Some(result) => result,
+            None => self.inner.rollback(id).await,
+        }
+    }
+
+    async fn forget(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        let pending = self.heuristic.iter().position(|(h, _)| *h == id);
+        let result = match self.inject(RmMethod::Forget).await {
+            Some(result) => result,
+            // the wrapped resource manager does not know about the heuristic outcome,
+            // so the branch is completed now
+            None => match pending.map(|i| self.heuristic[i].1) {
+                Some(RmMethod::Commit) => self.inner.commit(id).await,
+                Some(RmMethod::CommitOnePhase) => self.inner.commit_one_phase(id).await,
+                Some(_) => self.inner.rollback(id).await,
+                None => self.inner.forget(id).await,
+            },
+        };
+        if let (Some(i), Ok(_)) = (pending, &result) {
+            self.heuristic.remove(i);
+        }
+        result
+    }
+
+    async fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        match self.inject(RmMethod::Recover).await {
+            Some(Err(e)) => Err(e),
+            _ => self.inner.recover().await,
+        }
+    }
+
+    async fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        match self.inject(RmMethod::BeginRecover).await {
+            Some(Err(e)) => Err(e),
+            _ => self.inner.begin_recover().await,
+        }
+    }
+
+    async fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        match self.inject(RmMethod::EndRecover).await {
+            Some(Err(e)) => Err(e),
+            _ => self.inner.end_recover().await,
+        }
+    }
+
+    async fn prepare_vote(&mut self, id: XaTransactionId) -> Result<PrepareVote, RmError> {
+        match self.inject(RmMethod::Prepare).await {
+            Some(result) => typed(RmMethod::Prepare, &id, result),
+            None => self.inner.prepare_vote(id).await,
+        }
+    }
+
+    async fn commit_outcome(&mut self, id: XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        match self.inject_completion(RmMethod::Commit, id).await {
+            Some(result) => typed(RmMethod::Commit, &id, result),
+            None => self.inner.commit_outcome(id).await,
+        }
+    }
+
+    async fn commit_one_phase_outcome(
+        &mut self,
+        id: XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        match self.inject_completion(RmMethod::CommitOnePhase, id).await {
+            Some(result) => typed(RmMethod::CommitOnePhase, &id, result),
+            None => self.inner.commit_one_phase_outcome(id).await,
+        }
+    }
+
+    async fn rollback_outcome(
+        &mut self,
+        id: XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        match self.inject_completion(RmMethod::Rollback, id).await {
+            Some(result) => typed(RmMethod::Rollback, &id, result),
+            None => self.inner.rollback_outcome(id).await,
+        }
+    }
+}
+
+// Whether the time has elapsed, and the waker to notify then.
+type DelayState = Arc<Mutex<(bool, Option<Waker>)>>;
+
+// A future that completes after the given time, using a helper thread as timer.
+struct Delay {
+    duration: Duration,
+    state: Option<DelayState>,
+}
+impl Delay {
+    fn new(duration: Duration) -> Delay {
+        Delay {
+            duration,
+            state: None,
+        }
+    }
+}
+impl Future for Delay {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let duration = self.duration;
+        let state = self.state.get_or_insert_with(|| {
+            let state = Arc::new(Mutex::new((false, None::<Waker>)));
+            let thread_state = Arc::clone(&state);
+            std::thread::spawn(move || {
+                std::thread::sleep(duration);
+                let mut guard = thread_state
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                guard.0 = true;
+                if let Some(waker) = guard.1.take() {
+                    waker.wake();
+                }
+            });
+            state
+        });
+        let mut guard = state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if guard.0 {
+            Poll::Ready(())
+        } else {
+            guard.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use super::CResourceManager;
+use crate::{FileStore, Flags, ReturnCode, RmError, XaTransactionId};
+use async_trait::async_trait;
+
+/// Implementation of `CResourceManager` for a [`FileStore`].
+///
+/// To register it at a transaction manager, wrap it into a [`CRmWrapper`](super::CRmWrapper).
+///
+/// Note that the file I/O of `FileStore` is blocking.
+#[derive(Clone, Debug)]
+pub struct FileResourceManager(FileStore);
+impl FileResourceManager {
+    /// Creates a resource manager that works on the given store.
+    #[must_use]
+    pub fn new(store: &FileStore) -> FileResourceManager {
+        FileResourceManager(store.clone())
+    }
+}
+
+#[async_trait]
+impl CResourceManager for FileResourceManager {
+    async fn start(&mut self, id: XaTransactionId, flag: Flags) -> Result<ReturnCode, RmError> {
+        self.0.xa_start(&id, flag)
+    }
+
+    async fn end(&mut self, id: XaTransactionId, flag: Flags) -> Result<ReturnCode, RmError> {
+        self.0.xa_end(&id, flag)
+    }
+
+    async fn prepare(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.0.xa_prepare(&id)
+    }
+
+    async fn commit(&mut self, id: XaTransactionId, flag: Flags) -> Result<ReturnCode, RmError> {
+        self.0.xa_commit(&id, flag)
+    }
+
+    async fn rollback(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.0.xa_rollback(&id)
+    }
+
+    async fn forget(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.0.xa_forget(&id)
+    }
+
+    async fn recover(&mut self, flag: Flags) -> Result<Vec<XaTransactionId>, RmError> {
+        self.0.xa_recover(flag)
+    }
+}
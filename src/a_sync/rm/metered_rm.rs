@@ -0,0 +1,132 @@
+use super::ResourceManager;
+use crate::{
+    metrics, CompletionOutcome, MetricsRecorder, PrepareVote, ReturnCode, RmError, RmMethod,
+    XaTransactionId,
+};
+use async_trait::async_trait;
+use std::time::Instant;
+
+/// Wraps a `ResourceManager` and reports the latency and the outcome of each XA call
+/// to a [`MetricsRecorder`](crate::MetricsRecorder).
+#[derive(Debug)]
+pub struct MeteredRm<T: ResourceManager> {
+    inner: T,
+    rm_id: u64,
+    recorder: Box<dyn MetricsRecorder>,
+}
+impl<T: ResourceManager> MeteredRm<T> {
+    /// Wraps the given resource manager; the metrics are reported with the given `rm_id`.
+    pub fn new<M: MetricsRecorder + 'static>(inner: T, rm_id: u64, recorder: M) -> MeteredRm<T> {
+        MeteredRm {
+            inner,
+            rm_id,
+            recorder: Box::new(recorder),
+        }
+    }
+
+    /// Returns the wrapped resource manager.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+// Runs the call and records its outcome.
+macro_rules! metered {
+    ($self:ident, $method:expr, $id:ident, $call:ident) => {{
+        let start = Instant::now();
+        let result = $self.inner.$call($id).await;
+        metrics::record_result(&*$self.recorder, $self.rm_id, $method, start, &result);
+        result
+    }};
+}
+
+// Runs the recover call and records its outcome.
+macro_rules! metered_recover {
+    ($self:ident, $method:expr, $call:ident) => {{
+        let start = Instant::now();
+        let result = $self.inner.$call().await;
+        metrics::record_recover(&*$self.recorder, $self.rm_id, $method, start, &result);
+        result
+    }};
+}
+
+#[async_trait]
+impl<T: ResourceManager> ResourceManager for MeteredRm<T> {
+    async fn start(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::Start, id, start)
+    }
+
+    async fn start_by_joining(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::StartByJoining, id, start_by_joining)
+    }
+
+    async fn start_by_resuming(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::StartByResuming, id, start_by_resuming)
+    }
+
+    async fn end_success(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::EndSuccess, id, end_success)
+    }
+
+    async fn end_failure(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::EndFailure, id, end_failure)
+    }
+
+    async fn end_suspend(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::EndSuspend, id, end_suspend)
+    }
+
+    async fn prepare(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::Prepare, id, prepare)
+    }
+
+    async fn commit(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::Commit, id, commit)
+    }
+
+    async fn commit_one_phase(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::CommitOnePhase, id, commit_one_phase)
+    }
+
+    async fn rollback(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::Rollback, id, rollback)
+    }
+
+    async fn forget(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        metered!(self, RmMethod::Forget, id, forget)
+    }
+
+    async fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        metered_recover!(self, RmMethod::Recover, recover)
+    }
+
+    async fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        metered_recover!(self, RmMethod::BeginRecover, begin_recover)
+    }
+
+    async fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        metered_recover!(self, RmMethod::EndRecover, end_recover)
+    }
+
+    async fn prepare_vote(&mut self, id: XaTransactionId) -> Result<PrepareVote, RmError> {
+        metered!(self, RmMethod::Prepare, id, prepare_vote)
+    }
+
+    async fn commit_outcome(&mut self, id: XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        metered!(self, RmMethod::Commit, id, commit_outcome)
+    }
+
+    async fn commit_one_phase_outcome(
+        &mut self,
+        id: XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        metered!(self, RmMethod::CommitOnePhase, id, commit_one_phase_outcome)
+    }
+
+    async fn rollback_outcome(
+        &mut self,
+        id: XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        metered!(self, RmMethod::Rollback, id, rollback_outcome)
+    }
+}
@@ -0,0 +1,141 @@
+use super::ResourceManager;
+use crate::{CallLog, ReturnCode, RmError, RmMethod, XaTransactionId};
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+
+/// A `ResourceManager` for tests, which records all calls in a [`CallLog`]
+/// and returns scripted results.
+///
+/// Calls for which no result is scripted return `Ok(ReturnCode::Ok)`,
+/// the recover methods return the transaction ids given with `recovering()`.
+///
+/// ```rust
+/// use dist_tx::{CallLog, RmMethod, a_sync::{rm::MockResourceManager,
+///     tm::{SimpleTransactionManager, TransactionManager}}};
+/// # futures::executor::block_on(async {
+///
+/// let log = CallLog::new();
+/// let mut tm = SimpleTransactionManager::new("XA Demo");
+/// tm.register(Box::new(MockResourceManager::new(&log)), 1, false).await.unwrap();
+/// tm.register(Box::new(MockResourceManager::new(&log)), 2, false).await.unwrap();
+///
+/// tm.start_transaction().await.unwrap();
+/// tm.commit_transaction().await.unwrap();
+///
+/// log.assert_called_before(RmMethod::Prepare, RmMethod::Commit);
+/// log.assert_not_called(RmMethod::CommitOnePhase);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct MockResourceManager {
+    log: CallLog,
+    results: HashMap<RmMethod, VecDeque<Result<ReturnCode, RmError>>>,
+    recovered: Vec<XaTransactionId>,
+}
+impl MockResourceManager {
+    /// Creates a mock that records its calls into the given log.
+    #[must_use]
+    pub fn new(log: &CallLog) -> MockResourceManager {
+        MockResourceManager {
+            log: log.clone(),
+            results: HashMap::new(),
+            recovered: Vec::new(),
+        }
+    }
+
+    /// Scripts the result of the next call of the given method for which no result is
+    /// scripted yet.
+    ///
+    /// For the recover methods, only errors are used; `Ok` values are ignored.
+    #[must_use]
+    pub fn returning(
+        mut self,
+        method: RmMethod,
+        result: Result<ReturnCode, RmError>,
+    ) -> MockResourceManager {
+        self.results.entry(method).or_default().push_back(result);
+        self
+    }
+
+    /// Sets the transaction ids that are returned by the recover methods.
+    #[must_use]
+    pub fn recovering(mut self, xids: Vec<XaTransactionId>) -> MockResourceManager {
+        self.recovered = xids;
+        self
+    }
+
+    fn call(&mut self, method: RmMethod, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.log.record(method, Some(id));
+        self.results
+            .get_mut(&method)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or(Ok(ReturnCode::Ok))
+    }
+
+    fn call_recover(&mut self, method: RmMethod) -> Result<Vec<XaTransactionId>, RmError> {
+        self.log.record(method, None);
+        match self.results.get_mut(&method).and_then(VecDeque::pop_front) {
+            Some(Err(e)) => Err(e),
+            Some(Ok(_)) | None => Ok(self.recovered.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl ResourceManager for MockResourceManager {
+    async fn start(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Start, &id)
+    }
+
+    async fn start_by_joining(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByJoining, &id)
+    }
+
+    async fn start_by_resuming(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByResuming, &id)
+    }
+
+    async fn end_success(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuccess, &id)
+    }
+
+    async fn end_failure(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndFailure, &id)
+    }
+
+    async fn end_suspend(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuspend, &id)
+    }
+
+    async fn prepare(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Prepare, &id)
+    }
+
+    async fn commit(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Commit, &id)
+    }
+
+    async fn commit_one_phase(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::CommitOnePhase, &id)
+    }
+
+    async fn rollback(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Rollback, &id)
+    }
+
+    async fn forget(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Forget, &id)
+    }
+
+    async fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::Recover)
+    }
+
+    async fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::BeginRecover)
+    }
+
+    async fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::EndRecover)
+    }
+}
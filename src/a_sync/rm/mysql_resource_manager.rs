@@ -0,0 +1,177 @@
+use super::ResourceManager;
+use crate::{
+    mysql::{map_error, parse_recover_row, rm_error, xid_literal, RECOVER_STATEMENT},
+    ErrorCode, MySqlError, ReturnCode, RmError, XaTransactionId,
+};
+use async_trait::async_trait;
+use log::trace;
+
+/// Executes SQL statements asynchronously on a `MySQL` or `MariaDB` session,
+/// on behalf of a [`MySqlResourceManager`].
+///
+/// Implement this trait for the connection type of your `MySQL` driver.
+#[async_trait]
+pub trait MySqlExecutor: std::fmt::Debug + Send {
+    /// Executes a statement that does not return rows.
+    ///
+    /// # Errors
+    ///
+    /// `MySqlError` if the statement fails.
+    async fn execute(&mut self, sql: &str) -> Result<(), MySqlError>;
+
+    /// Executes a query and returns all rows, with all values as text.
+    ///
+    /// # Errors
+    ///
+    /// `MySqlError` if the query fails.
+    async fn query(&mut self, sql: &str) -> Result<Vec<Vec<String>>, MySqlError>;
+}
+
+/// Implementation of `ResourceManager` for `MySQL` and `MariaDB`, using their
+/// [XA statements](https://dev.mysql.com/doc/refman/8.0/en/xa-statements.html).
+///
+/// All SQL is executed with a user-supplied [`MySqlExecutor`].
+/// Every method is mapped to the respective XA statement, where the `XaTransactionId` is
+/// given as `X'gtrid',X'bqual',formatID`.
+///
+/// The server's XA errors are mapped onto `ErrorCode`, and its rollback errors
+/// (like `XA_RBDEADLOCK`) onto the respective `ReturnCode`.
+///
+/// `MySQL` knows neither `XA END ... FAIL` nor `XA FORGET`:
+/// a branch that was ended with `end_failure()` is rolled back when it is prepared or
+/// committed, and `forget()` always fails.
+#[derive(Debug)]
+pub struct MySqlResourceManager<E: MySqlExecutor> {
+    executor: E,
+    rollback_only: Option<XaTransactionId>,
+}
+
+impl<E: MySqlExecutor> MySqlResourceManager<E> {
+    /// Creates an instance that uses the given executor.
+    pub fn new(executor: E) -> MySqlResourceManager<E> {
+        MySqlResourceManager {
+            executor,
+            rollback_only: None,
+        }
+    }
+
+    /// Returns a reference to the executor.
+    pub fn executor(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    async fn xa(
+        &mut self,
+        statement: &str,
+        id: &XaTransactionId,
+        suffix: &str,
+    ) -> Result<ReturnCode, RmError> {
+        let sql = format!("XA {statement} {}{suffix}", xid_literal(id));
+        trace!("MySqlResourceManager: {sql}");
+        match self.executor.execute(&sql).await {
+            Ok(()) => Ok(ReturnCode::Ok),
+            Err(e) => map_error(e),
+        }
+    }
+
+    // Rolls the branch back if it was ended with `end_failure()`.
+    async fn rollback_if_failed(
+        &mut self,
+        id: &XaTransactionId,
+    ) -> Option<Result<ReturnCode, RmError>> {
+        match self.rollback_only.take() {
+            Some(xid) if xid == *id => Some(
+                self.xa("ROLLBACK", id, "")
+                    .await
+                    .map(|_| ReturnCode::RollbackUnspecified),
+            ),
+            other => {
+                self.rollback_only = other;
+                None
+            }
+        }
+    }
+
+    async fn recover_all(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        trace!("MySqlResourceManager: {RECOVER_STATEMENT}");
+        match self.executor.query(RECOVER_STATEMENT).await {
+            Ok(rows) => rows.iter().map(|row| parse_recover_row(row)).collect(),
+            Err(e) => Err(rm_error(e)),
+        }
+    }
+}
+
+#[async_trait]
+impl<E: MySqlExecutor> ResourceManager for MySqlResourceManager<E> {
+    async fn start(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("START", &id, "").await
+    }
+
+    async fn start_by_joining(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("START", &id, " JOIN").await
+    }
+
+    async fn start_by_resuming(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("START", &id, " RESUME").await
+    }
+
+    async fn end_success(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("END", &id, "").await
+    }
+
+    async fn end_failure(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        let result = self.xa("END", &id, "").await;
+        self.rollback_only = Some(id);
+        result
+    }
+
+    async fn end_suspend(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("END", &id, " SUSPEND").await
+    }
+
+    async fn prepare(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.rollback_if_failed(&id).await {
+            Some(result) => result,
+            None => self.xa("PREPARE", &id, "").await,
+        }
+    }
+
+    async fn commit(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.rollback_if_failed(&id).await {
+            Some(result) => result,
+            None => self.xa("COMMIT", &id, "").await,
+        }
+    }
+
+    async fn commit_one_phase(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.rollback_if_failed(&id).await {
+            Some(result) => result,
+            None => self.xa("COMMIT", &id, " ONE PHASE").await,
+        }
+    }
+
+    async fn rollback(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.rollback_if_failed(&id).await;
+        self.xa("ROLLBACK", &id, "").await
+    }
+
+    async fn forget(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        Err(RmError::new(
+            ErrorCode::InvalidTransactionId,
+            format!("transaction branch {id:?} was not heuristically completed"),
+        ))
+    }
+
+    async fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all().await
+    }
+
+    async fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all().await
+    }
+
+    async fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        // the complete list is always returned with the start of the scan
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,230 @@
+use super::ResourceManager;
+use crate::{ErrorCode, ReturnCode, RmError, XaTransactionId};
+use async_trait::async_trait;
+use log::trace;
+
+/// Executes SQL statements asynchronously on a `PostgreSQL` session, on behalf of a [`PgResourceManager`].
+///
+/// Implement this trait for the connection type of your `PostgreSQL` driver.
+/// The connection must not be in automatic commit mode
+/// while a transaction branch is active.
+#[async_trait]
+pub trait PgExecutor: std::fmt::Debug + Send {
+    /// Executes a statement that does not return rows.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if the statement fails.
+    async fn execute(&mut self, sql: &str) -> Result<(), RmError>;
+
+    /// Executes a query and returns the values of its first column, as text.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if the query fails.
+    async fn query_column(&mut self, sql: &str) -> Result<Vec<String>, RmError>;
+}
+
+/// Implementation of `ResourceManager` for `PostgreSQL`, using its
+/// [two-phase commit statements](https://www.postgresql.org/docs/current/sql-prepare-transaction.html).
+///
+/// All SQL is executed with a user-supplied [`PgExecutor`], so that any `PostgreSQL` driver
+/// can be enlisted without knowing about `dist_tx`.
+///
+/// * `start()` executes `BEGIN`
+/// * `prepare()` executes `PREPARE TRANSACTION 'gid'`,
+///   where the gid is produced with [`XaTransactionId::to_gid`]
+/// * `commit()` and `rollback()` execute `COMMIT PREPARED 'gid'` and `ROLLBACK PREPARED 'gid'`
+///   for prepared branches, `commit_one_phase()` and `rollback()` of a branch that is not
+///   yet prepared execute `COMMIT` and `ROLLBACK`
+/// * `recover()` reads the view `pg_prepared_xacts` of the current database,
+///   and ignores all gids that were not produced with [`XaTransactionId::to_gid`]
+///
+/// `PostgreSQL` does not complete transactions heuristically, so `forget()` always fails.
+#[derive(Debug)]
+pub struct PgResourceManager<E: PgExecutor> {
+    executor: E,
+    branch: Option<(XaTransactionId, BranchState)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BranchState {
+    Active,
+    Suspended,
+    Idle,
+    RollbackOnly,
+}
+
+const RECOVER_QUERY: &str =
+    "SELECT gid FROM pg_prepared_xacts WHERE database = current_database() ORDER BY prepared";
+
+impl<E: PgExecutor> PgResourceManager<E> {
+    /// Creates an instance that uses the given executor.
+    pub fn new(executor: E) -> PgResourceManager<E> {
+        PgResourceManager {
+            executor,
+            branch: None,
+        }
+    }
+
+    /// Returns a reference to the executor.
+    pub fn executor(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    async fn execute(&mut self, sql: &str) -> Result<ReturnCode, RmError> {
+        trace!("PgResourceManager: {sql}");
+        self.executor.execute(sql).await.map(|()| ReturnCode::Ok)
+    }
+
+    fn current_state(&self, id: &XaTransactionId) -> Option<BranchState> {
+        match self.branch {
+            Some((ref xid, state)) if xid == id => Some(state),
+            _ => None,
+        }
+    }
+
+    // Changes the state of the current branch, if it is in the required state.
+    fn transition(
+        &mut self,
+        id: &XaTransactionId,
+        required: BranchState,
+        new: BranchState,
+    ) -> Result<ReturnCode, RmError> {
+        match self.current_state(id) {
+            Some(state) if state == required => {
+                self.branch = Some((*id, new));
+                Ok(ReturnCode::Ok)
+            }
+            Some(_) => Err(protocol_error(id, "is in the wrong state")),
+            None => Err(protocol_error(id, "is not associated with this session")),
+        }
+    }
+
+    async fn recover_all(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        trace!("PgResourceManager: {RECOVER_QUERY}");
+        Ok(self
+            .executor
+            .query_column(RECOVER_QUERY)
+            .await?
+            .iter()
+            .filter_map(|gid| XaTransactionId::from_gid(gid).ok())
+            .collect())
+    }
+}
+
+#[async_trait]
+impl<E: PgExecutor> ResourceManager for PgResourceManager<E> {
+    async fn start(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        if self.branch.is_some() {
+            return Err(protocol_error(
+                &id,
+                "cannot start, a branch is already in use",
+            ));
+        }
+        self.execute("BEGIN").await?;
+        self.branch = Some((id, BranchState::Active));
+        Ok(ReturnCode::Ok)
+    }
+
+    async fn start_by_joining(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(&id, BranchState::Idle, BranchState::Active)
+    }
+
+    async fn start_by_resuming(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(&id, BranchState::Suspended, BranchState::Active)
+    }
+
+    async fn end_success(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(&id, BranchState::Active, BranchState::Idle)
+    }
+
+    async fn end_failure(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(&id, BranchState::Active, BranchState::RollbackOnly)
+    }
+
+    async fn end_suspend(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(&id, BranchState::Active, BranchState::Suspended)
+    }
+
+    async fn prepare(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.current_state(&id) {
+            Some(BranchState::Idle) => {
+                // the session is disassociated from the transaction in any case
+                self.branch = None;
+                self.execute(&format!("PREPARE TRANSACTION '{}'", id.to_gid()))
+                    .await
+            }
+            Some(BranchState::RollbackOnly) => {
+                self.branch = None;
+                self.execute("ROLLBACK").await?;
+                Ok(ReturnCode::RollbackUnspecified)
+            }
+            Some(_) => Err(protocol_error(&id, "is not ended")),
+            None => Err(protocol_error(&id, "is not associated with this session")),
+        }
+    }
+
+    async fn commit(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        if self.current_state(&id).is_some() {
+            return Err(protocol_error(&id, "is not prepared"));
+        }
+        self.execute(&format!("COMMIT PREPARED '{}'", id.to_gid()))
+            .await
+    }
+
+    async fn commit_one_phase(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.current_state(&id) {
+            Some(BranchState::Idle) => {
+                self.branch = None;
+                self.execute("COMMIT").await
+            }
+            Some(BranchState::RollbackOnly) => {
+                self.branch = None;
+                self.execute("ROLLBACK").await?;
+                Ok(ReturnCode::RollbackUnspecified)
+            }
+            Some(_) => Err(protocol_error(&id, "is not ended")),
+            None => Err(protocol_error(&id, "is not associated with this session")),
+        }
+    }
+
+    async fn rollback(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        if self.current_state(&id).is_some() {
+            self.branch = None;
+            self.execute("ROLLBACK").await
+        } else {
+            self.execute(&format!("ROLLBACK PREPARED '{}'", id.to_gid()))
+                .await
+        }
+    }
+
+    async fn forget(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        Err(RmError::new(
+            ErrorCode::InvalidTransactionId,
+            format!("transaction branch {id:?} was not heuristically completed"),
+        )
+        .with_xid(id))
+    }
+
+    async fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all().await
+    }
+
+    async fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all().await
+    }
+
+    async fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        // the complete list is always returned with the start of the scan
+        Ok(Vec::new())
+    }
+}
+
+fn protocol_error(id: &XaTransactionId, s: &str) -> RmError {
+    RmError::new(
+        ErrorCode::ProtocolError,
+        format!("transaction branch {id:?} {s}"),
+    )
+    .with_xid(*id)
+}
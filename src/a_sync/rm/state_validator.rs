@@ -0,0 +1,119 @@
+use super::ResourceManager;
+use crate::{
+    branch_state::BranchStates, BranchState, ReturnCode, RmError, RmMethod, ValidationMode,
+    XaTransactionId,
+};
+use async_trait::async_trait;
+
+/// Wraps a `ResourceManager` and tracks the [`BranchState`] of each transaction branch,
+/// to detect call sequences that are illegal according to the XA specification,
+/// like a `commit` without `prepare`, or a `start` for an active branch.
+///
+/// Depending on the [`ValidationMode`], an illegal call is rejected with
+/// `ErrorCode::ProtocolError`, or logged and forwarded.
+/// The wrapper helps finding bugs in transaction managers and in resource managers;
+/// wrap a `CResourceManager` into `CRmWrapper` first to validate it.
+#[derive(Debug)]
+pub struct StateValidator<T: ResourceManager> {
+    inner: T,
+    mode: ValidationMode,
+    states: BranchStates,
+}
+impl<T: ResourceManager> StateValidator<T> {
+    /// Wraps the given resource manager.
+    pub fn new(inner: T, mode: ValidationMode) -> StateValidator<T> {
+        StateValidator {
+            inner,
+            mode,
+            states: BranchStates::default(),
+        }
+    }
+
+    /// Returns the current state of the given branch.
+    pub fn state(&self, id: &XaTransactionId) -> BranchState {
+        self.states.get(id)
+    }
+
+    /// Returns the wrapped resource manager.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+// Checks the call, forwards it, and updates the state of the branch with the result.
+macro_rules! validated {
+    ($self:ident, $method:expr, $id:ident, $call:ident) => {{
+        $self.states.check($self.mode, $method, &$id)?;
+        let result = $self.inner.$call($id).await;
+        $self.states.update($method, &$id, &result);
+        result
+    }};
+}
+
+// Forwards a recover call and registers the found branches.
+macro_rules! recovered {
+    ($self:ident, $call:ident) => {{
+        let result = $self.inner.$call().await;
+        $self.states.recovered(&result);
+        result
+    }};
+}
+
+#[async_trait]
+impl<T: ResourceManager> ResourceManager for StateValidator<T> {
+    async fn start(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::Start, id, start)
+    }
+
+    async fn start_by_joining(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::StartByJoining, id, start_by_joining)
+    }
+
+    async fn start_by_resuming(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::StartByResuming, id, start_by_resuming)
+    }
+
+    async fn end_success(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::EndSuccess, id, end_success)
+    }
+
+    async fn end_failure(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::EndFailure, id, end_failure)
+    }
+
+    async fn end_suspend(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::EndSuspend, id, end_suspend)
+    }
+
+    async fn prepare(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::Prepare, id, prepare)
+    }
+
+    async fn commit(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::Commit, id, commit)
+    }
+
+    async fn commit_one_phase(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::CommitOnePhase, id, commit_one_phase)
+    }
+
+    async fn rollback(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::Rollback, id, rollback)
+    }
+
+    async fn forget(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        validated!(self, RmMethod::Forget, id, forget)
+    }
+
+    async fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        recovered!(self, recover)
+    }
+
+    async fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        recovered!(self, begin_recover)
+    }
+
+    async fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        recovered!(self, end_recover)
+    }
+}
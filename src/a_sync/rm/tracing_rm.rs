@@ -0,0 +1,135 @@
+use super::ResourceManager;
+use crate::{
+    xa_span, CompletionOutcome, PrepareVote, ReturnCode, RmError, RmMethod, XaTransactionId,
+};
+use async_trait::async_trait;
+use std::time::Instant;
+use tracing::Instrument;
+
+/// Wraps a `ResourceManager` and opens a [tracing](https://docs.rs/tracing) span
+/// for each XA call.
+///
+/// The span `xa_call` has the fields `method`, `rm_id`, `gtid` and `bqual` (both hex-encoded),
+/// `duration_us`, and, depending on the outcome, `return_code`, `error_code`, or,
+/// for the recover methods, `recovered`.
+/// `SimpleTransactionManager` opens a parent span `global_transaction` for each
+/// global transaction, so that the calls of all branches form a tree.
+#[derive(Debug)]
+pub struct TracingRm<T: ResourceManager> {
+    inner: T,
+    rm_id: u64,
+}
+impl<T: ResourceManager> TracingRm<T> {
+    /// Wraps the given resource manager; the `rm_id` is added to the spans.
+    pub fn new(inner: T, rm_id: u64) -> TracingRm<T> {
+        TracingRm { inner, rm_id }
+    }
+
+    /// Returns the wrapped resource manager.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+// Runs the call within its span, and records the outcome.
+macro_rules! traced {
+    ($self:ident, $method:expr, $id:ident, $call:ident) => {{
+        let span = xa_span::xa_call($method, Some(&$id), $self.rm_id);
+        let start = Instant::now();
+        let result = $self.inner.$call($id).instrument(span.clone()).await;
+        xa_span::record_result(&span, start, &result);
+        result
+    }};
+}
+
+// Runs the recover call within its span, and records the outcome.
+macro_rules! traced_recover {
+    ($self:ident, $method:expr, $call:ident) => {{
+        let span = xa_span::xa_call($method, None, $self.rm_id);
+        let start = Instant::now();
+        let result = $self.inner.$call().instrument(span.clone()).await;
+        xa_span::record_recover(&span, start, &result);
+        result
+    }};
+}
+
+#[async_trait]
+impl<T: ResourceManager> ResourceManager for TracingRm<T> {
+    async fn start(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::Start, id, start)
+    }
+
+    async fn start_by_joining(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::StartByJoining, id, start_by_joining)
+    }
+
+    async fn start_by_resuming(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::StartByResuming, id, start_by_resuming)
+    }
+
+    async fn end_success(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::EndSuccess, id, end_success)
+    }
+
+    async fn end_failure(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::EndFailure, id, end_failure)
+    }
+
+    async fn end_suspend(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::EndSuspend, id, end_suspend)
+    }
+
+    async fn prepare(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::Prepare, id, prepare)
+    }
+
+    async fn commit(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::Commit, id, commit)
+    }
+
+    async fn commit_one_phase(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::CommitOnePhase, id, commit_one_phase)
+    }
+
+    async fn rollback(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::Rollback, id, rollback)
+    }
+
+    async fn forget(&mut self, id: XaTransactionId) -> Result<ReturnCode, RmError> {
+        traced!(self, RmMethod::Forget, id, forget)
+    }
+
+    async fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        traced_recover!(self, RmMethod::Recover, recover)
+    }
+
+    async fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        traced_recover!(self, RmMethod::BeginRecover, begin_recover)
+    }
+
+    async fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        traced_recover!(self, RmMethod::EndRecover, end_recover)
+    }
+
+    async fn prepare_vote(&mut self, id: XaTransactionId) -> Result<PrepareVote, RmError> {
+        traced!(self, RmMethod::Prepare, id, prepare_vote)
+    }
+
+    async fn commit_outcome(&mut self, id: XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        traced!(self, RmMethod::Commit, id, commit_outcome)
+    }
+
+    async fn commit_one_phase_outcome(
+        &mut self,
+        id: XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        traced!(self, RmMethod::CommitOnePhase, id, commit_one_phase_outcome)
+    }
+
+    async fn rollback_outcome(
+        &mut self,
+        id: XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        traced!(self, RmMethod::Rollback, id, rollback_outcome)
+    }
+}
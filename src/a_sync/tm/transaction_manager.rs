@@ -5,7 +5,7 @@ use crate::{a_sync::rm::ResourceManager, XaError};
 
 /// A transaction manager for distributed transactions.
 ///
-/// Use register()/unregister() to define the set of resource managers
+/// Use `register()/unregister()` to define the set of resource managers
 /// you want to (potentially) take part in subsequent transactions.
 ///
 /// Then use `start_transaction()` to start a transaction. The rest is done on the
@@ -20,6 +20,11 @@ pub trait TransactionManager {
     /// here a `Box<Box<ResourceManagerImpl>>`.
     /// Note that each registration must use a different `rm_id` - overwrites will not be allowed.
     ///
+    /// With `cleanup`, the branches that the resource manager still holds prepared for this
+    /// transaction manager are completed, as in a recovery: those with a pending commit decision
+    /// are committed, all others are rolled back. They are not just forgotten, which would leave
+    /// them prepared.
+    ///
     /// # Errors
     ///
     /// `XaError` if the request cannot be handled regularily.
@@ -96,6 +101,11 @@ pub trait TransactionManager {
 bitflags::bitflags! {
     /// States of a `TransactionManager`.
     #[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(transparent)
+    )]
     pub struct Status: u32 {
         /// No transaction in use.
         const IDLE = 0x00_00_00_01;
@@ -0,0 +1,535 @@
+use crate::{ErrorCode, ReturnCode, TmEvent};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const NO_HASH: [u8; 32] = [0; 32];
+
+/// Appends a hash-chained record of each commit or rollback decision to a file.
+///
+/// See [`sync::tm::SimpleTransactionManager::with_audit_log`](crate::sync::tm::SimpleTransactionManager::with_audit_log)
+/// and [`a_sync::tm::SimpleTransactionManager::with_audit_log`](crate::a_sync::tm::SimpleTransactionManager::with_audit_log);
+/// use [`AuditReader`] to verify and read the records.
+///
+/// # Format
+///
+/// The audit log is a text file to which one line per decision is appended.
+/// Each line consists of space-separated fields in this order:
+///
+/// ```text
+/// seq=3 time=1729240000123 tm=8093 gtid=17 outcome=commit rms=1,2 votes=1:Ok,2:ReadOnlyCommitted prev=<hex> hash=<hex>
+/// ```
+///
+/// * `seq`: number of the record, starting with 1, without gaps
+/// * `time`: milliseconds since the UNIX epoch when the decision was taken
+/// * `tm`: id of the transaction manager
+/// * `gtid`: global transaction id
+/// * `outcome`: `commit` or `rollback`
+/// * `rms`: comma-separated ids of the participating resource managers
+/// * `votes`: comma-separated answers to the prepare requests, as `<rm_id>:<ReturnCode>`,
+///   or `<rm_id>:error:<ErrorCode>` if the request failed; empty for one-phase commits
+///   and for rollbacks before the prepare phase
+/// * `prev`: the `hash` of the previous record, or 64 zeros for the first record
+/// * `hash`: the hex-encoded SHA-256 hash of the line up to, but not including, ` hash=`
+///
+/// As each record contains the hash of its predecessor, modifying, removing, or inserting
+/// a record breaks the chain, unless all subsequent records are rewritten, too.
+/// A later record for the same transaction supersedes an earlier one (e.g., if a commit
+/// decision could not be executed and the transaction was rolled back).
+///
+/// A line without a trailing newline was torn by a crash and is discarded.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: File,
+    last_seq: u64,
+    last_hash: [u8; 32],
+    tm_id: u64,
+    // the transaction whose decision is pending
+    global_tid: Option<u64>,
+    rm_ids: Vec<u64>,
+    votes: Vec<(u64, String)>,
+}
+impl AuditLog {
+    /// Opens the audit log in the given file, and creates it if it does not exist.
+    ///
+    /// The existing records are verified, new records continue their chain.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the file cannot be read or written,
+    /// or if the existing records do not form a valid chain.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<AuditLog> {
+        let path = path.as_ref().to_path_buf();
+        let (mut last_seq, mut last_hash, mut valid_len) = (0, NO_HASH, 0);
+        if path.exists() {
+            let mut reader = AuditReader::open(&path)?;
+            for record in &mut reader {
+                let record = record?;
+                last_seq = record.sequence;
+                last_hash = record.hash;
+            }
+            valid_len = reader.valid_len;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        // discard a torn record
+        if file.metadata()?.len() > valid_len {
+            file.set_len(valid_len)?;
+        }
+        Ok(AuditLog {
+            path,
+            file,
+            last_seq,
+            last_hash,
+            tm_id: 0,
+            global_tid: None,
+            rm_ids: Vec::new(),
+            votes: Vec::new(),
+        })
+    }
+
+    /// Returns the path of the audit log file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn set_tm_id(&mut self, tm_id: u64) {
+        self.tm_id = tm_id;
+    }
+
+    // Collects the participants and votes of the current transaction.
+    pub(crate) fn observe(&mut self, event: &TmEvent) {
+        match event {
+            TmEvent::BranchStarted { global_tid, rm_id } => {
+                if self.global_tid != Some(*global_tid) {
+                    self.global_tid = Some(*global_tid);
+                    self.rm_ids.clear();
+                    self.votes.clear();
+                }
+                self.rm_ids.push(*rm_id);
+            }
+            TmEvent::PrepareVote {
+                global_tid,
+                rm_id,
+                vote,
+            } if self.global_tid == Some(*global_tid) => {
+                self.votes.push((*rm_id, vote_to_string(vote)));
+            }
+            _ => {}
+        }
+    }
+
+    // Durably appends the record of a decision.
+    pub(crate) fn record_decision(&mut self, global_tid: u64, commit: bool) -> std::io::Result<()> {
+        let (rm_ids, votes) = if self.global_tid == Some(global_tid) {
+            (&self.rm_ids[..], &self.votes[..])
+        } else {
+            (&[][..], &[][..])
+        };
+        let seq = self.last_seq + 1;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let mut line = format!(
+            "seq={seq} time={time} tm={} gtid={global_tid} outcome={} rms={} votes={} prev={}",
+            self.tm_id,
+            if commit { "commit" } else { "rollback" },
+            join(rm_ids.iter().map(u64::to_string)),
+            join(votes.iter().map(|(rm_id, vote)| format!("{rm_id}:{vote}"))),
+            hex(&self.last_hash),
+        );
+        let hash = hash(&line);
+        let _ = writeln!(line, " hash={}", hex(&hash));
+
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+        self.last_seq = seq;
+        self.last_hash = hash;
+        Ok(())
+    }
+}
+
+/// A record of the audit log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// Number of the record, starting with 1.
+    pub sequence: u64,
+    /// Time when the decision was taken.
+    pub timestamp: SystemTime,
+    /// Id of the transaction manager.
+    pub tm_id: u64,
+    /// Global transaction id.
+    pub global_tid: u64,
+    /// `true` if the transaction was committed, `false` if it was rolled back.
+    pub commit: bool,
+    /// The participating resource managers.
+    pub rm_ids: Vec<u64>,
+    /// The answers of the resource managers to the prepare requests,
+    /// as `ReturnCode`, or as `error:<ErrorCode>`.
+    pub votes: Vec<(u64, String)>,
+    /// The SHA-256 hash of the record.
+    pub hash: [u8; 32],
+}
+
+/// Reads an audit log, verifies its hash chain, and iterates over its records.
+///
+/// The iterator stops with an error of kind `std::io::ErrorKind::InvalidData`
+/// at the first record that is malformed or breaks the chain.
+///
+/// ```rust,no_run
+/// use dist_tx::AuditReader;
+///
+/// for record in AuditReader::open("audit.log").unwrap() {
+///     let record = record.unwrap();
+///     println!("{}: {}", record.global_tid, record.commit);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct AuditReader {
+    reader: BufReader<File>,
+    last_seq: u64,
+    last_hash: [u8; 32],
+    valid_len: u64,
+    failed: bool,
+}
+impl AuditReader {
+    /// Opens the audit log in the given file.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the file cannot be opened.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<AuditReader> {
+        Ok(AuditReader {
+            reader: BufReader::new(File::open(path)?),
+            last_seq: 0,
+            last_hash: NO_HASH,
+            valid_len: 0,
+            failed: false,
+        })
+    }
+
+    /// Reads all records and verifies the chain, and returns the number of records.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the file cannot be read, or if the chain is broken.
+    pub fn verify<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+        let mut reader = AuditReader::open(path)?;
+        for record in &mut reader {
+            record?;
+        }
+        Ok(reader.last_seq)
+    }
+
+    fn verify_line(&mut self, line: &str) -> std::io::Result<AuditRecord> {
+        let invalid = |reason: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("audit record {}: {reason}", self.last_seq + 1),
+            )
+        };
+        let (content, hash_hex) = line
+            .rsplit_once(" hash=")
+            .ok_or_else(|| invalid("hash is missing"))?;
+        let record = parse_record(content, hash_hex).ok_or_else(|| invalid("malformed"))?;
+        if record.sequence != self.last_seq + 1 {
+            return Err(invalid("unexpected sequence number"));
+        }
+        if !content.ends_with(&format!(" prev={}", hex(&self.last_hash))) {
+            return Err(invalid("chain is broken"));
+        }
+        if hash(content) != record.hash {
+            return Err(invalid("hash does not match"));
+        }
+        Ok(record)
+    }
+}
+impl Iterator for AuditReader {
+    type Item = std::io::Result<AuditRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut line = String::new();
+        let result = match self.reader.read_line(&mut line) {
+            // a line without newline was torn by a crash and is ignored
+            Ok(_) if !line.ends_with('\n') => return None,
+            Ok(len) => self
+                .verify_line(line.trim_end_matches('\n'))
+                .map(|r| (r, len)),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok((record, len)) => {
+                self.last_seq = record.sequence;
+                self.last_hash = record.hash;
+                self.valid_len += len as u64;
+                Some(Ok(record))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn parse_record(content: &str, hash_hex: &str) -> Option<AuditRecord> {
+    let mut fields = content.split(' ');
+    let mut field = |name: &str| fields.next()?.strip_prefix(name)?.strip_prefix('=');
+    let sequence = field("seq")?.parse().ok()?;
+    let millis = field("time")?.parse().ok()?;
+    let tm_id = field("tm")?.parse().ok()?;
+    let global_tid = field("gtid")?.parse().ok()?;
+    let commit = match field("outcome")? {
+        "commit" => true,
+        "rollback" => false,
+        _ => return None,
+    };
+    let rm_ids = split_list(field("rms")?)
+        .map(|rm_id| rm_id.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    let votes = split_list(field("votes")?)
+        .map(|vote| {
+            let (rm_id, vote) = vote.split_once(':')?;
+            Some((rm_id.parse().ok()?, vote.to_string()))
+        })
+        .collect::<Option<Vec<(u64, String)>>>()?;
+    field("prev")?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(AuditRecord {
+        sequence,
+        timestamp: UNIX_EPOCH + Duration::from_millis(millis),
+        tm_id,
+        global_tid,
+        commit,
+        rm_ids,
+        votes,
+        hash: unhex(hash_hex)?,
+    })
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').filter(|s| !s.is_empty())
+}
+
+fn join<I: Iterator<Item = String>>(items: I) -> String {
+    items.collect::<Vec<String>>().join(",")
+}
+
+fn vote_to_string(vote: &Result<ReturnCode, ErrorCode>) -> String {
+    match vote {
+        Ok(rc) => format!("{rc:?}"),
+        Err(ec) => format!("error:{ec:?}"),
+    }
+}
+
+fn hash(content: &str) -> [u8; 32] {
+    Sha256::digest(content.as_bytes()).into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+fn unhex(s: &str) -> Option<[u8; 32]> {
+    let mut bytes = [0_u8; 32];
+    if s.len() != 64 {
+        return None;
+    }
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(s.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditLog, AuditReader};
+    use crate::file_store::tests::test_dir;
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_decisions_are_chained() {
+        use crate::{
+            sync::{
+                rm::MockResourceManager,
+                tm::{SimpleTransactionManager, TransactionManager},
+            },
+            CallLog, ReturnCode, RmMethod,
+        };
+        use std::io::Write;
+
+        let dir = test_dir("audit_log");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.log");
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::new("test_decisions_are_chained")
+            .with_audit_log(AuditLog::open(&path).unwrap());
+        tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+            .unwrap();
+        let rm = MockResourceManager::new(&log)
+            .returning(RmMethod::Prepare, Ok(ReturnCode::Ok))
+            .returning(RmMethod::Prepare, Ok(ReturnCode::RollbackDeadlock));
+        tm.register(Box::new(rm), 2, false).unwrap();
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap_err();
+        drop(tm);
+
+        // a torn record is discarded, and the chain is continued
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"seq=3 time=")
+            .unwrap();
+        let mut audit_log = AuditLog::open(&path).unwrap();
+        audit_log.record_decision(3, false).unwrap();
+
+        let records = AuditReader::open(&path)
+            .unwrap()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(records[0].commit);
+        assert_eq!(records[0].rm_ids, vec![1, 2]);
+        assert_eq!(
+            records[0].votes,
+            vec![(1, "Ok".to_string()), (2, "Ok".to_string())]
+        );
+        assert!(!records[1].commit);
+        assert_eq!(records[1].votes[1], (2, "RollbackDeadlock".to_string()));
+        assert_eq!(records[2].global_tid, 3);
+        assert!(records[2].rm_ids.is_empty());
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_one_phase_outcome_is_recorded() {
+        use crate::{
+            sync::{
+                rm::{FaultInjector, MockResourceManager},
+                tm::{SimpleTransactionManager, TransactionManager},
+            },
+            CallLog, Fault, FaultPlan, ReturnCode, RmMethod,
+        };
+
+        let dir = test_dir("audit_log");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.log");
+        let log = CallLog::new();
+        let plan = FaultPlan::new().on_call(
+            RmMethod::CommitOnePhase,
+            2,
+            Fault::Return(ReturnCode::RollbackDeadlock),
+        );
+        let mut tm = SimpleTransactionManager::new("test_one_phase_outcome_is_recorded")
+            .with_audit_log(AuditLog::open(&path).unwrap());
+        let rm = FaultInjector::new(MockResourceManager::new(&log), plan);
+        tm.register(Box::new(rm), 1, false).unwrap();
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap_err();
+        drop(tm);
+
+        let records = AuditReader::open(&path)
+            .unwrap()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].commit);
+        assert_eq!(records[1].rm_ids, vec![1]);
+        assert!(!records[1].commit);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_failed_audit_prevents_the_commit() {
+        use crate::{
+            sync::{
+                rm::MockResourceManager,
+                tm::{SimpleTransactionManager, TransactionManager},
+            },
+            CallLog, FileTmLog, RmMethod, TmEvent,
+        };
+        use std::sync::{Arc, Mutex};
+
+        let dir = test_dir("audit_log");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.log");
+        let log_path = dir.join("tm.log");
+        let mut audit_log = AuditLog::open(&path).unwrap();
+        // the file is read-only, so that all records fail
+        audit_log.file = std::fs::File::open(&path).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::<bool>::new()));
+        let events2 = Arc::clone(&events);
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::new("test_failed_audit_prevents_the_commit")
+            .with_tm_log(FileTmLog::open(&log_path).unwrap())
+            .with_audit_log(audit_log);
+        tm.add_listener(move |event: &TmEvent| {
+            if let TmEvent::DecisionMade { commit, .. } = event {
+                events2.lock().unwrap().push(*commit);
+            }
+        });
+        for rm_id in 1..=2 {
+            tm.register(Box::new(MockResourceManager::new(&log)), rm_id, false)
+                .unwrap();
+        }
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap_err();
+        drop(tm);
+
+        log.assert_not_called(RmMethod::Commit);
+        log.assert_called_before(RmMethod::Prepare, RmMethod::Rollback);
+        // the commit was neither logged nor reported
+        assert_eq!(std::fs::read_to_string(&log_path).unwrap(), "");
+        assert_eq!(*events.lock().unwrap(), [false]);
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let dir = test_dir("audit_log");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.log");
+        let mut audit_log = AuditLog::open(&path).unwrap();
+        for gtid in 1..=3 {
+            audit_log.record_decision(gtid, true).unwrap();
+        }
+        assert_eq!(AuditReader::verify(&path).unwrap(), 3);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(
+            &path,
+            content.replacen("gtid=2 outcome=commit", "gtid=2 outcome=rollback", 1),
+        )
+        .unwrap();
+        assert!(AuditReader::verify(&path).is_err());
+        assert!(AuditLog::open(&path).is_err());
+
+        let mut lines: Vec<&str> = content.lines().collect();
+        lines.remove(1);
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let records: Vec<_> = AuditReader::open(&path).unwrap().collect();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].is_ok());
+        assert!(records[1].is_err());
+    }
+}
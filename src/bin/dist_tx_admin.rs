@@ -0,0 +1,37 @@
+//! Administrative tool for the manual resolution of in-doubt transaction branches.
+//!
+//! ```text
+//! dist_tx_admin --config <file> list
+//! dist_tx_admin --config <file> (commit|rollback|forget) <rm_id> <gid> [--yes]
+//! ```
+//!
+//! See [`Admin::from_config`] for the format of the configuration file,
+//! and [`run_command`] for the commands.
+use dist_tx::sync::tm::{run_command, Admin};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match &args[..] {
+        [flag, config, command @ ..] if flag == "--config" => {
+            Admin::from_config(config).and_then(|mut admin| {
+                run_command(
+                    &mut admin,
+                    command,
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stdout(),
+                )
+            })
+        }
+        _ => {
+            eprintln!("usage: dist_tx_admin --config <file> <command>");
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+        if let dist_tx::XaError::UsageDetails(details) = e {
+            eprintln!("{details}");
+        }
+        std::process::exit(1);
+    }
+}
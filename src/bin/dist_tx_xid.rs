@@ -0,0 +1,82 @@
+//! Converts transaction ids between their representations, and decodes them.
+//!
+//! ```text
+//! dist_tx_xid [--from <format>] [--to <format>] [<xid>...]
+//! ```
+//!
+//! The formats are `struct`, `hex`, `base64`, `gid`, and `mysql` (see [`XidFormat`]).
+//! Without `--from`, the format of each xid is guessed; without `--to`, the xid is shown
+//! in all formats, together with its fields, and its parts if it was produced by a
+//! `SimpleTransactionManager`. Without xids, they are read line by line from stdin.
+use dist_tx::{SimpleXid, XaError, XaTransactionId, XidFormat};
+use std::io::BufRead;
+
+fn main() {
+    let mut from = None;
+    let mut to = None;
+    let mut xids = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = Some(format_arg(args.next())),
+            "--to" => to = Some(format_arg(args.next())),
+            _ => xids.push(arg),
+        }
+    }
+    if xids.is_empty() {
+        xids = std::io::stdin()
+            .lock()
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+    }
+
+    let mut failed = false;
+    for s in &xids {
+        let format = from.unwrap_or_else(|| XidFormat::detect(s));
+        match format.decode(s) {
+            Ok(xid) => match to {
+                Some(to) => println!("{}", to.encode(&xid)),
+                None => describe(format, &xid),
+            },
+            Err(e) => {
+                failed = true;
+                match e {
+                    XaError::ReadXid(details) => eprintln!("error: {details}"),
+                    e => eprintln!("error: {e} ({format}: {s})"),
+                }
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn format_arg(name: Option<String>) -> XidFormat {
+    if let Some(format) = name.as_deref().and_then(XidFormat::from_name) {
+        format
+    } else {
+        eprintln!("usage: dist_tx_xid [--from <format>] [--to <format>] [<xid>...]");
+        eprintln!("formats: struct, hex, base64, gid, mysql");
+        std::process::exit(2);
+    }
+}
+
+fn describe(input_format: XidFormat, xid: &XaTransactionId) {
+    println!("read as {input_format}:");
+    println!("  format_id:        {}", xid.get_format_id());
+    println!("  global_tid:       {} bytes", xid.get_global_tid().len());
+    println!(
+        "  branch_qualifier: {} bytes",
+        xid.get_branch_qualifier().len()
+    );
+    match SimpleXid::decode(xid) {
+        Some(parts) => println!("  {parts}"),
+        None => println!("  (not from a SimpleTransactionManager)"),
+    }
+    for format in XidFormat::ALL {
+        println!("  {format:<7} {}", format.encode(xid));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{ErrorCode, ReturnCode, RmError, RmMethod, XaTransactionId};
+use log::warn;
+use std::collections::HashMap;
+
+/// The state of a transaction branch, as defined by the XA specification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BranchState {
+    /// The resource manager does not know the branch.
+    NonExistent,
+    /// The branch is associated with a thread of control.
+    Active,
+    /// The association was ended successfully; the branch can be prepared or committed.
+    Idle,
+    /// The association is suspended and can be resumed.
+    Suspended,
+    /// The branch is prepared and waits for commit or rollback.
+    Prepared,
+    /// The branch can only be rolled back.
+    RollbackOnly,
+    /// The branch was completed heuristically and must be forgotten.
+    HeuristicallyCompleted,
+}
+
+/// How a `StateValidator`
+/// ([`sync::rm::StateValidator`](crate::sync::rm::StateValidator) or
+/// [`a_sync::rm::StateValidator`](crate::a_sync::rm::StateValidator))
+/// handles calls that are illegal in the current state of the branch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// The call is not forwarded, and fails with `ErrorCode::ProtocolError`.
+    Reject,
+    /// The violation is logged as warning, and the call is forwarded.
+    Log,
+}
+
+// The states of all branches that are not in state `NonExistent`.
+#[derive(Debug, Default)]
+pub(crate) struct BranchStates(HashMap<XaTransactionId, BranchState>);
+impl BranchStates {
+    pub(crate) fn get(&self, id: &XaTransactionId) -> BranchState {
+        self.0.get(id).copied().unwrap_or(BranchState::NonExistent)
+    }
+
+    // Returns an error if the call is illegal in the current state of the branch
+    // and is to be rejected.
+    pub(crate) fn check(
+        &self,
+        mode: ValidationMode,
+        method: RmMethod,
+        id: &XaTransactionId,
+    ) -> Result<(), RmError> {
+        let state = self.get(id);
+        if is_legal(method, state) {
+            return Ok(());
+        }
+        let msg = format!("{method:?} is illegal for branch {id:?} in state {state:?}");
+        match mode {
+            ValidationMode::Reject => Err(RmError::new(ErrorCode::ProtocolError, msg)),
+            ValidationMode::Log => {
+                warn!("{msg}");
+                Ok(())
+            }
+        }
+    }
+
+    // Applies the result of a call to the state of the branch.
+    pub(crate) fn update(
+        &mut self,
+        method: RmMethod,
+        id: &XaTransactionId,
+        result: &Result<ReturnCode, RmError>,
+    ) {
+        let Ok(rc) = result else {
+            // failed calls leave the branch unchanged
+            return;
+        };
+        let state = self.get(id);
+        let new_state = match (method, rc) {
+            (_, ReturnCode::Retry) => state,
+            (
+                RmMethod::Commit | RmMethod::CommitOnePhase | RmMethod::Rollback,
+                ReturnCode::HeuristicallyCompleted
+                | ReturnCode::HeuristicallyCommitted
+                | ReturnCode::HeuristicallyRolledBack
+                | ReturnCode::HeuristicallyMessedUp,
+            ) => BranchState::HeuristicallyCompleted,
+            (
+                RmMethod::Prepare
+                | RmMethod::Commit
+                | RmMethod::CommitOnePhase
+                | RmMethod::Rollback,
+                rc,
+            ) if rc.is_rollback() => BranchState::NonExistent,
+            (_, rc) if rc.is_rollback() => BranchState::RollbackOnly,
+            (RmMethod::Start | RmMethod::StartByJoining | RmMethod::StartByResuming, _) => {
+                BranchState::Active
+            }
+            (RmMethod::EndSuccess, _) => BranchState::Idle,
+            (RmMethod::EndFailure, _) => BranchState::RollbackOnly,
+            (RmMethod::EndSuspend, _) => BranchState::Suspended,
+            (RmMethod::Prepare, ReturnCode::ReadOnlyCommitted)
+            | (
+                RmMethod::Commit
+                | RmMethod::CommitOnePhase
+                | RmMethod::Rollback
+                | RmMethod::Forget
+                | RmMethod::Recover
+                | RmMethod::BeginRecover
+                | RmMethod::EndRecover,
+                _,
+            ) => BranchState::NonExistent,
+            (RmMethod::Prepare, _) => BranchState::Prepared,
+        };
+        if new_state == BranchState::NonExistent {
+            self.0.remove(id);
+        } else {
+            self.0.insert(*id, new_state);
+        }
+    }
+
+    // Registers the branches that were found in recovery, so that they can be completed.
+    pub(crate) fn recovered(&mut self, result: &Result<Vec<XaTransactionId>, RmError>) {
+        if let Ok(ids) = result {
+            for id in ids {
+                self.0.entry(*id).or_insert(BranchState::Prepared);
+            }
+        }
+    }
+}
+
+// The state table of the XA specification, from the perspective of a single branch.
+// A rollback is also accepted for an active branch, which XA allows from a different
+// thread of control.
+// A rollback-only branch can still be prepared or committed in one phase, which then
+// returns an `XA_RB*` code; a heuristically completed branch can still be committed
+// or rolled back, which then returns the heuristic code again.
+fn is_legal(method: RmMethod, state: BranchState) -> bool {
+    use BranchState as S;
+    match method {
+        RmMethod::Start => state == S::NonExistent,
+        RmMethod::StartByResuming => state == S::Suspended,
+        RmMethod::EndSuccess | RmMethod::EndFailure => {
+            matches!(state, S::Active | S::Suspended | S::RollbackOnly)
+        }
+        RmMethod::EndSuspend => state == S::Active,
+        RmMethod::StartByJoining => state == S::Idle,
+        RmMethod::Prepare | RmMethod::CommitOnePhase => matches!(state, S::Idle | S::RollbackOnly),
+        RmMethod::Commit => matches!(state, S::Prepared | S::HeuristicallyCompleted),
+        RmMethod::Rollback => matches!(
+            state,
+            S::Active
+                | S::Idle
+                | S::Suspended
+                | S::Prepared
+                | S::RollbackOnly
+                | S::HeuristicallyCompleted
+        ),
+        RmMethod::Forget => state == S::HeuristicallyCompleted,
+        RmMethod::Recover | RmMethod::BeginRecover | RmMethod::EndRecover => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BranchState, BranchStates, ValidationMode};
+    use crate::{ErrorCode, ReturnCode, RmMethod, XaTransactionId};
+
+    #[test]
+    fn test_state_table() {
+        let id = XaTransactionId::try_new(1, vec![1], vec![2]).unwrap();
+        let mut states = BranchStates::default();
+        let mut call = |method, rc| {
+            states.check(ValidationMode::Reject, method, &id)?;
+            states.update(method, &id, &Ok(rc));
+            Ok::<_, crate::RmError>(states.get(&id))
+        };
+
+        assert!(call(RmMethod::Commit, ReturnCode::Ok).is_err());
+        assert_eq!(
+            call(RmMethod::Start, ReturnCode::Ok).unwrap(),
+            BranchState::Active
+        );
+        assert!(matches!(
+            call(RmMethod::Start, ReturnCode::Ok)
+                .unwrap_err()
+                .get_code(),
+            ErrorCode::ProtocolError
+        ));
+        assert_eq!(
+            call(RmMethod::EndSuspend, ReturnCode::Ok).unwrap(),
+            BranchState::Suspended
+        );
+        assert_eq!(
+            call(RmMethod::StartByResuming, ReturnCode::Ok).unwrap(),
+            BranchState::Active
+        );
+        assert_eq!(
+            call(RmMethod::EndSuccess, ReturnCode::Ok).unwrap(),
+            BranchState::Idle
+        );
+        assert!(call(RmMethod::Commit, ReturnCode::Ok).is_err());
+        assert_eq!(
+            call(RmMethod::Prepare, ReturnCode::Ok).unwrap(),
+            BranchState::Prepared
+        );
+        assert_eq!(
+            call(RmMethod::Commit, ReturnCode::HeuristicallyRolledBack).unwrap(),
+            BranchState::HeuristicallyCompleted
+        );
+        assert_eq!(
+            call(RmMethod::Forget, ReturnCode::Ok).unwrap(),
+            BranchState::NonExistent
+        );
+        assert_eq!(
+            call(RmMethod::Start, ReturnCode::Ok).unwrap(),
+            BranchState::Active
+        );
+        assert_eq!(
+            call(RmMethod::EndFailure, ReturnCode::Ok).unwrap(),
+            BranchState::RollbackOnly
+        );
+        assert_eq!(
+            call(RmMethod::Rollback, ReturnCode::Ok).unwrap(),
+            BranchState::NonExistent
+        );
+
+        // a rollback-only branch can be prepared or committed, which rolls it back
+        for method in [RmMethod::Prepare, RmMethod::CommitOnePhase] {
+            call(RmMethod::Start, ReturnCode::Ok).unwrap();
+            assert_eq!(
+                call(RmMethod::EndFailure, ReturnCode::Ok).unwrap(),
+                BranchState::RollbackOnly
+            );
+            assert_eq!(
+                call(method, ReturnCode::RollbackUnspecified).unwrap(),
+                BranchState::NonExistent
+            );
+        }
+
+        // a completion that returns XA_RB* ends the branch
+        for method in [RmMethod::Commit, RmMethod::Rollback] {
+            call(RmMethod::Start, ReturnCode::Ok).unwrap();
+            call(RmMethod::EndSuccess, ReturnCode::Ok).unwrap();
+            call(RmMethod::Prepare, ReturnCode::Ok).unwrap();
+            assert_eq!(
+                call(method, ReturnCode::RollbackDeadlock).unwrap(),
+                BranchState::NonExistent
+            );
+        }
+
+        // a heuristically completed branch answers commit and rollback until it is forgotten
+        call(RmMethod::Start, ReturnCode::Ok).unwrap();
+        call(RmMethod::EndSuccess, ReturnCode::Ok).unwrap();
+        assert_eq!(
+            call(RmMethod::CommitOnePhase, ReturnCode::HeuristicallyCommitted).unwrap(),
+            BranchState::HeuristicallyCompleted
+        );
+        assert_eq!(
+            call(RmMethod::Commit, ReturnCode::HeuristicallyCommitted).unwrap(),
+            BranchState::HeuristicallyCompleted
+        );
+        assert_eq!(
+            call(RmMethod::Rollback, ReturnCode::HeuristicallyCommitted).unwrap(),
+            BranchState::HeuristicallyCompleted
+        );
+        assert!(call(RmMethod::Prepare, ReturnCode::Ok).is_err());
+        assert_eq!(
+            call(RmMethod::Forget, ReturnCode::Ok).unwrap(),
+            BranchState::NonExistent
+        );
+
+        states.recovered(&Ok(vec![id]));
+        assert_eq!(states.get(&id), BranchState::Prepared);
+        assert!(states
+            .check(ValidationMode::Log, RmMethod::Prepare, &id)
+            .is_ok());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{Flags, RmMethod, XaTransactionId};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A call that a `MockResourceManager`
+/// ([`sync::rm::MockResourceManager`](crate::sync::rm::MockResourceManager) or
+/// [`a_sync::rm::MockResourceManager`](crate::a_sync::rm::MockResourceManager))
+/// has received.
+#[derive(Clone, Debug)]
+pub struct RecordedCall {
+    /// The called method.
+    pub method: RmMethod,
+    /// The transaction id, if the method has one.
+    pub xid: Option<XaTransactionId>,
+    /// The XA flags that correspond to the call.
+    pub flags: Flags,
+}
+
+/// The calls that one or more `MockResourceManager`s have received, in the order of
+/// their arrival, with assertions on their sequence.
+///
+/// `CallLog` is a cheap handle, clones share the same state, so that a clone can be kept
+/// to inspect the calls after the `MockResourceManager` was handed over to a
+/// transaction manager.
+///
+/// The assertion methods panic with a descriptive message if they are violated,
+/// and are meant to be used in tests.
+#[derive(Clone, Debug, Default)]
+pub struct CallLog(Arc<Mutex<Vec<RecordedCall>>>);
+
+impl CallLog {
+    /// Creates an empty log.
+    #[must_use]
+    pub fn new() -> CallLog {
+        CallLog::default()
+    }
+
+    /// Returns all recorded calls.
+    #[must_use]
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.lock().clone()
+    }
+
+    /// Returns the methods of all recorded calls.
+    #[must_use]
+    pub fn methods(&self) -> Vec<RmMethod> {
+        self.lock().iter().map(|call| call.method).collect()
+    }
+
+    /// Returns the recorded calls for the given transaction id.
+    #[must_use]
+    pub fn calls_for(&self, xid: &XaTransactionId) -> Vec<RecordedCall> {
+        self.lock()
+            .iter()
+            .filter(|call| is_for(call, xid))
+            .cloned()
+            .collect()
+    }
+
+    /// Removes all recorded calls.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Asserts that exactly the given methods were called, in the given order.
+    ///
+    /// # Panics
+    ///
+    /// If the recorded calls differ.
+    #[track_caller]
+    pub fn assert_methods(&self, expected: &[RmMethod]) {
+        let methods = self.methods();
+        assert!(
+            methods == expected,
+            "expected calls {expected:?}, but got {methods:?}"
+        );
+    }
+
+    /// Asserts that the given method was never called.
+    ///
+    /// # Panics
+    ///
+    /// If the method was called.
+    #[track_caller]
+    pub fn assert_not_called(&self, method: RmMethod) {
+        if let Some(call) = self.lock().iter().find(|call| call.method == method) {
+            panic!("expected no call of {method:?}, but got {call:?}");
+        }
+    }
+
+    /// Asserts that for each transaction id, every call of `later` was preceded by a call of
+    /// `earlier`, e.g. that each branch was prepared before it was committed.
+    ///
+    /// # Panics
+    ///
+    /// If a call of `later` was not preceded by a call of `earlier`.
+    #[track_caller]
+    pub fn assert_called_before(&self, earlier: RmMethod, later: RmMethod) {
+        let calls = self.lock();
+        for (i, call) in calls.iter().enumerate() {
+            if call.method == later {
+                let preceded = calls[..i].iter().any(|c| {
+                    c.method == earlier && c.xid.as_ref().is_some_and(|x| is_for(call, x))
+                });
+                assert!(
+                    preceded,
+                    "expected a call of {earlier:?} before {call:?}, but there was none"
+                );
+            }
+        }
+    }
+
+    /// Asserts that for each transaction id, no further call was made after a call of the given
+    /// method, e.g. that a branch was left alone after it was rolled back.
+    ///
+    /// # Panics
+    ///
+    /// If a call followed a call of `method` for the same transaction id.
+    #[track_caller]
+    pub fn assert_no_calls_after(&self, method: RmMethod) {
+        let calls = self.lock();
+        for (i, call) in calls.iter().enumerate() {
+            if let (true, Some(xid)) = (call.method == method, &call.xid) {
+                if let Some(later) = calls[i + 1..].iter().find(|c| is_for(c, xid)) {
+                    panic!("expected no call after {method:?} for {xid:?}, but got {later:?}");
+                }
+            }
+        }
+    }
+
+    pub(crate) fn record(&self, method: RmMethod, xid: Option<&XaTransactionId>) {
+        self.lock().push(RecordedCall {
+            method,
+            xid: xid.copied(),
+            flags: method.flags(),
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<RecordedCall>> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+fn is_for(call: &RecordedCall, xid: &XaTransactionId) -> bool {
+    call.xid.as_ref().is_some_and(|x| x == xid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CallLog;
+    use crate::{Flags, RmMethod, XaTransactionId};
+
+    #[test]
+    fn test_assertions() {
+        let xid1 = XaTransactionId::try_new(1, vec![1], vec![1]).unwrap();
+        let xid2 = XaTransactionId::try_new(1, vec![2], vec![1]).unwrap();
+        let log = CallLog::new();
+        log.record(RmMethod::Start, Some(&xid1));
+        log.record(RmMethod::EndSuccess, Some(&xid1));
+        log.record(RmMethod::Prepare, Some(&xid1));
+        log.record(RmMethod::Rollback, Some(&xid2));
+        log.record(RmMethod::Commit, Some(&xid1));
+
+        assert_eq!(log.calls_for(&xid1).len(), 4);
+        assert_eq!(log.calls()[1].flags, Flags::SUCCESS);
+        log.assert_called_before(RmMethod::Prepare, RmMethod::Commit);
+        log.assert_no_calls_after(RmMethod::Rollback);
+        log.assert_not_called(RmMethod::Forget);
+        log.assert_methods(&[
+            RmMethod::Start,
+            RmMethod::EndSuccess,
+            RmMethod::Prepare,
+            RmMethod::Rollback,
+            RmMethod::Commit,
+        ]);
+
+        log.record(RmMethod::Commit, Some(&xid2));
+        assert!(std::panic::catch_unwind(|| {
+            log.assert_called_before(RmMethod::Prepare, RmMethod::Commit);
+        })
+        .is_err());
+        assert!(
+            std::panic::catch_unwind(|| log.assert_no_calls_after(RmMethod::Rollback)).is_err()
+        );
+
+        log.clear();
+        assert!(log.calls().is_empty());
+    }
+}
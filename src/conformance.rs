@@ -0,0 +1,174 @@
+use crate::{ErrorCode, ReturnCode, RmError, XaTransactionId};
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The result of a conformance check of a resource manager implementation, as produced by
+/// [`sync::rm::check_conformance`](crate::sync::rm::check_conformance) or
+/// [`a_sync::rm::check_conformance`](crate::a_sync::rm::check_conformance).
+///
+/// The `Display` implementation lists the deviations from the XA specification.
+#[derive(Clone, Debug, Default)]
+pub struct ConformanceReport {
+    /// The results of the individual scenarios, in the order of their execution.
+    pub scenarios: Vec<ScenarioResult>,
+}
+impl ConformanceReport {
+    /// Returns `true` if no deviations were found.
+    #[must_use]
+    pub fn is_conformant(&self) -> bool {
+        self.scenarios.iter().all(ScenarioResult::passed)
+    }
+}
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for scenario in &self.scenarios {
+            if scenario.passed() {
+                writeln!(f, "{}: ok", scenario.name)?;
+            } else {
+                writeln!(f, "{}: FAILED", scenario.name)?;
+                for deviation in &scenario.deviations {
+                    writeln!(f, "    {deviation}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The result of a single scenario of a conformance check.
+#[derive(Clone, Debug)]
+pub struct ScenarioResult {
+    /// The name of the scenario, e.g. `"two_phase_commit"`.
+    pub name: &'static str,
+    /// The observed deviations from the XA specification.
+    pub deviations: Vec<String>,
+}
+impl ScenarioResult {
+    /// Returns `true` if the scenario showed no deviations.
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.deviations.is_empty()
+    }
+}
+
+// Collects the deviations of a scenario.
+pub(crate) struct Scenario {
+    result: ScenarioResult,
+    run_id: u64,
+}
+impl Scenario {
+    pub(crate) fn new(name: &'static str) -> Scenario {
+        // distinguishes the xids of different runs against persistent resource managers
+        let run_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() ^ u64::from(d.subsec_nanos()) << 32)
+            ^ u64::from(std::process::id());
+        Scenario {
+            result: ScenarioResult {
+                name,
+                deviations: Vec::new(),
+            },
+            run_id,
+        }
+    }
+
+    // Returns a fresh xid for this scenario.
+    pub(crate) fn xid(&self, n: u8) -> XaTransactionId {
+        let mut gtid = self.result.name.as_bytes().to_vec();
+        gtid.truncate(40);
+        gtid.extend_from_slice(&self.run_id.to_le_bytes());
+        gtid.push(n);
+        XaTransactionId::try_new(0x4454_5843, gtid, vec![n]).unwrap(/* lengths are below 64 */)
+    }
+
+    pub(crate) fn deviation(&mut self, s: String) {
+        self.result.deviations.push(s);
+    }
+
+    // Expects a successful call; returns false otherwise.
+    pub(crate) fn expect_ok(&mut self, step: &str, result: &Result<ReturnCode, RmError>) -> bool {
+        self.expect_return(step, result, &ReturnCode::Ok)
+    }
+
+    // Expects a call that returns the given code; returns false otherwise.
+    pub(crate) fn expect_return(
+        &mut self,
+        step: &str,
+        result: &Result<ReturnCode, RmError>,
+        expected: &ReturnCode,
+    ) -> bool {
+        match result {
+            Ok(rc) if rc == expected => true,
+            Ok(rc) => {
+                self.deviation(format!("{step}: expected {expected:?}, got {rc:?}"));
+                false
+            }
+            Err(e) => {
+                self.deviation(format!("{step}: expected {expected:?}, got error {e:?}"));
+                false
+            }
+        }
+    }
+
+    // Expects a successful prepare, for which a read-only vote is also fine.
+    pub(crate) fn expect_prepared(
+        &mut self,
+        step: &str,
+        result: &Result<ReturnCode, RmError>,
+    ) -> bool {
+        if let Ok(ReturnCode::ReadOnlyCommitted) = result {
+            self.deviation(format!(
+                "{step}: branch with changes was voted read-only, cannot check the scenario"
+            ));
+            false
+        } else {
+            self.expect_ok(step, result)
+        }
+    }
+
+    // Expects a failing call with one of the given error codes.
+    pub(crate) fn expect_err<T: fmt::Debug>(
+        &mut self,
+        step: &str,
+        result: &Result<T, RmError>,
+        expected: &[ErrorCode],
+    ) {
+        match result {
+            Ok(t) => self.deviation(format!("{step}: expected error {expected:?}, got {t:?}")),
+            Err(e) => {
+                let code = std::mem::discriminant(&e.get_code());
+                if !expected.iter().any(|ex| std::mem::discriminant(ex) == code) {
+                    self.deviation(format!("{step}: expected error {expected:?}, got {e:?}"));
+                }
+            }
+        }
+    }
+
+    // Checks whether the recovered xids contain the given one as expected.
+    pub(crate) fn expect_recovered(
+        &mut self,
+        step: &str,
+        result: &Result<Vec<XaTransactionId>, RmError>,
+        id: &XaTransactionId,
+        expected: bool,
+    ) {
+        match result {
+            Ok(ids) => {
+                let found = ids.contains(id);
+                if found != expected {
+                    self.deviation(format!(
+                        "{step}: prepared branch was {}returned by recover",
+                        if found { "" } else { "not " }
+                    ));
+                }
+            }
+            Err(e) => self.deviation(format!("{step}: recover failed with {e:?}")),
+        }
+    }
+
+    pub(crate) fn finish(self) -> ScenarioResult {
+        self.result
+    }
+}
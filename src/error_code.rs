@@ -1,5 +1,10 @@
 /// Errors occuring in resource managers.
-#[derive(Clone, Debug)]
+///
+/// The variants correspond to the `XAER_*` error codes of the XA standard (`xa.h`);
+/// [`from_i32`](ErrorCode::from_i32) and [`to_i32`](ErrorCode::to_i32) convert
+/// from and to the numeric codes, `Display` shows the symbolic name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ErrorCode {
     /// A resource manager error occurred in the transaction branch.
     RmError,
@@ -13,6 +18,10 @@ pub enum ErrorCode {
     RmFailure,
     /// The XID already exists.
     DuplicateTransactionId,
+    /// An asynchronous operation is already outstanding.
+    AsyncOutstanding,
+    /// The resource manager is doing work outside a global transaction.
+    OutsideGlobalTransaction,
     /// Should never be used.
     UnknownErrorCode(i32),
 }
@@ -21,13 +30,84 @@ impl ErrorCode {
     #[must_use]
     pub fn from_i32(i: i32) -> ErrorCode {
         match i {
+            -2 => ErrorCode::AsyncOutstanding,
             -3 => ErrorCode::RmError,
             -4 => ErrorCode::InvalidTransactionId,
             -5 => ErrorCode::InvalidArguments,
             -6 => ErrorCode::ProtocolError,
             -7 => ErrorCode::RmFailure,
             -8 => ErrorCode::DuplicateTransactionId,
+            -9 => ErrorCode::OutsideGlobalTransaction,
             i => ErrorCode::UnknownErrorCode(i),
         }
     }
+
+    /// Returns the error code as defined in the XA standard.
+    #[must_use]
+    pub fn to_i32(&self) -> i32 {
+        match self {
+            ErrorCode::AsyncOutstanding => -2,
+            ErrorCode::RmError => -3,
+            ErrorCode::InvalidTransactionId => -4,
+            ErrorCode::InvalidArguments => -5,
+            ErrorCode::ProtocolError => -6,
+            ErrorCode::RmFailure => -7,
+            ErrorCode::DuplicateTransactionId => -8,
+            ErrorCode::OutsideGlobalTransaction => -9,
+            ErrorCode::UnknownErrorCode(i) => *i,
+        }
+    }
+
+    /// Returns the symbolic name of the error code in `xa.h`, e.g. `XAER_RMERR`,
+    /// or `None` for unknown codes.
+    #[must_use]
+    pub fn xa_name(&self) -> Option<&'static str> {
+        Some(match self {
+            ErrorCode::AsyncOutstanding => "XAER_ASYNC",
+            ErrorCode::RmError => "XAER_RMERR",
+            ErrorCode::InvalidTransactionId => "XAER_NOTA",
+            ErrorCode::InvalidArguments => "XAER_INVAL",
+            ErrorCode::ProtocolError => "XAER_PROTO",
+            ErrorCode::RmFailure => "XAER_RMFAIL",
+            ErrorCode::DuplicateTransactionId => "XAER_DUPID",
+            ErrorCode::OutsideGlobalTransaction => "XAER_OUTSIDE",
+            ErrorCode::UnknownErrorCode(_) => return None,
+        })
+    }
+
+    /// Returns `true` if the same request may succeed when it is repeated later,
+    /// because the resource manager is temporarily unavailable or busy.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorCode::RmFailure | ErrorCode::AsyncOutstanding)
+    }
+}
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.xa_name() {
+            Some(name) => f.pad(name),
+            None => f.pad(&format!("unknown error code {}", self.to_i32())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorCode;
+
+    #[test]
+    fn test_xa_codes() {
+        for i in -10..=0 {
+            let code = ErrorCode::from_i32(i);
+            assert_eq!(code.to_i32(), i);
+            assert_eq!(code.xa_name().is_some(), (-9..=-2).contains(&i), "{i}");
+        }
+        assert_eq!(ErrorCode::from_i32(-9).to_string(), "XAER_OUTSIDE");
+        assert_eq!(
+            ErrorCode::from_i32(-42).to_string(),
+            "unknown error code -42"
+        );
+        assert!(ErrorCode::RmFailure.is_retryable());
+        assert!(!ErrorCode::DuplicateTransactionId.is_retryable());
+    }
 }
@@ -0,0 +1,258 @@
+use crate::{ErrorCode, ReturnCode, RmError, RmMethod};
+use log::debug;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// A failure that a `FaultInjector`
+/// ([`sync::rm::FaultInjector`](crate::sync::rm::FaultInjector) or
+/// [`a_sync::rm::FaultInjector`](crate::a_sync::rm::FaultInjector))
+/// injects into a call of a resource manager.
+#[derive(Clone, Debug)]
+pub enum Fault {
+    /// The call is not forwarded, and fails with an `RmError` with the given code.
+    Error(ErrorCode),
+    /// The call is not forwarded, and returns the given `ReturnCode`
+    /// (e.g. `ReturnCode::HeuristicallyRolledBack` on commit).
+    ///
+    /// A heuristic outcome of commit, one-phase commit or rollback is kept like a resource
+    /// manager would keep it: the branch is completed in the wrapped resource manager
+    /// only when it is forgotten.
+    ///
+    /// Is ignored for the recover methods.
+    Return(ReturnCode),
+    /// The call hangs for the given time, and is then forwarded.
+    Delay(Duration),
+}
+
+/// Decides which calls of a `FaultInjector` fail, and how.
+///
+/// Faults can be scripted for specific calls, and can in addition be drawn from
+/// a random schedule that is reproducible from a seed.
+/// A scripted fault takes precedence over a random one.
+///
+/// ```rust
+/// use dist_tx::{ErrorCode, Fault, FaultPlan, ReturnCode, RmMethod};
+/// use std::time::Duration;
+///
+/// let plan = FaultPlan::new()
+///     .on_call(RmMethod::Prepare, 2, Fault::Error(ErrorCode::RmFailure))
+///     .on_call(RmMethod::Commit, 1, Fault::Return(ReturnCode::HeuristicallyRolledBack))
+///     .always(RmMethod::Rollback, Fault::Delay(Duration::from_secs(5)));
+/// ```
+///
+/// `FaultPlan` is a cheap handle, clones share the same state, so that a clone can be kept
+/// to inspect the injected faults after the `FaultInjector` was handed over to a
+/// transaction manager.
+#[derive(Clone, Debug, Default)]
+pub struct FaultPlan(Arc<Mutex<PlanInner>>);
+
+#[derive(Debug, Default)]
+struct PlanInner {
+    scripted: Vec<Rule>,
+    random: Option<RandomFaults>,
+    calls: HashMap<RmMethod, usize>,
+    injected: Vec<InjectedFault>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    method: RmMethod,
+    // `None` stands for all calls
+    nth: Option<usize>,
+    fault: Fault,
+}
+
+#[derive(Debug)]
+struct RandomFaults {
+    rng: SplitMix64,
+    probability: f64,
+    faults: Vec<Fault>,
+}
+
+/// A fault that was injected by a `FaultInjector`.
+#[derive(Clone, Debug)]
+pub struct InjectedFault {
+    /// The method whose call was affected.
+    pub method: RmMethod,
+    /// The number of the call of this method (starting with 1).
+    pub call: usize,
+    /// The injected fault.
+    pub fault: Fault,
+}
+
+impl FaultPlan {
+    /// Creates a plan that injects no faults.
+    #[must_use]
+    pub fn new() -> FaultPlan {
+        FaultPlan::default()
+    }
+
+    /// Injects the fault into the `nth` call (starting with 1) of the given method.
+    #[must_use]
+    pub fn on_call(self, method: RmMethod, nth: usize, fault: Fault) -> FaultPlan {
+        self.lock().scripted.push(Rule {
+            method,
+            nth: Some(nth),
+            fault,
+        });
+        self
+    }
+
+    /// Injects the fault into all calls of the given method.
+    #[must_use]
+    pub fn always(self, method: RmMethod, fault: Fault) -> FaultPlan {
+        self.lock().scripted.push(Rule {
+            method,
+            nth: None,
+            fault,
+        });
+        self
+    }
+
+    /// Injects, with the given probability, into every call one of the given faults,
+    /// chosen randomly.
+    ///
+    /// The same seed produces the same sequence of faults for the same sequence of calls.
+    #[must_use]
+    pub fn with_random_faults(self, seed: u64, probability: f64, faults: Vec<Fault>) -> FaultPlan {
+        self.lock().random = Some(RandomFaults {
+            rng: SplitMix64(seed),
+            probability,
+            faults,
+        });
+        self
+    }
+
+    /// Returns the faults that were injected so far.
+    #[must_use]
+    pub fn injected(&self) -> Vec<InjectedFault> {
+        self.lock().injected.clone()
+    }
+
+    /// Returns how often the given method was called so far.
+    #[must_use]
+    pub fn calls(&self, method: RmMethod) -> usize {
+        self.lock().calls.get(&method).copied().unwrap_or(0)
+    }
+
+    // Counts the call and decides whether a fault is to be injected into it.
+    pub(crate) fn next_fault(&self, method: RmMethod) -> Option<Fault> {
+        let mut inner = self.lock();
+        let call = {
+            let count = inner.calls.entry(method).or_insert(0);
+            *count += 1;
+            *count
+        };
+
+        let scripted = inner
+            .scripted
+            .iter()
+            .find(|rule| rule.method == method && rule.nth.is_none_or(|nth| nth == call))
+            .map(|rule| rule.fault.clone());
+        let fault = match (scripted, inner.random.as_mut()) {
+            (Some(fault), _) => Some(fault),
+            (None, Some(random)) => random.draw(),
+            (None, None) => None,
+        }
+        .filter(|fault| !(method.is_recover() && matches!(fault, Fault::Return(_))));
+
+        if let Some(ref fault) = fault {
+            debug!("injecting {fault:?} into call {call} of {method:?}");
+            inner.injected.push(InjectedFault {
+                method,
+                call,
+                fault: fault.clone(),
+            });
+        }
+        fault
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PlanInner> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl RandomFaults {
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    fn draw(&mut self) -> Option<Fault> {
+        // 53 random bits give a uniformly distributed f64 in [0, 1)
+        let x = (self.rng.next() >> 11) as f64 / (1_u64 << 53) as f64;
+        if self.faults.is_empty() || x >= self.probability {
+            None
+        } else {
+            let i = (self.rng.next() % self.faults.len() as u64) as usize;
+            Some(self.faults[i].clone())
+        }
+    }
+}
+
+// Creates the error for an injected `Fault::Error`.
+pub(crate) fn injected_error(method: RmMethod, code: ErrorCode) -> RmError {
+    RmError::new(code, format!("injected fault in {method:?}"))
+}
+
+// A small, seedable pseudo random number generator (see https://prng.di.unimi.it).
+#[derive(Debug)]
+pub(crate) struct SplitMix64(pub(crate) u64);
+impl SplitMix64 {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fault, FaultPlan};
+    use crate::{ErrorCode, ReturnCode, RmMethod};
+
+    #[test]
+    fn test_scripted_faults() {
+        let plan = FaultPlan::new()
+            .on_call(RmMethod::Prepare, 2, Fault::Error(ErrorCode::RmFailure))
+            .always(RmMethod::Recover, Fault::Return(ReturnCode::Retry));
+        assert!(plan.next_fault(RmMethod::Prepare).is_none());
+        assert!(matches!(
+            plan.next_fault(RmMethod::Prepare),
+            Some(Fault::Error(ErrorCode::RmFailure))
+        ));
+        assert!(plan.next_fault(RmMethod::Prepare).is_none());
+        assert!(plan.next_fault(RmMethod::Recover).is_none());
+        assert_eq!(plan.calls(RmMethod::Prepare), 3);
+        assert_eq!(plan.injected().len(), 1);
+    }
+
+    #[test]
+    fn test_random_faults_are_reproducible() {
+        let run = |seed| {
+            let plan = FaultPlan::new().with_random_faults(
+                seed,
+                0.3,
+                vec![
+                    Fault::Error(ErrorCode::RmError),
+                    Fault::Return(ReturnCode::RollbackOther),
+                ],
+            );
+            for _ in 0..100 {
+                plan.next_fault(RmMethod::Commit);
+            }
+            plan.injected()
+                .iter()
+                .map(|f| (f.call, format!("{:?}", f.fault)))
+                .collect::<Vec<_>>()
+        };
+        let faults = run(42);
+        assert!(!faults.is_empty() && faults.len() < 100);
+        assert_eq!(faults, run(42));
+        assert_ne!(faults, run(43));
+    }
+}
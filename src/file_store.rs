@@ -0,0 +1,661 @@
+use crate::{ErrorCode, Flags, ReturnCode, RmError, XaTransactionId};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::trace;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+const DATA_FILE: &str = "data";
+const PREPARED_DIR: &str = "prepared";
+const RECORD_SUFFIX: &str = "rec";
+const RECORD_MAGIC: &[u8; 4] = b"DTXP";
+const DATA_MAGIC: &[u8; 4] = b"DTXD";
+
+/// A simple transactional key-value store that keeps its data in a local directory.
+///
+/// `FileStore` is a reference implementation of a resource manager's backend,
+/// and is meant as a worked example for driver authors and as a durable participant
+/// in tests of the transaction managers.
+/// Its XA interface is provided by
+/// [`sync::rm::FileResourceManager`](crate::sync::rm::FileResourceManager) and
+/// [`a_sync::rm::FileResourceManager`](crate::a_sync::rm::FileResourceManager).
+///
+/// A `FileStore` is a cheap handle, clones share the same state.
+/// The application uses it like a database connection: changes done with
+/// [`put`](FileStore::put) and [`delete`](FileStore::delete) belong to the transaction branch
+/// that is currently associated, and become visible for others only when that branch is
+/// committed.
+///
+/// ## Durability
+///
+/// Committed data are kept in the file `data`, which is always replaced atomically.
+/// Before `prepare` votes yes, the changes of the branch are written, together with its
+/// `XaTransactionId`, into a record file in the subdirectory `prepared`, and synced to disk;
+/// the file is named by a digest of the id, as ids can be too long for a file name.
+/// When the directory is opened again (e.g. after a process restart),
+/// all prepared branches are restored and reported by `recover`,
+/// and can be committed or rolled back as if the process had never stopped.
+///
+/// A directory must not be used by more than one `FileStore` at a time.
+#[derive(Clone, Debug)]
+pub struct FileStore(Arc<Mutex<Inner>>);
+
+#[derive(Debug)]
+struct Inner {
+    dir: PathBuf,
+    data: BTreeMap<String, Vec<u8>>,
+    branches: BTreeMap<Vec<u8>, Branch>,
+    associated: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct Branch {
+    xid: XaTransactionId,
+    state: BranchState,
+    changes: Vec<Change>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BranchState {
+    Active,
+    Suspended,
+    Idle,
+    RollbackOnly,
+    Prepared,
+}
+
+#[derive(Clone, Debug)]
+enum Change {
+    Put(String, Vec<u8>),
+    Delete(String),
+}
+
+impl FileStore {
+    /// Opens the store in the given directory, which is created if it does not yet exist.
+    ///
+    /// Branches that were prepared, but not completed, by a previous instance are restored.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the directory or its content cannot be read.
+    pub fn open<P: AsRef<Path>>(dir: P) -> std::io::Result<FileStore> {
+        let dir = dir.as_ref().to_path_buf();
+        trace!("FileStore::open({})", dir.display());
+        fs::create_dir_all(dir.join(PREPARED_DIR))?;
+
+        let data = match fs::read(dir.join(DATA_FILE)) {
+            Ok(bytes) => decode_data(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut branches = BTreeMap::new();
+        for entry in fs::read_dir(dir.join(PREPARED_DIR))? {
+            let path = entry?.path();
+            if path.extension().and_then(std::ffi::OsStr::to_str) != Some(RECORD_SUFFIX) {
+                continue;
+            }
+            let (xid, changes) = decode_record(&fs::read(&path)?)?;
+            trace!("FileStore::open(): restoring prepared branch {xid:?}");
+            // records of older versions are named differently
+            let record_path = record_path(&dir, &xid);
+            if path != record_path {
+                fs::rename(&path, &record_path)?;
+            }
+            branches.insert(
+                key_of(&xid),
+                Branch {
+                    xid,
+                    state: BranchState::Prepared,
+                    changes,
+                },
+            );
+        }
+
+        Ok(FileStore(Arc::new(Mutex::new(Inner {
+            dir,
+            data,
+            branches,
+            associated: None,
+        }))))
+    }
+
+    /// Returns the directory of the store.
+    #[must_use]
+    pub fn dir(&self) -> PathBuf {
+        self.lock().dir.clone()
+    }
+
+    /// Returns the value for the given key.
+    ///
+    /// If a transaction branch is currently associated, its own uncommitted changes are
+    /// visible; otherwise only committed data are returned.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let inner = self.lock();
+        if let Some(branch) = inner
+            .associated
+            .as_ref()
+            .and_then(|k| inner.branches.get(k))
+        {
+            for change in branch.changes.iter().rev() {
+                match change {
+                    Change::Put(k, v) if k == key => return Some(v.clone()),
+                    Change::Delete(k) if k == key => return None,
+                    _ => {}
+                }
+            }
+        }
+        inner.data.get(key).cloned()
+    }
+
+    /// Returns the keys of all committed entries.
+    #[must_use]
+    pub fn committed_keys(&self) -> Vec<String> {
+        self.lock().data.keys().cloned().collect()
+    }
+
+    /// Sets the value for the given key within the currently associated transaction branch.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` with `ErrorCode::ProtocolError` if no transaction branch is associated.
+    pub fn put<K: Into<String>, V: Into<Vec<u8>>>(&self, key: K, value: V) -> Result<(), RmError> {
+        self.change(Change::Put(key.into(), value.into()))
+    }
+
+    /// Removes the given key within the currently associated transaction branch.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` with `ErrorCode::ProtocolError` if no transaction branch is associated.
+    pub fn delete<K: Into<String>>(&self, key: K) -> Result<(), RmError> {
+        self.change(Change::Delete(key.into()))
+    }
+
+    fn change(&self, change: Change) -> Result<(), RmError> {
+        let mut guard = self.lock();
+        let inner = &mut *guard;
+        match inner
+            .associated
+            .as_ref()
+            .and_then(|k| inner.branches.get_mut(k))
+        {
+            Some(branch) => {
+                branch.changes.push(change);
+                Ok(())
+            }
+            None => Err(protocol_error("no transaction branch is associated")),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // a panic while holding the lock leaves the state consistent enough for a test store
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub(crate) fn xa_start(
+        &self,
+        xid: &XaTransactionId,
+        flags: Flags,
+    ) -> Result<ReturnCode, RmError> {
+        let mut inner = self.lock();
+        let key = key_of(xid);
+        if flags.is_empty() && inner.branches.contains_key(&key) {
+            return Err(RmError::new(
+                ErrorCode::DuplicateTransactionId,
+                format!("transaction branch {xid:?} exists already"),
+            ));
+        }
+        if inner.associated.is_some() {
+            return Err(protocol_error("another transaction branch is associated"));
+        }
+        if flags.is_empty() {
+            inner.branches.insert(
+                key.clone(),
+                Branch {
+                    xid: *xid,
+                    state: BranchState::Active,
+                    changes: Vec::new(),
+                },
+            );
+        } else {
+            let required = if flags == Flags::JOIN {
+                BranchState::Idle
+            } else if flags == Flags::RESUME {
+                BranchState::Suspended
+            } else {
+                return Err(invalid_flags(flags));
+            };
+            let branch = inner.branch_mut(xid)?;
+            if branch.state != required {
+                return Err(protocol_error("transaction branch is in the wrong state"));
+            }
+            branch.state = BranchState::Active;
+        }
+        inner.associated = Some(key);
+        Ok(ReturnCode::Ok)
+    }
+
+    pub(crate) fn xa_end(
+        &self,
+        xid: &XaTransactionId,
+        flags: Flags,
+    ) -> Result<ReturnCode, RmError> {
+        let mut inner = self.lock();
+        let key = key_of(xid);
+        if inner.associated.as_ref() != Some(&key) {
+            return Err(protocol_error("transaction branch is not associated"));
+        }
+        let new_state = if flags == Flags::SUCCESS {
+            BranchState::Idle
+        } else if flags == Flags::FAIL {
+            BranchState::RollbackOnly
+        } else if flags == Flags::SUSPEND {
+            BranchState::Suspended
+        } else {
+            return Err(invalid_flags(flags));
+        };
+        inner.branch_mut(xid)?.state = new_state;
+        inner.associated = None;
+        Ok(ReturnCode::Ok)
+    }
+
+    pub(crate) fn xa_prepare(&self, xid: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        let mut inner = self.lock();
+        match inner.branch_mut(xid)?.state {
+            BranchState::Idle => {}
+            BranchState::RollbackOnly => {
+                inner.branches.remove(&key_of(xid));
+                return Ok(ReturnCode::RollbackUnspecified);
+            }
+            _ => return Err(protocol_error("transaction branch is not ended")),
+        }
+        inner.write_record(xid).map_err(io_error)?;
+        inner.branch_mut(xid)?.state = BranchState::Prepared;
+        Ok(ReturnCode::Ok)
+    }
+
+    pub(crate) fn xa_commit(
+        &self,
+        xid: &XaTransactionId,
+        flags: Flags,
+    ) -> Result<ReturnCode, RmError> {
+        let mut inner = self.lock();
+        let state = inner.branch_mut(xid)?.state;
+        if flags == Flags::ONE_PHASE {
+            match state {
+                BranchState::Idle => {}
+                BranchState::RollbackOnly => {
+                    inner.branches.remove(&key_of(xid));
+                    return Ok(ReturnCode::RollbackUnspecified);
+                }
+                _ => return Err(protocol_error("transaction branch is not ended")),
+            }
+        } else if flags.is_empty() {
+            if state != BranchState::Prepared {
+                return Err(protocol_error("transaction branch is not prepared"));
+            }
+        } else {
+            return Err(invalid_flags(flags));
+        }
+
+        let key = key_of(xid);
+        let mut data = inner.data.clone();
+        for change in &inner.branches[&key].changes {
+            match change {
+                Change::Put(k, v) => data.insert(k.clone(), v.clone()),
+                Change::Delete(k) => data.remove(k),
+            };
+        }
+        inner.write_data(&data).map_err(io_error)?;
+        inner.data = data;
+        if state == BranchState::Prepared {
+            inner.remove_record(xid).map_err(io_error)?;
+        }
+        inner.branches.remove(&key);
+        Ok(ReturnCode::Ok)
+    }
+
+    pub(crate) fn xa_rollback(&self, xid: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        let mut inner = self.lock();
+        let key = key_of(xid);
+        if inner.branch_mut(xid)?.state == BranchState::Prepared {
+            inner.remove_record(xid).map_err(io_error)?;
+        }
+        if inner.associated.as_ref() == Some(&key) {
+            inner.associated = None;
+        }
+        inner.branches.remove(&key);
+        Ok(ReturnCode::Ok)
+    }
+
+    pub(crate) fn xa_forget(&self, xid: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        // this store never completes a branch heuristically
+        self.lock().branch_mut(xid)?;
+        Err(protocol_error(
+            "transaction branch was not heuristically completed",
+        ))
+    }
+
+    pub(crate) fn xa_recover(&self, flags: Flags) -> Result<Vec<XaTransactionId>, RmError> {
+        if !flags.contains_only(Flags::START_RECOVERY_SCAN | Flags::END_RECOVERY_SCAN) {
+            return Err(invalid_flags(flags));
+        }
+        if !flags.contains(Flags::START_RECOVERY_SCAN) {
+            // the complete list is always returned with the start of the scan
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .lock()
+            .branches
+            .values()
+            .filter(|branch| branch.state == BranchState::Prepared)
+            .map(|branch| branch.xid)
+            .collect())
+    }
+}
+
+impl Inner {
+    fn branch_mut(&mut self, xid: &XaTransactionId) -> Result<&mut Branch, RmError> {
+        self.branches.get_mut(&key_of(xid)).ok_or_else(|| {
+            RmError::new(
+                ErrorCode::InvalidTransactionId,
+                format!("unknown transaction branch {xid:?}"),
+            )
+            .with_xid(*xid)
+        })
+    }
+
+    fn write_record(&self, xid: &XaTransactionId) -> std::io::Result<()> {
+        let branch = &self.branches[&key_of(xid)];
+        let mut buf = Vec::<u8>::new();
+        buf.write_all(RECORD_MAGIC)?;
+        write_bytes(&mut buf, &xid.as_bytes(false))?;
+        write_changes(&mut buf, &branch.changes)?;
+        write_atomically(&record_path(&self.dir, xid), &buf)
+    }
+
+    fn remove_record(&self, xid: &XaTransactionId) -> std::io::Result<()> {
+        fs::remove_file(record_path(&self.dir, xid))?;
+        sync_dir(&self.dir.join(PREPARED_DIR))
+    }
+
+    fn write_data(&self, data: &BTreeMap<String, Vec<u8>>) -> std::io::Result<()> {
+        let mut buf = Vec::<u8>::new();
+        buf.write_all(DATA_MAGIC)?;
+        write_len(&mut buf, data.len())?;
+        for (k, v) in data {
+            write_bytes(&mut buf, k.as_bytes())?;
+            write_bytes(&mut buf, v)?;
+        }
+        write_atomically(&self.dir.join(DATA_FILE), &buf)
+    }
+}
+
+fn key_of(xid: &XaTransactionId) -> Vec<u8> {
+    xid.as_bytes(false)
+}
+
+// The record file of a prepared branch; the full id is kept in the record.
+fn record_path(dir: &Path, xid: &XaTransactionId) -> PathBuf {
+    let name = Sha256::digest(key_of(xid))
+        .iter()
+        .fold(String::new(), |s, b| s + &format!("{b:02x}"));
+    dir.join(PREPARED_DIR)
+        .join(name)
+        .with_extension(RECORD_SUFFIX)
+}
+
+fn protocol_error(s: &str) -> RmError {
+    RmError::new(ErrorCode::ProtocolError, s.to_string())
+}
+
+fn invalid_flags(flags: Flags) -> RmError {
+    RmError::new(
+        ErrorCode::InvalidArguments,
+        format!("unsupported flags {flags:?}"),
+    )
+}
+
+fn io_error(e: std::io::Error) -> RmError {
+    RmError::new(ErrorCode::RmError, format!("I/O error in FileStore: {e}")).with_source(e)
+}
+
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    sync_dir(path.parent().unwrap_or_else(|| Path::new(".")))
+}
+
+#[cfg(unix)]
+pub(crate) fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    OpenOptions::new().read(true).open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_len(w: &mut Vec<u8>, len: usize) -> std::io::Result<()> {
+    w.write_u32::<LittleEndian>(len as u32)
+}
+
+fn write_bytes(w: &mut Vec<u8>, bytes: &[u8]) -> std::io::Result<()> {
+    write_len(w, bytes.len())?;
+    w.write_all(bytes)
+}
+
+fn write_changes(w: &mut Vec<u8>, changes: &[Change]) -> std::io::Result<()> {
+    write_len(w, changes.len())?;
+    for change in changes {
+        match change {
+            Change::Put(k, v) => {
+                w.write_u8(0)?;
+                write_bytes(w, k.as_bytes())?;
+                write_bytes(w, v)?;
+            }
+            Change::Delete(k) => {
+                w.write_u8(1)?;
+                write_bytes(w, k.as_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_bytes(rdr: &mut Cursor<&[u8]>) -> std::io::Result<Vec<u8>> {
+    let len = rdr.read_u32::<LittleEndian>()?;
+    if u64::from(len) > rdr.get_ref().len() as u64 - rdr.position() {
+        return Err(corrupt("length exceeds file size"));
+    }
+    let mut bytes = vec![0_u8; len as usize];
+    rdr.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string(rdr: &mut Cursor<&[u8]>) -> std::io::Result<String> {
+    String::from_utf8(read_bytes(rdr)?).map_err(|_| corrupt("key is not valid UTF-8"))
+}
+
+fn read_magic(rdr: &mut Cursor<&[u8]>, magic: [u8; 4]) -> std::io::Result<()> {
+    let mut buf = [0_u8; 4];
+    rdr.read_exact(&mut buf)?;
+    if buf == magic {
+        Ok(())
+    } else {
+        Err(corrupt("unexpected file type"))
+    }
+}
+
+fn decode_data(bytes: &[u8]) -> std::io::Result<BTreeMap<String, Vec<u8>>> {
+    let mut rdr = Cursor::new(bytes);
+    read_magic(&mut rdr, *DATA_MAGIC)?;
+    let mut data = BTreeMap::new();
+    for _ in 0..rdr.read_u32::<LittleEndian>()? {
+        let k = read_string(&mut rdr)?;
+        data.insert(k, read_bytes(&mut rdr)?);
+    }
+    Ok(data)
+}
+
+fn decode_record(bytes: &[u8]) -> std::io::Result<(XaTransactionId, Vec<Change>)> {
+    let mut rdr = Cursor::new(bytes);
+    read_magic(&mut rdr, *RECORD_MAGIC)?;
+    let xid = XaTransactionId::parse(&read_bytes(&mut rdr)?, 1, false)
+        .map_err(|e| corrupt(&e.to_string()))?
+        .pop()
+        .ok_or_else(|| corrupt("missing XaTransactionId"))?;
+    let mut changes = Vec::new();
+    for _ in 0..rdr.read_u32::<LittleEndian>()? {
+        changes.push(match rdr.read_u8()? {
+            0 => {
+                let k = read_string(&mut rdr)?;
+                Change::Put(k, read_bytes(&mut rdr)?)
+            }
+            1 => Change::Delete(read_string(&mut rdr)?),
+            _ => return Err(corrupt("unknown change type")),
+        });
+    }
+    Ok((xid, changes))
+}
+
+fn corrupt(s: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("corrupt FileStore file: {s}"),
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::FileStore;
+    use crate::{ErrorCode, Flags, ReturnCode, XaTransactionId};
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    // Returns a fresh, empty directory below the system's temp directory.
+    pub(crate) fn test_dir(name: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "dist_tx_{name}_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    fn xid(n: u8) -> XaTransactionId {
+        XaTransactionId::try_new(1, vec![n], vec![0]).unwrap()
+    }
+
+    fn prepare_with(store: &FileStore, xid: &XaTransactionId, key: &str, value: &str) {
+        store.xa_start(xid, Flags::default()).unwrap();
+        store.put(key, value).unwrap();
+        store.xa_end(xid, Flags::SUCCESS).unwrap();
+        store.xa_prepare(xid).unwrap();
+    }
+
+    #[test]
+    fn test_prepared_branches_survive_reopen() {
+        let dir = test_dir("file_store");
+        {
+            let store = FileStore::open(&dir).unwrap();
+            prepare_with(&store, &xid(1), "a", "1");
+            prepare_with(&store, &xid(2), "b", "2");
+            assert_eq!(store.get("a"), None);
+        }
+
+        let store = FileStore::open(&dir).unwrap();
+        let recovered = store.xa_recover(Flags::START_RECOVERY_SCAN).unwrap();
+        assert_eq!(recovered.len(), 2);
+        store.xa_commit(&xid(1), Flags::default()).unwrap();
+        store.xa_rollback(&xid(2)).unwrap();
+        assert_eq!(store.get("a"), Some(b"1".to_vec()));
+        assert_eq!(store.get("b"), None);
+
+        let store = FileStore::open(&dir).unwrap();
+        assert!(store
+            .xa_recover(Flags::START_RECOVERY_SCAN | Flags::END_RECOVERY_SCAN)
+            .unwrap()
+            .is_empty());
+        assert_eq!(store.committed_keys(), vec!["a".to_string()]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_long_xid() {
+        let dir = test_dir("file_store");
+        let long = XaTransactionId::try_new(1, vec![0xab; 64], vec![0xcd; 64]).unwrap();
+        {
+            let store = FileStore::open(&dir).unwrap();
+            prepare_with(&store, &long, "a", "1");
+        }
+
+        let store = FileStore::open(&dir).unwrap();
+        assert_eq!(
+            store.xa_recover(Flags::START_RECOVERY_SCAN).unwrap(),
+            vec![long]
+        );
+        store.xa_commit(&long, Flags::default()).unwrap();
+        assert_eq!(store.get("a"), Some(b"1".to_vec()));
+        assert_eq!(
+            std::fs::read_dir(dir.join(super::PREPARED_DIR))
+                .unwrap()
+                .count(),
+            0
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_protocol_checks() {
+        let dir = test_dir("file_store");
+        let store = FileStore::open(&dir).unwrap();
+        assert!(matches!(
+            store.put("x", "y").unwrap_err().get_code(),
+            ErrorCode::ProtocolError
+        ));
+
+        store.xa_start(&xid(1), Flags::default()).unwrap();
+        assert!(matches!(
+            store.xa_prepare(&xid(1)).unwrap_err().get_code(),
+            ErrorCode::ProtocolError
+        ));
+        store.xa_end(&xid(1), Flags::FAIL).unwrap();
+        assert!(matches!(
+            store
+                .xa_start(&xid(1), Flags::default())
+                .unwrap_err()
+                .get_code(),
+            ErrorCode::DuplicateTransactionId
+        ));
+        assert!(matches!(
+            store.xa_prepare(&xid(1)),
+            Ok(ReturnCode::RollbackUnspecified)
+        ));
+        assert!(matches!(
+            store
+                .xa_commit(&xid(1), Flags::default())
+                .unwrap_err()
+                .get_code(),
+            ErrorCode::InvalidTransactionId
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -3,6 +3,11 @@ bitflags::bitflags! {
     /// ([`sync::rm::ResourceManager`](sync/rm/trait.ResourceManager.html) and
     /// [`a_sync::rm::ResourceManager`](a_sync/rm/trait.ResourceManager.html)).
     #[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(transparent)
+    )]
     pub struct Flags: u32 {
         // /  No resource manager feature selected.
         // const NO_FLAGS = 0;
@@ -71,4 +76,14 @@ mod test {
         assert!(Flags::default().contains_only(pattern));
         assert!(!Flags::RESUME.contains_only(pattern));
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde_test::{assert_tokens, Configure, Token};
+
+        let flags = Flags::RESUME | Flags::JOIN;
+        assert_tokens(&flags.readable(), &[Token::Str("RESUME | JOIN")]);
+        assert_tokens(&flags.compact(), &[Token::U32(0x08_20_00_00)]);
+    }
 }
@@ -0,0 +1,197 @@
+use crate::XaTransactionId;
+
+// Size of a Narayana `Uid`: two longs (host address) and three ints (process, seconds, counter).
+const NARAYANA_UID_LEN: usize = 28;
+
+/// The Java transaction managers whose `XaTransactionId`s can be decoded with [`JavaXid`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JavaTm {
+    /// Narayana (JBoss/WildFly transactions), JTA mode.
+    Narayana,
+    /// Atomikos `TransactionsEssentials`.
+    Atomikos,
+}
+impl JavaTm {
+    /// All supported transaction managers.
+    pub const ALL: [JavaTm; 2] = [JavaTm::Narayana, JavaTm::Atomikos];
+
+    /// Returns the `format_id` of the `XaTransactionId`s of the transaction manager.
+    #[must_use]
+    pub fn format_id(self) -> i32 {
+        match self {
+            JavaTm::Narayana => 131_077,
+            // "ATOM"
+            JavaTm::Atomikos => 0x4154_4F4D,
+        }
+    }
+}
+impl std::fmt::Display for JavaTm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.pad(match self {
+            JavaTm::Narayana => "Narayana",
+            JavaTm::Atomikos => "Atomikos",
+        })
+    }
+}
+
+/// The parts of an `XaTransactionId` that was produced by a Java transaction manager.
+///
+/// * Narayana: the global transaction id is the `Uid` of the transaction (28 bytes)
+///   followed by the node name; the branch qualifier is the `Uid` of the branch,
+///   optionally followed by the EIS name (a 4-byte integer).
+///   The `Uid`s are given in their string form, e.g. `0:ffff7f000001:a4f1:5f7c1d4a:2`.
+/// * Atomikos: the global transaction id and the branch qualifier are strings;
+///   the global transaction id is the unique name of the transaction manager
+///   followed by a number.
+///
+/// Such branches belong to the Java transaction manager; a `SimpleTransactionManager`
+/// leaves them alone during recovery.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JavaXid {
+    /// The transaction manager that produced the id.
+    pub tm: JavaTm,
+    /// The name of the node (Narayana) or the unique name (Atomikos) of the
+    /// transaction manager.
+    pub node_name: String,
+    /// The id of the global transaction.
+    pub transaction_uid: String,
+    /// The id of the branch.
+    pub branch_uid: String,
+    /// The EIS name of the branch (only Narayana).
+    pub eis_name: Option<i32>,
+}
+impl JavaXid {
+    /// Decodes the given id, or returns `None` if it has none of the supported formats.
+    #[must_use]
+    pub fn decode(xid: &XaTransactionId) -> Option<JavaXid> {
+        match JavaTm::ALL
+            .into_iter()
+            .find(|tm| tm.format_id() == xid.get_format_id())?
+        {
+            JavaTm::Narayana => decode_narayana(xid),
+            JavaTm::Atomikos => decode_atomikos(xid),
+        }
+    }
+}
+impl std::fmt::Display for JavaXid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} node {}, tx {}, branch {}",
+            self.tm, self.node_name, self.transaction_uid, self.branch_uid
+        )?;
+        if let Some(eis_name) = self.eis_name {
+            write!(f, ", eis_name {eis_name}")?;
+        }
+        Ok(())
+    }
+}
+
+fn decode_narayana(xid: &XaTransactionId) -> Option<JavaXid> {
+    let gtrid = xid.get_global_tid();
+    let bqual = xid.get_branch_qualifier();
+    if gtrid.len() < NARAYANA_UID_LEN || bqual.len() < NARAYANA_UID_LEN {
+        return None;
+    }
+    let (uid, node_name) = gtrid.split_at(NARAYANA_UID_LEN);
+    let (branch_uid, rest) = bqual.split_at(NARAYANA_UID_LEN);
+    Some(JavaXid {
+        tm: JavaTm::Narayana,
+        node_name: String::from_utf8_lossy(node_name)
+            .trim_end_matches('\0')
+            .to_string(),
+        transaction_uid: narayana_uid(uid),
+        branch_uid: narayana_uid(branch_uid),
+        eis_name: rest
+            .get(..4)
+            .map(|bytes| i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+    })
+}
+
+// The string form of a Narayana `Uid`, as produced by `Uid.stringForm()`.
+fn narayana_uid(bytes: &[u8]) -> String {
+    let long = |i: usize| u64::from_be_bytes(bytes[i..i + 8].try_into().unwrap_or_default());
+    let int = |i: usize| u32::from_be_bytes(bytes[i..i + 4].try_into().unwrap_or_default());
+    format!(
+        "{:x}:{:x}:{:x}:{:x}:{:x}",
+        long(0),
+        long(8),
+        int(16),
+        int(20),
+        int(24)
+    )
+}
+
+fn decode_atomikos(xid: &XaTransactionId) -> Option<JavaXid> {
+    let transaction_uid = std::str::from_utf8(xid.get_global_tid()).ok()?;
+    let branch_uid = std::str::from_utf8(xid.get_branch_qualifier()).ok()?;
+    Some(JavaXid {
+        tm: JavaTm::Atomikos,
+        node_name: transaction_uid
+            .trim_end_matches(|c: char| c.is_ascii_digit())
+            .to_string(),
+        transaction_uid: transaction_uid.to_string(),
+        branch_uid: branch_uid.to_string(),
+        eis_name: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JavaTm, JavaXid};
+    use crate::{SimpleXid, XaTransactionId};
+
+    fn uid(counter: u32) -> Vec<u8> {
+        let mut uid = Vec::new();
+        uid.extend_from_slice(&0_u64.to_be_bytes());
+        uid.extend_from_slice(&0xffff_7f00_0001_u64.to_be_bytes());
+        uid.extend_from_slice(&0xa4f1_u32.to_be_bytes());
+        uid.extend_from_slice(&0x5f7c_1d4a_u32.to_be_bytes());
+        uid.extend_from_slice(&counter.to_be_bytes());
+        uid
+    }
+
+    #[test]
+    fn test_narayana() {
+        let mut gtrid = uid(2);
+        gtrid.extend_from_slice(b"node1");
+        let mut bqual = uid(5);
+        bqual.extend_from_slice(&3_i32.to_be_bytes());
+        let xid = XaTransactionId::try_new(131_077, gtrid, bqual).unwrap();
+
+        let parts = JavaXid::decode(&xid).unwrap();
+        assert_eq!(parts.tm, JavaTm::Narayana);
+        assert_eq!(parts.node_name, "node1");
+        assert_eq!(parts.transaction_uid, "0:ffff7f000001:a4f1:5f7c1d4a:2");
+        assert_eq!(parts.branch_uid, "0:ffff7f000001:a4f1:5f7c1d4a:5");
+        assert_eq!(parts.eis_name, Some(3));
+        assert_eq!(
+            parts.to_string(),
+            "Narayana node node1, tx 0:ffff7f000001:a4f1:5f7c1d4a:2, \
+             branch 0:ffff7f000001:a4f1:5f7c1d4a:5, eis_name 3"
+        );
+
+        let short = XaTransactionId::try_new(131_077, vec![0; 27], uid(5)).unwrap();
+        assert_eq!(JavaXid::decode(&short), None);
+    }
+
+    #[test]
+    fn test_atomikos() {
+        let xid =
+            XaTransactionId::try_new(0x4154_4F4D, "10.0.0.7.tm170001", "10.0.0.7.tm2").unwrap();
+        let parts = JavaXid::decode(&xid).unwrap();
+        assert_eq!(parts.tm, JavaTm::Atomikos);
+        assert_eq!(parts.node_name, "10.0.0.7.tm");
+        assert_eq!(parts.transaction_uid, "10.0.0.7.tm170001");
+        assert_eq!(parts.branch_uid, "10.0.0.7.tm2");
+        assert_eq!(parts.eis_name, None);
+
+        let simple = SimpleXid {
+            global_tid: 1,
+            tm_id: 256,
+            rm_id: 1,
+        }
+        .encode();
+        assert_eq!(JavaXid::decode(&simple), None);
+    }
+}
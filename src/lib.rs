@@ -23,16 +23,90 @@ pub mod a_sync;
 #[cfg_attr(docsrs, doc(cfg(feature = "sync")))]
 pub mod sync;
 
+#[cfg(all(feature = "audit", any(feature = "sync", feature = "async")))]
+mod audit_log;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod branch_state;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod call_log;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod conformance;
 mod error_code;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod fault;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod file_store;
 mod flags;
+mod java_xid;
+#[cfg(all(feature = "metrics", any(feature = "sync", feature = "async")))]
+mod metrics;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod mysql;
 mod return_code;
 mod rm_error;
+mod rm_method;
+mod simple_xid;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod tm_event;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod tm_log;
 mod xa_error;
+mod xa_outcome;
+#[cfg(all(feature = "tracing", any(feature = "sync", feature = "async")))]
+mod xa_span;
 mod xa_transaction_id;
+mod xid_codec;
+mod xid_decoder;
+#[cfg(feature = "cli")]
+mod xid_format;
+#[cfg(any(feature = "sync", feature = "async"))]
+mod xid_generator;
 
+#[cfg(all(feature = "audit", any(feature = "sync", feature = "async")))]
+#[cfg_attr(docsrs, doc(cfg(feature = "audit")))]
+pub use audit_log::{AuditLog, AuditReader, AuditRecord};
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use branch_state::{BranchState, ValidationMode};
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use call_log::{CallLog, RecordedCall};
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use conformance::{ConformanceReport, ScenarioResult};
 pub use error_code::ErrorCode;
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use fault::{Fault, FaultPlan, InjectedFault};
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use file_store::FileStore;
 pub use flags::Flags;
+pub use java_xid::{JavaTm, JavaXid};
+#[cfg(all(feature = "metrics", any(feature = "sync", feature = "async")))]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub use metrics::{Histogram, InMemoryRecorder, Metrics, MetricsRecorder, LATENCY_BUCKETS};
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use mysql::MySqlError;
 pub use return_code::ReturnCode;
 pub use rm_error::RmError;
+pub use rm_method::RmMethod;
+pub use simple_xid::SimpleXid;
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use tm_event::{TmEvent, TmEventListener};
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use tm_log::{FileTmLog, TmLog};
 pub use xa_error::XaError;
+pub use xa_outcome::{CompletionOutcome, HeuristicKind, PrepareVote, RollbackReason};
 pub use xa_transaction_id::XaTransactionId;
+pub use xid_codec::{Endianness, XidCodec, XidLayout};
+pub use xid_decoder::XidDecoder;
+#[cfg(feature = "cli")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cli")))]
+pub use xid_format::XidFormat;
+#[cfg(any(feature = "sync", feature = "async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sync", feature = "async"))))]
+pub use xid_generator::{SimpleXidGenerator, XidGenerator};
@@ -0,0 +1,258 @@
+use crate::{ErrorCode, ReturnCode, RmError, RmMethod, XaTransactionId};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// Receives the metrics of a `SimpleTransactionManager` and of `MeteredRm`.
+///
+/// The transaction managers report the transaction counters and the number of
+/// in-doubt transactions; wrap the resource managers into `MeteredRm` to get
+/// the call latencies, the error counts, and the heuristic outcomes.
+///
+/// Implement this trait to forward the metrics to a monitoring system,
+/// or use [`InMemoryRecorder`].
+pub trait MetricsRecorder: Debug + Send + Sync {
+    /// A global transaction was started.
+    fn transaction_started(&self);
+
+    /// A global transaction was committed.
+    fn transaction_committed(&self);
+
+    /// A global transaction was rolled back.
+    fn transaction_rolled_back(&self);
+
+    /// The number of transactions whose commit was decided, but could not yet be completed
+    /// by all resource managers, has changed.
+    fn in_doubt(&self, count: usize);
+
+    /// A resource manager reported a heuristic outcome.
+    fn heuristic_outcome(&self, rm_id: u64, return_code: &ReturnCode);
+
+    /// A call to a resource manager was completed, successfully or not.
+    fn call_latency(&self, rm_id: u64, method: RmMethod, duration: Duration);
+
+    /// A call to a resource manager failed.
+    fn call_error(&self, rm_id: u64, method: RmMethod, error_code: &ErrorCode);
+}
+
+/// Upper bounds of the buckets of a [`Histogram`]; a last bucket takes all longer durations.
+pub const LATENCY_BUCKETS: [Duration; 10] = [
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2_500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+
+/// A latency histogram with the buckets of [`LATENCY_BUCKETS`].
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    counts: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: Duration,
+}
+impl Histogram {
+    /// Returns the number of observed durations.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the sum of the observed durations.
+    #[must_use]
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Returns the upper bound and the number of observations of each bucket;
+    /// the upper bound of the last bucket is `Duration::MAX`.
+    #[must_use]
+    pub fn buckets(&self) -> Vec<(Duration, u64)> {
+        LATENCY_BUCKETS
+            .iter()
+            .copied()
+            .chain(std::iter::once(Duration::MAX))
+            .zip(self.counts.iter().copied())
+            .collect()
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let idx = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| duration <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[idx] += 1;
+        self.sum += duration;
+    }
+}
+
+/// The metrics collected by an [`InMemoryRecorder`].
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    /// Number of started global transactions.
+    pub transactions_started: u64,
+    /// Number of committed global transactions.
+    pub transactions_committed: u64,
+    /// Number of rolled back global transactions.
+    pub transactions_rolled_back: u64,
+    /// Number of heuristic outcomes reported by resource managers.
+    pub heuristic_outcomes: u64,
+    /// Current number of in-doubt transactions.
+    pub in_doubt: usize,
+    /// Latencies of the calls, per `rm_id` and method.
+    pub latencies: BTreeMap<(u64, RmMethod), Histogram>,
+    /// Number of failed calls, per error code.
+    pub errors: BTreeMap<ErrorCode, u64>,
+}
+
+/// A [`MetricsRecorder`] that keeps the metrics in memory.
+///
+/// `InMemoryRecorder` is a cheap handle, clones share the same state, so that a clone
+/// can be kept to read the metrics after others were handed over to the transaction
+/// manager and the resource managers.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryRecorder(Arc<Mutex<Metrics>>);
+impl InMemoryRecorder {
+    /// Creates a recorder without any metrics.
+    #[must_use]
+    pub fn new() -> InMemoryRecorder {
+        InMemoryRecorder::default()
+    }
+
+    /// Returns a copy of the current metrics.
+    #[must_use]
+    pub fn snapshot(&self) -> Metrics {
+        self.lock().clone()
+    }
+
+    /// Resets all metrics.
+    pub fn clear(&self) {
+        *self.lock() = Metrics::default();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Metrics> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+impl MetricsRecorder for InMemoryRecorder {
+    fn transaction_started(&self) {
+        self.lock().transactions_started += 1;
+    }
+
+    fn transaction_committed(&self) {
+        self.lock().transactions_committed += 1;
+    }
+
+    fn transaction_rolled_back(&self) {
+        self.lock().transactions_rolled_back += 1;
+    }
+
+    fn in_doubt(&self, count: usize) {
+        self.lock().in_doubt = count;
+    }
+
+    fn heuristic_outcome(&self, _rm_id: u64, _return_code: &ReturnCode) {
+        self.lock().heuristic_outcomes += 1;
+    }
+
+    fn call_latency(&self, rm_id: u64, method: RmMethod, duration: Duration) {
+        self.lock()
+            .latencies
+            .entry((rm_id, method))
+            .or_default()
+            .observe(duration);
+    }
+
+    fn call_error(&self, _rm_id: u64, _method: RmMethod, error_code: &ErrorCode) {
+        *self.lock().errors.entry(error_code.clone()).or_default() += 1;
+    }
+}
+
+// The metrics state of a transaction manager.
+#[derive(Debug, Default)]
+pub(crate) struct TmMetrics {
+    recorder: Option<Box<dyn MetricsRecorder>>,
+    in_doubt: BTreeSet<u64>,
+}
+impl TmMetrics {
+    pub(crate) fn set_recorder(&mut self, recorder: Box<dyn MetricsRecorder>) {
+        self.recorder = Some(recorder);
+    }
+
+    pub(crate) fn started(&self) {
+        if let Some(ref recorder) = self.recorder {
+            recorder.transaction_started();
+        }
+    }
+
+    pub(crate) fn committed(&self) {
+        if let Some(ref recorder) = self.recorder {
+            recorder.transaction_committed();
+        }
+    }
+
+    pub(crate) fn rolled_back(&self) {
+        if let Some(ref recorder) = self.recorder {
+            recorder.transaction_rolled_back();
+        }
+    }
+
+    // The commit of these transactions was decided, but not completed.
+    pub(crate) fn add_in_doubt(&mut self, global_tids: &[u64]) {
+        self.in_doubt.extend(global_tids);
+        self.report_in_doubt();
+    }
+
+    // Only these transactions are still in doubt.
+    pub(crate) fn set_in_doubt(&mut self, global_tids: &[u64]) {
+        self.in_doubt.clear();
+        self.add_in_doubt(global_tids);
+    }
+
+    fn report_in_doubt(&self) {
+        if let Some(ref recorder) = self.recorder {
+            recorder.in_doubt(self.in_doubt.len());
+        }
+    }
+}
+
+pub(crate) fn record_result<R: Clone + Into<ReturnCode>>(
+    recorder: &dyn MetricsRecorder,
+    rm_id: u64,
+    method: RmMethod,
+    start: Instant,
+    result: &Result<R, RmError>,
+) {
+    recorder.call_latency(rm_id, method, start.elapsed());
+    match result {
+        Ok(outcome) => {
+            let rc: ReturnCode = outcome.clone().into();
+            if rc.is_heuristic() {
+                recorder.heuristic_outcome(rm_id, &rc);
+            }
+        }
+        Err(e) => recorder.call_error(rm_id, method, &e.get_code()),
+    }
+}
+
+pub(crate) fn record_recover(
+    recorder: &dyn MetricsRecorder,
+    rm_id: u64,
+    method: RmMethod,
+    start: Instant,
+    result: &Result<Vec<XaTransactionId>, RmError>,
+) {
+    recorder.call_latency(rm_id, method, start.elapsed());
+    if let Err(e) = result {
+        recorder.call_error(rm_id, method, &e.get_code());
+    }
+}
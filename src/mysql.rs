@@ -0,0 +1,162 @@
+use crate::{
+    xa_transaction_id::{hex, unhex},
+    ErrorCode, ReturnCode, RmError, XaTransactionId,
+};
+
+/// An error reported by a `MySQL` or `MariaDB` server.
+///
+/// Implementations of the `MySqlExecutor` traits
+/// ([`sync::rm::MySqlExecutor`](crate::sync::rm::MySqlExecutor) and
+/// [`a_sync::rm::MySqlExecutor`](crate::a_sync::rm::MySqlExecutor))
+/// must provide the server's error number, so that XA errors can be mapped
+/// onto `ErrorCode` and `ReturnCode`.
+#[derive(Clone, Debug)]
+pub struct MySqlError {
+    /// The server error number, e.g. `1397` for `ER_XAER_NOTA`.
+    pub number: u16,
+    /// The error message.
+    pub message: String,
+}
+impl MySqlError {
+    /// Factory method.
+    #[must_use]
+    pub fn new<S: Into<String>>(number: u16, message: S) -> MySqlError {
+        MySqlError {
+            number,
+            message: message.into(),
+        }
+    }
+}
+impl std::fmt::Display for MySqlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "MySQL error {}: {}", self.number, self.message)
+    }
+}
+impl std::error::Error for MySqlError {}
+
+// Server error numbers of the XA errors
+const ER_XAER_NOTA: u16 = 1397;
+const ER_XAER_INVAL: u16 = 1398;
+const ER_XAER_RMFAIL: u16 = 1399;
+const ER_XAER_OUTSIDE: u16 = 1400;
+const ER_XA_RBROLLBACK: u16 = 1402;
+const ER_XAER_DUPID: u16 = 1440;
+const ER_XA_RBTIMEOUT: u16 = 1613;
+const ER_XA_RBDEADLOCK: u16 = 1614;
+
+// Maps the XA rollback errors onto the respective `ReturnCode`,
+// and all other errors onto an `RmError`.
+pub(crate) fn map_error(e: MySqlError) -> Result<ReturnCode, RmError> {
+    match e.number {
+        ER_XA_RBROLLBACK => Ok(ReturnCode::RollbackUnspecified),
+        ER_XA_RBTIMEOUT => Ok(ReturnCode::RollbackTimeout),
+        ER_XA_RBDEADLOCK => Ok(ReturnCode::RollbackDeadlock),
+        _ => Err(rm_error(e)),
+    }
+}
+
+// Maps any error onto an `RmError`; used for `XA RECOVER`, which has no
+// outcome that could be reported as a `ReturnCode`.
+pub(crate) fn rm_error(e: MySqlError) -> RmError {
+    let code = match e.number {
+        ER_XAER_NOTA => ErrorCode::InvalidTransactionId,
+        ER_XAER_INVAL => ErrorCode::InvalidArguments,
+        ER_XAER_RMFAIL => ErrorCode::RmFailure,
+        ER_XAER_OUTSIDE => ErrorCode::OutsideGlobalTransaction,
+        ER_XAER_DUPID => ErrorCode::DuplicateTransactionId,
+        // ER_XAER_RMERR (1401), and all errors that are not specific to XA
+        _ => ErrorCode::RmError,
+    };
+    RmError::new(code, e.to_string()).with_source(e)
+}
+
+// Returns the xid in the syntax of the XA statements: `X'gtrid',X'bqual',formatID`.
+pub(crate) fn xid_literal(id: &XaTransactionId) -> String {
+    format!(
+        "X'{}',X'{}',{}",
+        hex(id.get_global_tid()),
+        hex(id.get_branch_qualifier()),
+        id.get_format_id()
+    )
+}
+
+// The statement that lists the prepared branches, in the format parsed by `parse_recover_row`.
+pub(crate) const RECOVER_STATEMENT: &str = "XA RECOVER CONVERT XID";
+
+// Parses a row of the result of `XA RECOVER CONVERT XID`, which has the columns
+// formatID, gtrid_length, bqual_length, and data (the hex-encoded concatenation of
+// gtrid and bqual, with the prefix `0x`).
+pub(crate) fn parse_recover_row(row: &[String]) -> Result<XaTransactionId, RmError> {
+    let bad_row = || {
+        RmError::new(
+            ErrorCode::RmError,
+            format!("unexpected row in result of {RECOVER_STATEMENT}: {row:?}"),
+        )
+    };
+    let [format_id, gtrid_length, bqual_length, data] = row else {
+        return Err(bad_row());
+    };
+    let format_id: i32 = format_id.trim().parse().map_err(|_| bad_row())?;
+    let gtrid_length: usize = gtrid_length.trim().parse().map_err(|_| bad_row())?;
+    let bqual_length: usize = bqual_length.trim().parse().map_err(|_| bad_row())?;
+
+    let data = data.trim();
+    let data = data
+        .strip_prefix("0x")
+        .or_else(|| data.strip_prefix("0X"))
+        .ok_or_else(bad_row)?;
+    if data.len() != 2 * (gtrid_length + bqual_length) {
+        return Err(bad_row());
+    }
+    let bytes = unhex(data).ok_or_else(bad_row)?;
+    let (gtrid, bqual) = bytes.split_at(gtrid_length);
+    XaTransactionId::try_new(format_id, gtrid, bqual).map_err(|_| bad_row())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{map_error, parse_recover_row, rm_error, xid_literal, MySqlError};
+    use crate::{ErrorCode, ReturnCode, XaTransactionId};
+
+    #[test]
+    fn test_xid_round_trip() {
+        let xid = XaTransactionId::try_new(99, vec![0x01, 0xAB], vec![0xFF]).unwrap();
+        assert_eq!(xid_literal(&xid), "X'01AB',X'FF',99");
+
+        let row: Vec<String> = ["99", "2", "1", "0x01ABFF"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let parsed = parse_recover_row(&row).unwrap();
+        assert_eq!(parsed.as_bytes(false), xid.as_bytes(false));
+
+        for bad in [["99", "2", "2", "0x01ABFF"], ["99", "2", "1", "01ABFF"]] {
+            let row: Vec<String> = bad.iter().map(ToString::to_string).collect();
+            assert!(parse_recover_row(&row).is_err());
+        }
+    }
+
+    #[test]
+    fn test_map_error() {
+        assert!(matches!(
+            map_error(MySqlError::new(1614, "deadlock")),
+            Ok(ReturnCode::RollbackDeadlock)
+        ));
+        assert!(matches!(
+            map_error(MySqlError::new(1397, "unknown XID"))
+                .unwrap_err()
+                .get_code(),
+            ErrorCode::InvalidTransactionId
+        ));
+        assert!(matches!(
+            map_error(MySqlError::new(1399, "wrong state"))
+                .unwrap_err()
+                .get_code(),
+            ErrorCode::RmFailure
+        ));
+        assert_eq!(
+            rm_error(MySqlError::new(1614, "deadlock")).code(),
+            &ErrorCode::RmError
+        );
+    }
+}
@@ -1,5 +1,10 @@
 /// Return codes used by resource managers.
-#[derive(Clone, Debug)]
+///
+/// The variants correspond to the non-negative return codes of the XA standard (`xa.h`);
+/// [`from_i32`](ReturnCode::from_i32) and [`to_i32`](ReturnCode::to_i32) convert
+/// from and to the numeric codes, `Display` shows the symbolic name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ReturnCode {
     /// A rollback was caused by an unspecified reason.
     RollbackUnspecified,
@@ -31,6 +36,8 @@ pub enum ReturnCode {
     Retry,
     /// The transaction branch was read-only and has been committed.
     ReadOnlyCommitted,
+    /// The resumption must occur where the suspension occurred.
+    NoMigrate,
 
     /// Normal execution.
     Ok,
@@ -57,10 +64,137 @@ impl ReturnCode {
             6 => ReturnCode::HeuristicallyRolledBack,
             5 => ReturnCode::HeuristicallyMessedUp,
 
+            9 => ReturnCode::NoMigrate,
             4 => ReturnCode::Retry,
             3 => ReturnCode::ReadOnlyCommitted,
             0 => ReturnCode::Ok,
             i => ReturnCode::UnknownErrorCode(i),
         }
     }
+
+    /// Returns the return code as defined in the XA standard.
+    #[must_use]
+    pub fn to_i32(&self) -> i32 {
+        match self {
+            ReturnCode::RollbackUnspecified => 100,
+            ReturnCode::RollbackCommunicationFailure => 101,
+            ReturnCode::RollbackDeadlock => 102,
+            ReturnCode::RollbackIntegrity => 103,
+            ReturnCode::RollbackOther => 104,
+            ReturnCode::RollbackProtocol => 105,
+            ReturnCode::RollbackTimeout => 106,
+            ReturnCode::RollbackTransient => 107,
+
+            ReturnCode::HeuristicallyCompleted => 8,
+            ReturnCode::HeuristicallyCommitted => 7,
+            ReturnCode::HeuristicallyRolledBack => 6,
+            ReturnCode::HeuristicallyMessedUp => 5,
+
+            ReturnCode::NoMigrate => 9,
+            ReturnCode::Retry => 4,
+            ReturnCode::ReadOnlyCommitted => 3,
+            ReturnCode::Ok => 0,
+            ReturnCode::UnknownErrorCode(i) => *i,
+        }
+    }
+
+    /// Returns the symbolic name of the return code in `xa.h`, e.g. `XA_RBDEADLOCK`,
+    /// or `None` for unknown codes.
+    #[must_use]
+    pub fn xa_name(&self) -> Option<&'static str> {
+        Some(match self {
+            ReturnCode::RollbackUnspecified => "XA_RBROLLBACK",
+            ReturnCode::RollbackCommunicationFailure => "XA_RBCOMMFAIL",
+            ReturnCode::RollbackDeadlock => "XA_RBDEADLOCK",
+            ReturnCode::RollbackIntegrity => "XA_RBINTEGRITY",
+            ReturnCode::RollbackOther => "XA_RBOTHER",
+            ReturnCode::RollbackProtocol => "XA_RBPROTO",
+            ReturnCode::RollbackTimeout => "XA_RBTIMEOUT",
+            ReturnCode::RollbackTransient => "XA_RBTRANSIENT",
+
+            ReturnCode::HeuristicallyCompleted => "XA_HEURHAZ",
+            ReturnCode::HeuristicallyCommitted => "XA_HEURCOM",
+            ReturnCode::HeuristicallyRolledBack => "XA_HEURRB",
+            ReturnCode::HeuristicallyMessedUp => "XA_HEURMIX",
+
+            ReturnCode::NoMigrate => "XA_NOMIGRATE",
+            ReturnCode::Retry => "XA_RETRY",
+            ReturnCode::ReadOnlyCommitted => "XA_RDONLY",
+            ReturnCode::Ok => "XA_OK",
+            ReturnCode::UnknownErrorCode(_) => return None,
+        })
+    }
+
+    /// Returns `true` if the transaction branch was rolled back (`XA_RB*`).
+    #[must_use]
+    pub fn is_rollback(&self) -> bool {
+        matches!(
+            self,
+            ReturnCode::RollbackUnspecified
+                | ReturnCode::RollbackCommunicationFailure
+                | ReturnCode::RollbackDeadlock
+                | ReturnCode::RollbackIntegrity
+                | ReturnCode::RollbackOther
+                | ReturnCode::RollbackProtocol
+                | ReturnCode::RollbackTimeout
+                | ReturnCode::RollbackTransient
+        )
+    }
+
+    /// Returns `true` if the transaction branch was completed heuristically (`XA_HEUR*`).
+    #[must_use]
+    pub fn is_heuristic(&self) -> bool {
+        matches!(
+            self,
+            ReturnCode::HeuristicallyCompleted
+                | ReturnCode::HeuristicallyCommitted
+                | ReturnCode::HeuristicallyRolledBack
+                | ReturnCode::HeuristicallyMessedUp
+        )
+    }
+
+    /// Returns `true` if the request had no effect and may be repeated (`XA_RETRY`),
+    /// or if a new transaction branch may succeed after a transient failure
+    /// (`XA_RBTRANSIENT`).
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ReturnCode::Retry | ReturnCode::RollbackTransient)
+    }
+}
+impl std::fmt::Display for ReturnCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.xa_name() {
+            Some(name) => f.pad(name),
+            None => f.pad(&format!("unknown return code {}", self.to_i32())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReturnCode;
+
+    #[test]
+    fn test_xa_codes() {
+        for i in (-1..=10).chain(99..=108) {
+            let rc = ReturnCode::from_i32(i);
+            assert_eq!(rc.to_i32(), i);
+            assert_eq!(
+                rc.xa_name().is_some(),
+                matches!(i, 0 | 3..=9 | 100..=107),
+                "{i}"
+            );
+            assert_eq!(rc.is_rollback(), (100..=107).contains(&i), "{i}");
+            assert_eq!(rc.is_heuristic(), (5..=8).contains(&i), "{i}");
+        }
+        assert_eq!(ReturnCode::from_i32(9), ReturnCode::NoMigrate);
+        assert_eq!(ReturnCode::RollbackDeadlock.to_string(), "XA_RBDEADLOCK");
+        assert_eq!(format!("{:>7}", ReturnCode::Ok), "  XA_OK");
+        assert_eq!(
+            ReturnCode::from_i32(42).to_string(),
+            "unknown return code 42"
+        );
+        assert!(ReturnCode::Retry.is_retryable());
+        assert!(!ReturnCode::RollbackDeadlock.is_retryable());
+    }
 }
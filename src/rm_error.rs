@@ -1,28 +1,177 @@
 use super::error_code::ErrorCode;
+use crate::XaTransactionId;
+use std::error::Error;
 
 /// Represents the possible errors that can occur in a `ResourceManager` (
 /// [`sync::rm::ResourceManager`](sync/rm/trait.ResourceManager.html) and
 /// [`a_sync::rm::ResourceManager`](a_sync/rm/trait.ResourceManager.html)
 /// ).
+///
+/// Besides the [`ErrorCode`] and a description, the error can carry the underlying
+/// error of the driver as its [`source`](Error::source), and, as context, the id of the
+/// transaction branch and the id of the resource manager.
+/// `SimpleTransactionManager` adds the context to the errors of its resource managers.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RmError {
+    #[cfg_attr(feature = "serde", serde(rename = "code"))]
     c: ErrorCode,
+    #[cfg_attr(feature = "serde", serde(rename = "description"))]
     s: String,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    source: Option<Box<dyn Error + Send + Sync>>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    // boxed, because the id is large
+    xid: Option<Box<XaTransactionId>>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    rm_id: Option<u64>,
 }
 impl RmError {
     /// Factory method.
     #[must_use]
     pub fn new(c: ErrorCode, s: String) -> RmError {
-        RmError { c, s }
+        RmError {
+            c,
+            s,
+            source: None,
+            xid: None,
+            rm_id: None,
+        }
     }
+
+    /// Attaches the underlying error, e.g. the one of the database driver.
+    #[must_use]
+    pub fn with_source<E: Into<Box<dyn Error + Send + Sync>>>(mut self, source: E) -> RmError {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Attaches the id of the transaction branch in which the error occured.
+    #[must_use]
+    pub fn with_xid(mut self, xid: XaTransactionId) -> RmError {
+        self.xid = Some(Box::new(xid));
+        self
+    }
+
+    /// Attaches the id under which the resource manager is registered.
+    #[must_use]
+    pub fn with_rm_id(mut self, rm_id: u64) -> RmError {
+        self.rm_id = Some(rm_id);
+        self
+    }
+
+    /// Returns the kind of error that has occured.
+    #[must_use]
+    pub fn code(&self) -> &ErrorCode {
+        &self.c
+    }
+
+    /// Returns a textual description of the error.
+    #[must_use]
+    pub fn description(&self) -> &str {
+        &self.s
+    }
+
+    /// Returns the id of the transaction branch, if it is known.
+    #[must_use]
+    pub fn xid(&self) -> Option<&XaTransactionId> {
+        self.xid.as_deref()
+    }
+
+    /// Returns the id of the resource manager, if it is known.
+    #[must_use]
+    pub fn rm_id(&self) -> Option<u64> {
+        self.rm_id
+    }
+
     /// Returns the kind of error that has occured.
     #[must_use]
     pub fn get_code(&self) -> ErrorCode {
         self.c.clone()
     }
+
     /// Returns a textual description of the error.
     #[must_use]
     pub fn get_description(&self) -> String {
         self.s.clone()
     }
 }
+impl std::fmt::Display for RmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({}", self.s, self.c)?;
+        if let Some(rm_id) = self.rm_id {
+            write!(f, ", rm_id {rm_id}")?;
+        }
+        if let Some(xid) = &self.xid {
+            write!(f, ", xid {xid}")?;
+        }
+        write!(f, ")")
+    }
+}
+impl Error for RmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RmError;
+    use crate::{ErrorCode, XaTransactionId};
+    use std::error::Error;
+
+    #[test]
+    fn test_source_and_context() {
+        let io_error = std::io::Error::other("disk full");
+        let xid = XaTransactionId::try_new(1, [1, 2], [3]).unwrap();
+        let e = RmError::new(ErrorCode::RmError, "write failed".to_string())
+            .with_source(io_error)
+            .with_xid(xid)
+            .with_rm_id(2);
+        assert_eq!(e.code(), &ErrorCode::RmError);
+        assert_eq!(e.description(), "write failed");
+        assert_eq!(e.xid(), Some(&xid));
+        assert_eq!(e.rm_id(), Some(2));
+        assert_eq!(
+            e.to_string(),
+            "write failed (XAER_RMERR, rm_id 2, xid 1:0102:03)"
+        );
+        assert_eq!(e.source().unwrap().to_string(), "disk full");
+
+        let e = RmError::new(ErrorCode::ProtocolError, "no branch".to_string());
+        assert_eq!(e.to_string(), "no branch (XAER_PROTO)");
+        assert!(e.source().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde_test::{assert_ser_tokens, Token};
+
+        assert_ser_tokens(
+            &RmError::new(ErrorCode::RmFailure, "gone".to_string()),
+            &[
+                Token::Struct {
+                    name: "RmError",
+                    len: 2,
+                },
+                Token::Str("code"),
+                Token::UnitVariant {
+                    name: "ErrorCode",
+                    variant: "RmFailure",
+                },
+                Token::Str("description"),
+                Token::Str("gone"),
+                Token::StructEnd,
+            ],
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use crate::Flags;
+
+/// The methods of the `ResourceManager` traits
+/// ([`sync::rm::ResourceManager`](sync/rm/trait.ResourceManager.html) and
+/// [`a_sync::rm::ResourceManager`](a_sync/rm/trait.ResourceManager.html)).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RmMethod {
+    /// `start()`
+    Start,
+    /// `start_by_joining()`
+    StartByJoining,
+    /// `start_by_resuming()`
+    StartByResuming,
+    /// `end_success()`
+    EndSuccess,
+    /// `end_failure()`
+    EndFailure,
+    /// `end_suspend()`
+    EndSuspend,
+    /// `prepare()`
+    Prepare,
+    /// `commit()`
+    Commit,
+    /// `commit_one_phase()`
+    CommitOnePhase,
+    /// `rollback()`
+    Rollback,
+    /// `forget()`
+    Forget,
+    /// `recover()`
+    Recover,
+    /// `begin_recover()`
+    BeginRecover,
+    /// `end_recover()`
+    EndRecover,
+}
+impl RmMethod {
+    /// Returns the flags with which the method calls the respective function of
+    /// `CResourceManager` in `CRmWrapper`.
+    #[must_use]
+    pub fn flags(self) -> Flags {
+        match self {
+            RmMethod::StartByJoining => Flags::JOIN,
+            RmMethod::StartByResuming => Flags::RESUME,
+            RmMethod::EndSuccess => Flags::SUCCESS,
+            RmMethod::EndFailure => Flags::FAIL,
+            RmMethod::EndSuspend => Flags::SUSPEND,
+            RmMethod::CommitOnePhase => Flags::ONE_PHASE,
+            RmMethod::Recover => Flags::START_RECOVERY_SCAN | Flags::END_RECOVERY_SCAN,
+            RmMethod::BeginRecover => Flags::START_RECOVERY_SCAN,
+            RmMethod::EndRecover => Flags::END_RECOVERY_SCAN,
+            RmMethod::Start
+            | RmMethod::Prepare
+            | RmMethod::Commit
+            | RmMethod::Rollback
+            | RmMethod::Forget => Flags::default(),
+        }
+    }
+
+    /// Returns `true` for the methods that return a list of `XaTransactionId`s
+    /// rather than a `ReturnCode`.
+    #[must_use]
+    pub fn is_recover(self) -> bool {
+        matches!(
+            self,
+            RmMethod::Recover | RmMethod::BeginRecover | RmMethod::EndRecover
+        )
+    }
+}
@@ -0,0 +1,85 @@
+use crate::XaTransactionId;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Cursor;
+
+/// The parts of an `XaTransactionId` that was produced by a `SimpleTransactionManager`
+/// ([`sync::tm::SimpleTransactionManager`](sync/tm/struct.SimpleTransactionManager.html) or
+/// [`a_sync::tm::SimpleTransactionManager`](a_sync/tm/struct.SimpleTransactionManager.html)).
+///
+/// Such ids have the `format_id` 99, the global transaction id as 8 bytes
+/// (u64, little endian), and as branch qualifier the id of the transaction manager
+/// followed by the id of the resource manager, both also as 8 bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SimpleXid {
+    /// The number of the global transaction.
+    pub global_tid: u64,
+    /// The id of the transaction manager (a hash of its name).
+    pub tm_id: u64,
+    /// The id under which the resource manager was registered.
+    pub rm_id: u64,
+}
+impl SimpleXid {
+    /// The `format_id` of the `XaTransactionId`s of `SimpleTransactionManager`.
+    pub const FORMAT_ID: i32 = 99;
+
+    /// Decodes the given id, or returns `None` if it has not the format
+    /// of a `SimpleTransactionManager`.
+    #[must_use]
+    pub fn decode(xid: &XaTransactionId) -> Option<SimpleXid> {
+        if xid.get_format_id() != SimpleXid::FORMAT_ID
+            || xid.get_global_tid().len() != 8
+            || xid.get_branch_qualifier().len() != 16
+        {
+            return None;
+        }
+        let mut rdr = Cursor::new(xid.get_branch_qualifier());
+        Some(SimpleXid {
+            tm_id: rdr.read_u64::<LittleEndian>().ok()?,
+            rm_id: rdr.read_u64::<LittleEndian>().ok()?,
+            global_tid: Cursor::new(xid.get_global_tid())
+                .read_u64::<LittleEndian>()
+                .ok()?,
+        })
+    }
+
+    /// Encodes the parts into an `XaTransactionId`.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn encode(&self) -> XaTransactionId {
+        let mut bq = [0_u8; 16];
+        bq[..8].copy_from_slice(&self.tm_id.to_le_bytes());
+        bq[8..].copy_from_slice(&self.rm_id.to_le_bytes());
+        // the lengths are always valid
+        XaTransactionId::try_new(SimpleXid::FORMAT_ID, self.global_tid.to_le_bytes(), bq).unwrap()
+    }
+}
+impl std::fmt::Display for SimpleXid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "gtid {}, tm_id {}, rm_id {}",
+            self.global_tid, self.tm_id, self.rm_id
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SimpleXid;
+    use crate::XaTransactionId;
+
+    #[test]
+    fn test_round_trip() {
+        let parts = SimpleXid {
+            global_tid: 17,
+            tm_id: 0x1234_5678_9abc_de00,
+            rm_id: 2,
+        };
+        let xid = parts.encode();
+        assert_eq!(xid.get_format_id(), 99);
+        assert_eq!(SimpleXid::decode(&xid), Some(parts));
+
+        let foreign = XaTransactionId::try_new(99, vec![1; 8], vec![2; 15]).unwrap();
+        assert_eq!(SimpleXid::decode(&foreign), None);
+    }
+}
@@ -11,57 +11,57 @@ pub struct CRmWrapper<T>(pub T);
 
 impl<T: CResourceManager + std::fmt::Debug> ResourceManager for CRmWrapper<T> {
     fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("start() with {:?}", id);
+        trace!("start() with {id:?}");
         self.0.start(id, Flags::default())
     }
 
     fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("start_by_joining() with {:?}", id);
+        trace!("start_by_joining() with {id:?}");
         self.0.start(id, Flags::JOIN)
     }
 
     fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("start_by_resuming() with {:?}", id);
+        trace!("start_by_resuming() with {id:?}");
         self.0.start(id, Flags::RESUME)
     }
 
     fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("end_success() with {:?}", id);
+        trace!("end_success() with {id:?}");
         self.0.end(id, Flags::SUCCESS)
     }
 
     fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("end_failure() with {:?}", id);
+        trace!("end_failure() with {id:?}");
         self.0.end(id, Flags::FAIL)
     }
 
     fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("end_suspend() with {:?}", id);
+        trace!("end_suspend() with {id:?}");
         self.0.end(id, Flags::SUSPEND)
     }
 
     fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("prepare() with {:?}", id);
+        trace!("prepare() with {id:?}");
         self.0.prepare(id)
     }
 
     fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("commit() with {:?}", id);
+        trace!("commit() with {id:?}");
         self.0.commit(id, Flags::default())
     }
 
     fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("commit_one_phase() with {:?}", id);
+        trace!("commit_one_phase() with {id:?}");
         self.0.commit(id, Flags::ONE_PHASE)
     }
 
     fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("rollback() with {:?}", id);
+        trace!("rollback() with {id:?}");
         self.0.rollback(id)
     }
 
     fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
-        trace!("forget() with {:?}", id);
+        trace!("forget() with {id:?}");
         self.0.forget(id)
     }
 
@@ -0,0 +1,216 @@
+use super::{FaultInjector, ResourceManager};
+use crate::{
+    conformance::Scenario, ConformanceReport, ErrorCode, Fault, FaultPlan, ReturnCode, RmMethod,
+};
+
+type ScenarioFn<R> = fn(&mut Scenario, &mut dyn FnMut() -> R);
+
+/// Runs a battery of XA scenarios against fresh instances of a resource manager
+/// implementation, and reports which behaviors deviate from the XA specification.
+///
+/// The factory must return a new instance with each call,
+/// e.g. on a new connection, but backed by the same persistent storage,
+/// so that prepared branches survive a reconnect.
+/// Implementors of `CResourceManager` can check their implementation by wrapping it
+/// into `CRmWrapper`.
+///
+/// The scenarios create transaction branches with a distinct format id and leave
+/// no prepared branches behind, unless the implementation fails a scenario.
+///
+/// ```rust
+/// # use dist_tx::{FileStore, sync::rm::{check_conformance, CRmWrapper, FileResourceManager}};
+/// # let dir = std::env::temp_dir().join(format!("dist_tx_doc_conformance_{}", std::process::id()));
+/// let report = check_conformance(|| {
+///     CRmWrapper(FileResourceManager::new(&FileStore::open(&dir).unwrap()))
+/// });
+/// assert!(report.is_conformant(), "{report}");
+/// # std::fs::remove_dir_all(&dir).ok();
+/// ```
+pub fn check_conformance<R, F>(mut factory: F) -> ConformanceReport
+where
+    R: ResourceManager,
+    F: FnMut() -> R,
+{
+    let scenarios: [(&'static str, ScenarioFn<R>); 8] = [
+        ("one_phase_commit", one_phase_commit),
+        ("two_phase_commit", two_phase_commit),
+        ("rollback_after_prepare", rollback_after_prepare),
+        ("recover_after_reconnect", recover_after_reconnect),
+        ("forget", forget),
+        ("forget_heuristic", forget_heuristic),
+        ("duplicate_xid", duplicate_xid),
+        ("unknown_xid", unknown_xid),
+    ];
+    ConformanceReport {
+        scenarios: scenarios
+            .iter()
+            .map(|(name, run)| {
+                let mut scenario = Scenario::new(name);
+                run(&mut scenario, &mut factory);
+                scenario.finish()
+            })
+            .collect(),
+    }
+}
+
+fn one_phase_commit<R: ResourceManager>(s: &mut Scenario, factory: &mut dyn FnMut() -> R) {
+    let mut rm = factory();
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(&id))
+        && s.expect_ok("end_success", &rm.end_success(&id))
+        && s.expect_ok("commit_one_phase", &rm.commit_one_phase(&id))
+    {
+        s.expect_err(
+            "commit_one_phase of committed branch",
+            &rm.commit_one_phase(&id),
+            &[ErrorCode::InvalidTransactionId],
+        );
+    }
+}
+
+fn two_phase_commit<R: ResourceManager>(s: &mut Scenario, factory: &mut dyn FnMut() -> R) {
+    let mut rm = factory();
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(&id))
+        && s.expect_ok("end_success", &rm.end_success(&id))
+        && s.expect_prepared("prepare", &rm.prepare(&id))
+    {
+        s.expect_recovered("recover after prepare", &rm.recover(), &id, true);
+        if s.expect_ok("commit", &rm.commit(&id)) {
+            s.expect_recovered("recover after commit", &rm.recover(), &id, false);
+        }
+    }
+}
+
+fn rollback_after_prepare<R: ResourceManager>(s: &mut Scenario, factory: &mut dyn FnMut() -> R) {
+    let mut rm = factory();
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(&id))
+        && s.expect_ok("end_success", &rm.end_success(&id))
+        && s.expect_prepared("prepare", &rm.prepare(&id))
+        && s.expect_ok("rollback", &rm.rollback(&id))
+    {
+        s.expect_recovered("recover after rollback", &rm.recover(), &id, false);
+        s.expect_err(
+            "commit of rolled back branch",
+            &rm.commit(&id),
+            &[ErrorCode::InvalidTransactionId],
+        );
+    }
+}
+
+fn recover_after_reconnect<R: ResourceManager>(s: &mut Scenario, factory: &mut dyn FnMut() -> R) {
+    let id = s.xid(1);
+    {
+        let mut rm = factory();
+        if !(s.expect_ok("start", &rm.start(&id))
+            && s.expect_ok("end_success", &rm.end_success(&id))
+            && s.expect_prepared("prepare", &rm.prepare(&id)))
+        {
+            return;
+        }
+    }
+    let mut rm = factory();
+    let recovered = rm.recover();
+    s.expect_recovered("recover after reconnect", &recovered, &id, true);
+    if s.expect_ok("commit of recovered branch", &rm.commit(&id)) {
+        s.expect_recovered("recover after commit", &rm.recover(), &id, false);
+    }
+}
+
+fn forget<R: ResourceManager>(s: &mut Scenario, factory: &mut dyn FnMut() -> R) {
+    let mut rm = factory();
+    s.expect_err(
+        "forget of unknown branch",
+        &rm.forget(&s.xid(2)),
+        &[ErrorCode::InvalidTransactionId],
+    );
+
+    // only heuristically completed branches can be forgotten
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(&id))
+        && s.expect_ok("end_success", &rm.end_success(&id))
+        && s.expect_prepared("prepare", &rm.prepare(&id))
+    {
+        s.expect_err(
+            "forget of prepared branch",
+            &rm.forget(&id),
+            &[ErrorCode::InvalidTransactionId, ErrorCode::ProtocolError],
+        );
+        s.expect_ok("rollback after forget", &rm.rollback(&id));
+    }
+}
+
+// A heuristic decision cannot be provoked from outside, so the commit is answered with
+// XA_HEURCOM by a `FaultInjector`, which keeps the branch until it is forgotten.
+fn forget_heuristic<R: ResourceManager>(s: &mut Scenario, factory: &mut dyn FnMut() -> R) {
+    let plan = FaultPlan::new().on_call(
+        RmMethod::Commit,
+        1,
+        Fault::Return(ReturnCode::HeuristicallyCommitted),
+    );
+    let mut rm = FaultInjector::new(factory(), plan);
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(&id))
+        && s.expect_ok("end_success", &rm.end_success(&id))
+        && s.expect_prepared("prepare", &rm.prepare(&id))
+        && s.expect_return(
+            "commit",
+            &rm.commit(&id),
+            &ReturnCode::HeuristicallyCommitted,
+        )
+    {
+        s.expect_recovered("recover after heuristic commit", &rm.recover(), &id, true);
+        if s.expect_ok("forget", &rm.forget(&id)) {
+            s.expect_recovered("recover after forget", &rm.recover(), &id, false);
+        }
+    }
+}
+
+fn duplicate_xid<R: ResourceManager>(s: &mut Scenario, factory: &mut dyn FnMut() -> R) {
+    let mut rm = factory();
+    let id = s.xid(1);
+    if s.expect_ok("start", &rm.start(&id)) {
+        s.expect_err(
+            "start of active branch",
+            &rm.start(&id),
+            &[ErrorCode::DuplicateTransactionId],
+        );
+        // cleanup, the outcome is not part of the scenario
+        rm.end_failure(&id).ok();
+        rm.rollback(&id).ok();
+    }
+}
+
+fn unknown_xid<R: ResourceManager>(s: &mut Scenario, factory: &mut dyn FnMut() -> R) {
+    let mut rm = factory();
+    let id = s.xid(1);
+    for (step, result) in [
+        ("prepare of unknown branch", rm.prepare(&id)),
+        ("commit of unknown branch", rm.commit(&id)),
+        ("rollback of unknown branch", rm.rollback(&id)),
+    ] {
+        s.expect_err(step, &result, &[ErrorCode::InvalidTransactionId]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_conformance;
+    use crate::{sync::rm::MockResourceManager, CallLog};
+
+    #[test]
+    fn test_deviations_are_reported() {
+        let log = CallLog::new();
+        let report = check_conformance(|| MockResourceManager::new(&log));
+        assert!(!report.is_conformant());
+        let failed: Vec<&str> = report
+            .scenarios
+            .iter()
+            .filter(|scenario| !scenario.passed())
+            .map(|scenario| scenario.name)
+            .collect();
+        assert!(failed.contains(&"duplicate_xid"), "{report}");
+        assert!(failed.contains(&"recover_after_reconnect"), "{report}");
+    }
+}
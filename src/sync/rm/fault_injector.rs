@@ -0,0 +1,221 @@
+use super::ResourceManager;
+use crate::{
+    fault::injected_error, xa_outcome::typed, CompletionOutcome, Fault, FaultPlan, PrepareVote,
+    ReturnCode, RmError, RmMethod, XaTransactionId,
+};
+
+/// Wraps a `ResourceManager` and injects failures into its calls, as decided by a
+/// [`FaultPlan`].
+///
+/// Register the wrapped resource manager at a transaction manager to verify that your
+/// application handles all failures correctly:
+///
+/// ```rust
+/// # use dist_tx::{ErrorCode, Fault, FaultPlan, FileStore, RmMethod, sync::{
+/// #     rm::{CRmWrapper, FaultInjector, FileResourceManager},
+/// #     tm::{SimpleTransactionManager, TransactionManager}}};
+/// # let dir = std::env::temp_dir().join(format!("dist_tx_doc_fault_{}", std::process::id()));
+/// # let store = FileStore::open(&dir).unwrap();
+/// let plan = FaultPlan::new().on_call(RmMethod::CommitOnePhase, 1, Fault::Error(ErrorCode::RmFailure));
+/// let rm = FaultInjector::new(CRmWrapper(FileResourceManager::new(&store)), plan.clone());
+///
+/// let mut tm = SimpleTransactionManager::new("XA Demo");
+/// tm.register(Box::new(rm), 1, false).unwrap();
+/// tm.start_transaction().unwrap();
+/// assert!(tm.commit_transaction().is_err());
+/// assert_eq!(plan.injected().len(), 1);
+/// # std::fs::remove_dir_all(&dir).ok();
+/// ```
+#[derive(Debug)]
+pub struct FaultInjector<T: ResourceManager> {
+    inner: T,
+    plan: FaultPlan,
+    // branches with an injected heuristic outcome, and the call that completes them
+    heuristic: Vec<(XaTransactionId, RmMethod)>,
+}
+impl<T: ResourceManager> FaultInjector<T> {
+    /// Wraps the given resource manager.
+    pub fn new(inner: T, plan: FaultPlan) -> FaultInjector<T> {
+        FaultInjector {
+            inner,
+            plan,
+            heuristic: Vec::new(),
+        }
+    }
+
+    /// Returns the wrapped resource manager.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn call<F>(&mut self, method: RmMethod, f: F) -> Result<ReturnCode, RmError>
+    where
+        F: FnOnce(&mut T) -> Result<ReturnCode, RmError>,
+    {
+        match self.inject(method) {
+            Some(result) => result,
+            None => f(&mut self.inner),
+        }
+    }
+
+    // Like `call`, but an injected heuristic outcome keeps the branch until it is forgotten.
+    fn complete<F>(
+        &mut self,
+        method: RmMethod,
+        id: &XaTransactionId,
+        f: F,
+    ) -> Result<ReturnCode, RmError>
+    where
+        F: FnOnce(&mut T) -> Result<ReturnCode, RmError>,
+    {
+        match self.inject_completion(method, id) {
+            Some(result) => result,
+            None => f(&mut self.inner),
+        }
+    }
+
+    // Returns the result of the call if it is not to be forwarded.
+    fn inject(&mut self, method: RmMethod) -> Option<Result<ReturnCode, RmError>> {
+        match self.plan.next_fault(method) {
+            None => None,
+            Some(Fault::Error(code)) => Some(Err(injected_error(method, code))),
+            Some(Fault::Return(rc)) => Some(Ok(rc)),
+            Some(Fault::Delay(duration)) => {
+                std::thread::sleep(duration);
+                None
+            }
+        }
+    }
+
+    // Like `inject`, but an injected heuristic outcome keeps the branch until it is forgotten.
+    fn inject_completion(
+        &mut self,
+        method: RmMethod,
+        id: &XaTransactionId,
+    ) -> Option<Result<ReturnCode, RmError>> {
+        let result = self.inject(method);
+        if let Some(Ok(rc)) = &result {
+            if rc.is_heuristic() {
+                self.heuristic.push((*id, method));
+            }
+        }
+        result
+    }
+
+    fn call_recover<F>(&mut self, method: RmMethod, f: F) -> Result<Vec<XaTransactionId>, RmError>
+    where
+        F: FnOnce(&mut T) -> Result<Vec<XaTransactionId>, RmError>,
+    {
+        match self.plan.next_fault(method) {
+            Some(Fault::Error(code)) => Err(injected_error(method, code)),
+            Some(Fault::Delay(duration)) => {
+                std::thread::sleep(duration);
+                f(&mut self.inner)
+            }
+            None | Some(Fault::Return(_)) => f(&mut self.inner),
+        }
+    }
+}
+
+impl<T: ResourceManager> ResourceManager for FaultInjector<T> {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Start, |rm| rm.start(id))
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByJoining, |rm| rm.start_by_joining(id))
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByResuming, |rm| rm.start_by_resuming(id))
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuccess, |rm| rm.end_success(id))
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndFailure, |rm| rm.end_failure(id))
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuspend, |rm| rm.end_suspend(id))
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Prepare, |rm| rm.prepare(id))
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.complete(RmMethod::Commit, id, |rm| rm.commit(id))
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.complete(RmMethod::CommitOnePhase, id, |rm| rm.commit_one_phase(id))
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.complete(RmMethod::Rollback, id, |rm| rm.rollback(id))
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        let Some(i) = self.heuristic.iter().position(|(h, _)| h == id) else {
+            return self.call(RmMethod::Forget, |rm| rm.forget(id));
+        };
+        // the wrapped resource manager does not know about the heuristic outcome,
+        // so the branch is completed now
+        let method = self.heuristic[i].1;
+        let result = self.call(RmMethod::Forget, |rm| match method {
+            RmMethod::Commit => rm.commit(id),
+            RmMethod::CommitOnePhase => rm.commit_one_phase(id),
+            _ => rm.rollback(id),
+        });
+        if result.is_ok() {
+            self.heuristic.remove(i);
+        }
+        result
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::Recover, ResourceManager::recover)
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::BeginRecover, ResourceManager::begin_recover)
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::EndRecover, ResourceManager::end_recover)
+    }
+
+    fn prepare_vote(&mut self, id: &XaTransactionId) -> Result<PrepareVote, RmError> {
+        match self.inject(RmMethod::Prepare) {
+            Some(result) => typed(RmMethod::Prepare, id, result),
+            None => self.inner.prepare_vote(id),
+        }
+    }
+
+    fn commit_outcome(&mut self, id: &XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        match self.inject_completion(RmMethod::Commit, id) {
+            Some(result) => typed(RmMethod::Commit, id, result),
+            None => self.inner.commit_outcome(id),
+        }
+    }
+
+    fn commit_one_phase_outcome(
+        &mut self,
+        id: &XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        match self.inject_completion(RmMethod::CommitOnePhase, id) {
+            Some(result) => typed(RmMethod::CommitOnePhase, id, result),
+            None => self.inner.commit_one_phase_outcome(id),
+        }
+    }
+
+    fn rollback_outcome(&mut self, id: &XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        match self.inject_completion(RmMethod::Rollback, id) {
+            Some(result) => typed(RmMethod::Rollback, id, result),
+            None => self.inner.rollback_outcome(id),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use super::CResourceManager;
+use crate::{FileStore, Flags, ReturnCode, RmError, XaTransactionId};
+
+/// Implementation of `CResourceManager` for a [`FileStore`].
+///
+/// To register it at a transaction manager, wrap it into a [`CRmWrapper`](super::CRmWrapper):
+///
+/// ```rust
+/// # use dist_tx::{FileStore, sync::{rm::{CRmWrapper, FileResourceManager},
+/// #     tm::{SimpleTransactionManager, TransactionManager}}};
+/// # let dir = std::env::temp_dir().join(format!("dist_tx_doc_sync_{}", std::process::id()));
+/// let store = FileStore::open(&dir).unwrap();
+/// let mut tm = SimpleTransactionManager::new("XA Demo");
+/// tm.register(Box::new(CRmWrapper(FileResourceManager::new(&store))), 1, true)
+///     .unwrap();
+///
+/// tm.start_transaction().unwrap();
+/// store.put("key", "value").unwrap();
+/// tm.commit_transaction().unwrap();
+/// assert_eq!(store.get("key"), Some(b"value".to_vec()));
+/// # std::fs::remove_dir_all(&dir).ok();
+/// ```
+#[derive(Clone, Debug)]
+pub struct FileResourceManager(FileStore);
+impl FileResourceManager {
+    /// Creates a resource manager that works on the given store.
+    #[must_use]
+    pub fn new(store: &FileStore) -> FileResourceManager {
+        FileResourceManager(store.clone())
+    }
+}
+
+impl CResourceManager for FileResourceManager {
+    fn start(&mut self, id: &XaTransactionId, flag: Flags) -> Result<ReturnCode, RmError> {
+        self.0.xa_start(id, flag)
+    }
+
+    fn end(&mut self, id: &XaTransactionId, flag: Flags) -> Result<ReturnCode, RmError> {
+        self.0.xa_end(id, flag)
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.0.xa_prepare(id)
+    }
+
+    fn commit(&mut self, id: &XaTransactionId, flag: Flags) -> Result<ReturnCode, RmError> {
+        self.0.xa_commit(id, flag)
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.0.xa_rollback(id)
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.0.xa_forget(id)
+    }
+
+    fn recover(&mut self, flag: Flags) -> Result<Vec<XaTransactionId>, RmError> {
+        self.0.xa_recover(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileResourceManager;
+    use crate::{
+        file_store::tests::test_dir,
+        sync::{
+            rm::CRmWrapper,
+            tm::{SimpleTransactionManager, TransactionManager},
+        },
+        FileStore,
+    };
+
+    #[test]
+    fn test_two_phase_commit_and_rollback() {
+        let (dir_a, dir_b) = (test_dir("file_rm_a"), test_dir("file_rm_b"));
+        let store_a = FileStore::open(&dir_a).unwrap();
+        let store_b = FileStore::open(&dir_b).unwrap();
+
+        let mut tm = SimpleTransactionManager::new("test_two_phase_commit_and_rollback");
+        for (id, store) in [(1, &store_a), (2, &store_b)] {
+            tm.register(
+                Box::new(CRmWrapper(FileResourceManager::new(store))),
+                id,
+                true,
+            )
+            .unwrap();
+        }
+
+        tm.start_transaction().unwrap();
+        store_a.put("k", "a").unwrap();
+        store_b.put("k", "b").unwrap();
+        tm.commit_transaction().unwrap();
+
+        tm.start_transaction().unwrap();
+        store_a.delete("k").unwrap();
+        store_b.put("k", "bb").unwrap();
+        tm.rollback_transaction().unwrap();
+
+        assert_eq!(store_a.get("k"), Some(b"a".to_vec()));
+        assert_eq!(store_b.get("k"), Some(b"b".to_vec()));
+        std::fs::remove_dir_all(&dir_a).ok();
+        std::fs::remove_dir_all(&dir_b).ok();
+    }
+}
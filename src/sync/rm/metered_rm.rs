@@ -0,0 +1,184 @@
+use super::ResourceManager;
+use crate::{
+    metrics, CompletionOutcome, MetricsRecorder, PrepareVote, ReturnCode, RmError, RmMethod,
+    XaTransactionId,
+};
+use std::time::Instant;
+
+/// Wraps a `ResourceManager` and reports the latency and the outcome of each XA call
+/// to a [`MetricsRecorder`](crate::MetricsRecorder).
+#[derive(Debug)]
+pub struct MeteredRm<T: ResourceManager> {
+    inner: T,
+    rm_id: u64,
+    recorder: Box<dyn MetricsRecorder>,
+}
+impl<T: ResourceManager> MeteredRm<T> {
+    /// Wraps the given resource manager; the metrics are reported with the given `rm_id`.
+    pub fn new<M: MetricsRecorder + 'static>(inner: T, rm_id: u64, recorder: M) -> MeteredRm<T> {
+        MeteredRm {
+            inner,
+            rm_id,
+            recorder: Box::new(recorder),
+        }
+    }
+
+    /// Returns the wrapped resource manager.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn call<R, F>(&mut self, method: RmMethod, f: F) -> Result<R, RmError>
+    where
+        R: Clone + Into<ReturnCode>,
+        F: FnOnce(&mut T) -> Result<R, RmError>,
+    {
+        let start = Instant::now();
+        let result = f(&mut self.inner);
+        metrics::record_result(&*self.recorder, self.rm_id, method, start, &result);
+        result
+    }
+
+    fn call_recover<F>(&mut self, method: RmMethod, f: F) -> Result<Vec<XaTransactionId>, RmError>
+    where
+        F: FnOnce(&mut T) -> Result<Vec<XaTransactionId>, RmError>,
+    {
+        let start = Instant::now();
+        let result = f(&mut self.inner);
+        metrics::record_recover(&*self.recorder, self.rm_id, method, start, &result);
+        result
+    }
+}
+
+impl<T: ResourceManager> ResourceManager for MeteredRm<T> {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Start, |rm| rm.start(id))
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByJoining, |rm| rm.start_by_joining(id))
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByResuming, |rm| rm.start_by_resuming(id))
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuccess, |rm| rm.end_success(id))
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndFailure, |rm| rm.end_failure(id))
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuspend, |rm| rm.end_suspend(id))
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Prepare, |rm| rm.prepare(id))
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Commit, |rm| rm.commit(id))
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::CommitOnePhase, |rm| rm.commit_one_phase(id))
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Rollback, |rm| rm.rollback(id))
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Forget, |rm| rm.forget(id))
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::Recover, ResourceManager::recover)
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::BeginRecover, ResourceManager::begin_recover)
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::EndRecover, ResourceManager::end_recover)
+    }
+
+    fn prepare_vote(&mut self, id: &XaTransactionId) -> Result<PrepareVote, RmError> {
+        self.call(RmMethod::Prepare, |rm| rm.prepare_vote(id))
+    }
+
+    fn commit_outcome(&mut self, id: &XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        self.call(RmMethod::Commit, |rm| rm.commit_outcome(id))
+    }
+
+    fn commit_one_phase_outcome(
+        &mut self,
+        id: &XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        self.call(RmMethod::CommitOnePhase, |rm| {
+            rm.commit_one_phase_outcome(id)
+        })
+    }
+
+    fn rollback_outcome(&mut self, id: &XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        self.call(RmMethod::Rollback, |rm| rm.rollback_outcome(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MeteredRm;
+    use crate::{
+        sync::{
+            rm::{MockResourceManager, ResourceManager},
+            tm::{SimpleTransactionManager, TransactionManager},
+        },
+        CallLog, ErrorCode, InMemoryRecorder, ReturnCode, RmError, RmMethod, XaTransactionId,
+    };
+
+    #[test]
+    fn test_metrics() {
+        let log = CallLog::new();
+        let recorder = InMemoryRecorder::new();
+        let mut tm = SimpleTransactionManager::new("test_metrics").with_metrics(recorder.clone());
+        for rm_id in 1..=2 {
+            let mut rm = MockResourceManager::new(&log);
+            if rm_id == 2 {
+                rm = rm
+                    .returning(RmMethod::Prepare, Ok(ReturnCode::Ok))
+                    .returning(
+                        RmMethod::Prepare,
+                        Err(RmError::new(ErrorCode::RmFailure, "down".to_string())),
+                    );
+            }
+            let rm = MeteredRm::new(rm, rm_id, recorder.clone());
+            tm.register(Box::new(rm), rm_id, false).unwrap();
+        }
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap_err();
+
+        let metrics = recorder.snapshot();
+        assert_eq!(metrics.transactions_started, 2);
+        assert_eq!(metrics.transactions_committed, 1);
+        assert_eq!(metrics.transactions_rolled_back, 1);
+        assert_eq!(metrics.in_doubt, 0);
+        assert_eq!(metrics.errors[&ErrorCode::RmFailure], 1);
+        assert_eq!(metrics.latencies[&(1, RmMethod::Prepare)].count(), 2);
+        assert_eq!(metrics.latencies[&(2, RmMethod::Rollback)].count(), 1);
+        let buckets = metrics.latencies[&(2, RmMethod::Start)].buckets();
+        assert_eq!(buckets.iter().map(|(_, n)| n).sum::<u64>(), 2);
+
+        let mock = MockResourceManager::new(&log)
+            .returning(RmMethod::Rollback, Ok(ReturnCode::HeuristicallyRolledBack));
+        let mut rm = MeteredRm::new(mock, 3, recorder.clone());
+        let id = XaTransactionId::try_new(1, vec![1], vec![3]).unwrap();
+        rm.rollback(&id).unwrap();
+        assert_eq!(recorder.snapshot().heuristic_outcomes, 1);
+    }
+}
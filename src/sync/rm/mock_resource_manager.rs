@@ -0,0 +1,137 @@
+use super::ResourceManager;
+use crate::{CallLog, ReturnCode, RmError, RmMethod, XaTransactionId};
+use std::collections::{HashMap, VecDeque};
+
+/// A `ResourceManager` for tests, which records all calls in a [`CallLog`]
+/// and returns scripted results.
+///
+/// Calls for which no result is scripted return `Ok(ReturnCode::Ok)`,
+/// the recover methods return the transaction ids given with `recovering()`.
+///
+/// ```rust
+/// use dist_tx::{CallLog, RmMethod, sync::{rm::MockResourceManager,
+///     tm::{SimpleTransactionManager, TransactionManager}}};
+///
+/// let log = CallLog::new();
+/// let mut tm = SimpleTransactionManager::new("XA Demo");
+/// tm.register(Box::new(MockResourceManager::new(&log)), 1, false).unwrap();
+/// tm.register(Box::new(MockResourceManager::new(&log)), 2, false).unwrap();
+///
+/// tm.start_transaction().unwrap();
+/// tm.commit_transaction().unwrap();
+///
+/// log.assert_called_before(RmMethod::Prepare, RmMethod::Commit);
+/// log.assert_not_called(RmMethod::CommitOnePhase);
+/// ```
+#[derive(Debug)]
+pub struct MockResourceManager {
+    log: CallLog,
+    results: HashMap<RmMethod, VecDeque<Result<ReturnCode, RmError>>>,
+    recovered: Vec<XaTransactionId>,
+}
+impl MockResourceManager {
+    /// Creates a mock that records its calls into the given log.
+    #[must_use]
+    pub fn new(log: &CallLog) -> MockResourceManager {
+        MockResourceManager {
+            log: log.clone(),
+            results: HashMap::new(),
+            recovered: Vec::new(),
+        }
+    }
+
+    /// Scripts the result of the next call of the given method for which no result is
+    /// scripted yet.
+    ///
+    /// For the recover methods, only errors are used; `Ok` values are ignored.
+    #[must_use]
+    pub fn returning(
+        mut self,
+        method: RmMethod,
+        result: Result<ReturnCode, RmError>,
+    ) -> MockResourceManager {
+        self.results.entry(method).or_default().push_back(result);
+        self
+    }
+
+    /// Sets the transaction ids that are returned by the recover methods.
+    #[must_use]
+    pub fn recovering(mut self, xids: Vec<XaTransactionId>) -> MockResourceManager {
+        self.recovered = xids;
+        self
+    }
+
+    fn call(&mut self, method: RmMethod, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.log.record(method, Some(id));
+        self.results
+            .get_mut(&method)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or(Ok(ReturnCode::Ok))
+    }
+
+    fn call_recover(&mut self, method: RmMethod) -> Result<Vec<XaTransactionId>, RmError> {
+        self.log.record(method, None);
+        match self.results.get_mut(&method).and_then(VecDeque::pop_front) {
+            Some(Err(e)) => Err(e),
+            Some(Ok(_)) | None => Ok(self.recovered.clone()),
+        }
+    }
+}
+
+impl ResourceManager for MockResourceManager {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Start, id)
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByJoining, id)
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByResuming, id)
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuccess, id)
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndFailure, id)
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuspend, id)
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Prepare, id)
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Commit, id)
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::CommitOnePhase, id)
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Rollback, id)
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Forget, id)
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::Recover)
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::BeginRecover)
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::EndRecover)
+    }
+}
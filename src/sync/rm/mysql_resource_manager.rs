@@ -0,0 +1,228 @@
+use super::ResourceManager;
+use crate::{
+    mysql::{map_error, parse_recover_row, rm_error, xid_literal, RECOVER_STATEMENT},
+    ErrorCode, MySqlError, ReturnCode, RmError, XaTransactionId,
+};
+use log::trace;
+
+/// Executes SQL statements on a `MySQL` or `MariaDB` session,
+/// on behalf of a [`MySqlResourceManager`].
+///
+/// Implement this trait for the connection type of your `MySQL` driver.
+pub trait MySqlExecutor: std::fmt::Debug {
+    /// Executes a statement that does not return rows.
+    ///
+    /// # Errors
+    ///
+    /// `MySqlError` if the statement fails.
+    fn execute(&mut self, sql: &str) -> Result<(), MySqlError>;
+
+    /// Executes a query and returns all rows, with all values as text.
+    ///
+    /// # Errors
+    ///
+    /// `MySqlError` if the query fails.
+    fn query(&mut self, sql: &str) -> Result<Vec<Vec<String>>, MySqlError>;
+}
+
+/// Implementation of `ResourceManager` for `MySQL` and `MariaDB`, using their
+/// [XA statements](https://dev.mysql.com/doc/refman/8.0/en/xa-statements.html).
+///
+/// All SQL is executed with a user-supplied [`MySqlExecutor`].
+/// Every method is mapped to the respective XA statement, where the `XaTransactionId` is
+/// given as `X'gtrid',X'bqual',formatID`.
+///
+/// The server's XA errors are mapped onto `ErrorCode`, and its rollback errors
+/// (like `XA_RBDEADLOCK`) onto the respective `ReturnCode`.
+///
+/// `MySQL` knows neither `XA END ... FAIL` nor `XA FORGET`:
+/// a branch that was ended with `end_failure()` is rolled back when it is prepared or
+/// committed, and `forget()` always fails.
+#[derive(Debug)]
+pub struct MySqlResourceManager<E: MySqlExecutor> {
+    executor: E,
+    rollback_only: Option<XaTransactionId>,
+}
+
+impl<E: MySqlExecutor> MySqlResourceManager<E> {
+    /// Creates an instance that uses the given executor.
+    pub fn new(executor: E) -> MySqlResourceManager<E> {
+        MySqlResourceManager {
+            executor,
+            rollback_only: None,
+        }
+    }
+
+    /// Returns a reference to the executor.
+    pub fn executor(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    fn xa(
+        &mut self,
+        statement: &str,
+        id: &XaTransactionId,
+        suffix: &str,
+    ) -> Result<ReturnCode, RmError> {
+        let sql = format!("XA {statement} {}{suffix}", xid_literal(id));
+        trace!("MySqlResourceManager: {sql}");
+        match self.executor.execute(&sql) {
+            Ok(()) => Ok(ReturnCode::Ok),
+            Err(e) => map_error(e),
+        }
+    }
+
+    // Rolls the branch back if it was ended with `end_failure()`.
+    fn rollback_if_failed(&mut self, id: &XaTransactionId) -> Option<Result<ReturnCode, RmError>> {
+        match self.rollback_only.take() {
+            Some(xid) if xid == *id => Some(
+                self.xa("ROLLBACK", id, "")
+                    .map(|_| ReturnCode::RollbackUnspecified),
+            ),
+            other => {
+                self.rollback_only = other;
+                None
+            }
+        }
+    }
+
+    fn recover_all(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        trace!("MySqlResourceManager: {RECOVER_STATEMENT}");
+        match self.executor.query(RECOVER_STATEMENT) {
+            Ok(rows) => rows.iter().map(|row| parse_recover_row(row)).collect(),
+            Err(e) => Err(rm_error(e)),
+        }
+    }
+}
+
+impl<E: MySqlExecutor> ResourceManager for MySqlResourceManager<E> {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("START", id, "")
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("START", id, " JOIN")
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("START", id, " RESUME")
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("END", id, "")
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        let result = self.xa("END", id, "");
+        self.rollback_only = Some(*id);
+        result
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.xa("END", id, " SUSPEND")
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.rollback_if_failed(id)
+            .unwrap_or_else(|| self.xa("PREPARE", id, ""))
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.rollback_if_failed(id)
+            .unwrap_or_else(|| self.xa("COMMIT", id, ""))
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.rollback_if_failed(id)
+            .unwrap_or_else(|| self.xa("COMMIT", id, " ONE PHASE"))
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.rollback_if_failed(id);
+        self.xa("ROLLBACK", id, "")
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        Err(RmError::new(
+            ErrorCode::InvalidTransactionId,
+            format!("transaction branch {id:?} was not heuristically completed"),
+        ))
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all()
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all()
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        // the complete list is always returned with the start of the scan
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MySqlExecutor, MySqlResourceManager};
+    use crate::{sync::rm::ResourceManager, MySqlError, ReturnCode, XaTransactionId};
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        statements: Vec<String>,
+        fail_with: Option<u16>,
+    }
+    impl MySqlExecutor for Recorder {
+        fn execute(&mut self, sql: &str) -> Result<(), MySqlError> {
+            self.statements.push(sql.to_string());
+            match self.fail_with.take() {
+                Some(number) => Err(MySqlError::new(number, "injected")),
+                None => Ok(()),
+            }
+        }
+        fn query(&mut self, sql: &str) -> Result<Vec<Vec<String>>, MySqlError> {
+            self.statements.push(sql.to_string());
+            if let Some(number) = self.fail_with.take() {
+                return Err(MySqlError::new(number, "injected"));
+            }
+            Ok(vec![vec![
+                "1".to_string(),
+                "1".to_string(),
+                "0".to_string(),
+                "0x2A".to_string(),
+            ]])
+        }
+    }
+
+    #[test]
+    fn test_statements() {
+        let xid = XaTransactionId::try_new(1, vec![42], vec![]).unwrap();
+        let mut rm = MySqlResourceManager::new(Recorder::default());
+        rm.start(&xid).unwrap();
+        rm.end_failure(&xid).unwrap();
+        assert!(matches!(
+            rm.commit_one_phase(&xid),
+            Ok(ReturnCode::RollbackUnspecified)
+        ));
+
+        rm.executor().fail_with = Some(1614);
+        assert!(matches!(rm.prepare(&xid), Ok(ReturnCode::RollbackDeadlock)));
+        assert_eq!(
+            rm.executor().statements,
+            vec![
+                "XA START X'2A',X'',1",
+                "XA END X'2A',X'',1",
+                "XA ROLLBACK X'2A',X'',1",
+                "XA PREPARE X'2A',X'',1",
+            ]
+        );
+
+        let recovered = rm.recover().unwrap();
+        assert_eq!(recovered[0].as_bytes(false), xid.as_bytes(false));
+
+        // a failed scan is never reported as an empty list
+        rm.executor().fail_with = Some(1614);
+        assert!(rm.recover().is_err());
+    }
+}
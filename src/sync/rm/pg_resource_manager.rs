@@ -0,0 +1,292 @@
+use super::ResourceManager;
+use crate::{ErrorCode, ReturnCode, RmError, XaTransactionId};
+use log::trace;
+
+/// Executes SQL statements on a `PostgreSQL` session, on behalf of a [`PgResourceManager`].
+///
+/// Implement this trait for the connection type of your `PostgreSQL` driver.
+/// The connection must not be in automatic commit mode
+/// while a transaction branch is active.
+pub trait PgExecutor: std::fmt::Debug {
+    /// Executes a statement that does not return rows.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if the statement fails.
+    fn execute(&mut self, sql: &str) -> Result<(), RmError>;
+
+    /// Executes a query and returns the values of its first column, as text.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if the query fails.
+    fn query_column(&mut self, sql: &str) -> Result<Vec<String>, RmError>;
+}
+
+/// Implementation of `ResourceManager` for `PostgreSQL`, using its
+/// [two-phase commit statements](https://www.postgresql.org/docs/current/sql-prepare-transaction.html).
+///
+/// All SQL is executed with a user-supplied [`PgExecutor`], so that any `PostgreSQL` driver
+/// can be enlisted without knowing about `dist_tx`.
+///
+/// * `start()` executes `BEGIN`
+/// * `prepare()` executes `PREPARE TRANSACTION 'gid'`,
+///   where the gid is produced with [`XaTransactionId::to_gid`]
+/// * `commit()` and `rollback()` execute `COMMIT PREPARED 'gid'` and `ROLLBACK PREPARED 'gid'`
+///   for prepared branches, `commit_one_phase()` and `rollback()` of a branch that is not
+///   yet prepared execute `COMMIT` and `ROLLBACK`
+/// * `recover()` reads the view `pg_prepared_xacts` of the current database,
+///   and ignores all gids that were not produced with [`XaTransactionId::to_gid`]
+///
+/// `PostgreSQL` does not complete transactions heuristically, so `forget()` always fails.
+#[derive(Debug)]
+pub struct PgResourceManager<E: PgExecutor> {
+    executor: E,
+    branch: Option<(XaTransactionId, BranchState)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BranchState {
+    Active,
+    Suspended,
+    Idle,
+    RollbackOnly,
+}
+
+const RECOVER_QUERY: &str =
+    "SELECT gid FROM pg_prepared_xacts WHERE database = current_database() ORDER BY prepared";
+
+impl<E: PgExecutor> PgResourceManager<E> {
+    /// Creates an instance that uses the given executor.
+    pub fn new(executor: E) -> PgResourceManager<E> {
+        PgResourceManager {
+            executor,
+            branch: None,
+        }
+    }
+
+    /// Returns a reference to the executor.
+    pub fn executor(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    fn execute(&mut self, sql: &str) -> Result<ReturnCode, RmError> {
+        trace!("PgResourceManager: {sql}");
+        self.executor.execute(sql).map(|()| ReturnCode::Ok)
+    }
+
+    fn current_state(&self, id: &XaTransactionId) -> Option<BranchState> {
+        match self.branch {
+            Some((ref xid, state)) if xid == id => Some(state),
+            _ => None,
+        }
+    }
+
+    // Changes the state of the current branch, if it is in the required state.
+    fn transition(
+        &mut self,
+        id: &XaTransactionId,
+        required: BranchState,
+        new: BranchState,
+    ) -> Result<ReturnCode, RmError> {
+        match self.current_state(id) {
+            Some(state) if state == required => {
+                self.branch = Some((*id, new));
+                Ok(ReturnCode::Ok)
+            }
+            Some(_) => Err(protocol_error(id, "is in the wrong state")),
+            None => Err(protocol_error(id, "is not associated with this session")),
+        }
+    }
+
+    fn recover_all(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        trace!("PgResourceManager: {RECOVER_QUERY}");
+        Ok(self
+            .executor
+            .query_column(RECOVER_QUERY)?
+            .iter()
+            .filter_map(|gid| XaTransactionId::from_gid(gid).ok())
+            .collect())
+    }
+}
+
+impl<E: PgExecutor> ResourceManager for PgResourceManager<E> {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        if self.branch.is_some() {
+            return Err(protocol_error(
+                id,
+                "cannot start, a branch is already in use",
+            ));
+        }
+        self.execute("BEGIN")?;
+        self.branch = Some((*id, BranchState::Active));
+        Ok(ReturnCode::Ok)
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Idle, BranchState::Active)
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Suspended, BranchState::Active)
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Active, BranchState::Idle)
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Active, BranchState::RollbackOnly)
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Active, BranchState::Suspended)
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.current_state(id) {
+            Some(BranchState::Idle) => {
+                // the session is disassociated from the transaction in any case
+                self.branch = None;
+                self.execute(&format!("PREPARE TRANSACTION '{}'", id.to_gid()))
+            }
+            Some(BranchState::RollbackOnly) => {
+                self.branch = None;
+                self.execute("ROLLBACK")?;
+                Ok(ReturnCode::RollbackUnspecified)
+            }
+            Some(_) => Err(protocol_error(id, "is not ended")),
+            None => Err(protocol_error(id, "is not associated with this session")),
+        }
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        if self.current_state(id).is_some() {
+            return Err(protocol_error(id, "is not prepared"));
+        }
+        self.execute(&format!("COMMIT PREPARED '{}'", id.to_gid()))
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.current_state(id) {
+            Some(BranchState::Idle) => {
+                self.branch = None;
+                self.execute("COMMIT")
+            }
+            Some(BranchState::RollbackOnly) => {
+                self.branch = None;
+                self.execute("ROLLBACK")?;
+                Ok(ReturnCode::RollbackUnspecified)
+            }
+            Some(_) => Err(protocol_error(id, "is not ended")),
+            None => Err(protocol_error(id, "is not associated with this session")),
+        }
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        if self.current_state(id).is_some() {
+            self.branch = None;
+            self.execute("ROLLBACK")
+        } else {
+            self.execute(&format!("ROLLBACK PREPARED '{}'", id.to_gid()))
+        }
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        Err(RmError::new(
+            ErrorCode::InvalidTransactionId,
+            format!("transaction branch {id:?} was not heuristically completed"),
+        )
+        .with_xid(*id))
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all()
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all()
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        // the complete list is always returned with the start of the scan
+        Ok(Vec::new())
+    }
+}
+
+fn protocol_error(id: &XaTransactionId, s: &str) -> RmError {
+    RmError::new(
+        ErrorCode::ProtocolError,
+        format!("transaction branch {id:?} {s}"),
+    )
+    .with_xid(*id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PgExecutor, PgResourceManager};
+    use crate::{
+        sync::{
+            rm::ResourceManager,
+            tm::{SimpleTransactionManager, TransactionManager},
+        },
+        RmError, XaTransactionId,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        statements: Arc<Mutex<Vec<String>>>,
+        prepared: Vec<String>,
+    }
+    impl PgExecutor for Recorder {
+        fn execute(&mut self, sql: &str) -> Result<(), RmError> {
+            self.statements.lock().unwrap().push(sql.to_string());
+            Ok(())
+        }
+        fn query_column(&mut self, sql: &str) -> Result<Vec<String>, RmError> {
+            self.statements.lock().unwrap().push(sql.to_string());
+            Ok(self.prepared.clone())
+        }
+    }
+
+    #[test]
+    fn test_statements_of_two_phase_commit() {
+        let statements = Arc::new(Mutex::new(Vec::new()));
+        let mut tm = SimpleTransactionManager::new("test_statements_of_two_phase_commit");
+        for rm_id in [1, 2] {
+            let executor = Recorder {
+                statements: Arc::clone(&statements),
+                prepared: Vec::new(),
+            };
+            tm.register(Box::new(PgResourceManager::new(executor)), rm_id, false)
+                .unwrap();
+        }
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+
+        let statements = statements.lock().unwrap();
+        assert_eq!(statements.len(), 6);
+        assert_eq!(statements[0], "BEGIN");
+        assert!(statements[2].starts_with("PREPARE TRANSACTION '99."));
+        assert!(statements[4].starts_with("COMMIT PREPARED '99."));
+    }
+
+    #[test]
+    fn test_recover() {
+        let xid = XaTransactionId::try_new(7, vec![1, 2, 3], vec![4]).unwrap();
+        let mut rm = PgResourceManager::new(Recorder {
+            statements: Arc::default(),
+            prepared: vec!["foreign".to_string(), xid.to_gid()],
+        });
+        let recovered = rm.recover().unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].as_bytes(false), xid.as_bytes(false));
+
+        rm.rollback(&xid).unwrap();
+        assert_eq!(
+            rm.executor().statements.lock().unwrap().last().unwrap(),
+            &format!("ROLLBACK PREPARED '{}'", xid.to_gid())
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{ReturnCode, RmError, XaTransactionId};
+use crate::{
+    xa_outcome::typed, CompletionOutcome, PrepareVote, ReturnCode, RmError, RmMethod,
+    XaTransactionId,
+};
 
 /// Interface of a resource manager, as required by a transaction manager.
 ///
@@ -104,4 +107,56 @@ pub trait ResourceManager: std::fmt::Debug {
     ///
     /// `RmError` if the request cannot be handled regularily.
     fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError>;
+
+    /// Prepares the given transaction branch like `prepare()`, and returns the vote.
+    ///
+    /// The default implementation converts the return code of `prepare()`.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if the request cannot be handled regularily,
+    /// or if `prepare()` does not return a valid vote.
+    fn prepare_vote(&mut self, id: &XaTransactionId) -> Result<PrepareVote, RmError> {
+        typed(RmMethod::Prepare, id, self.prepare(id))
+    }
+
+    /// Commits the given prepared transaction branch like `commit()`, and returns the outcome.
+    ///
+    /// The default implementation converts the return code of `commit()`.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if the request cannot be handled regularily,
+    /// or if `commit()` does not return a valid outcome.
+    fn commit_outcome(&mut self, id: &XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        typed(RmMethod::Commit, id, self.commit(id))
+    }
+
+    /// Commits the given not-prepared transaction branch like `commit_one_phase()`,
+    /// and returns the outcome.
+    ///
+    /// The default implementation converts the return code of `commit_one_phase()`.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if the request cannot be handled regularily,
+    /// or if `commit_one_phase()` does not return a valid outcome.
+    fn commit_one_phase_outcome(
+        &mut self,
+        id: &XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        typed(RmMethod::CommitOnePhase, id, self.commit_one_phase(id))
+    }
+
+    /// Rolls back the given transaction branch like `rollback()`, and returns the outcome.
+    ///
+    /// The default implementation converts the return code of `rollback()`.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if the request cannot be handled regularily,
+    /// or if `rollback()` does not return a valid outcome.
+    fn rollback_outcome(&mut self, id: &XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        typed(RmMethod::Rollback, id, self.rollback(id))
+    }
 }
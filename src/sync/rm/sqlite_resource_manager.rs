@@ -0,0 +1,507 @@
+use super::ResourceManager;
+use crate::{ErrorCode, ReturnCode, RmError, XaTransactionId};
+use log::trace;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+// In the side database: one row per prepared or heuristically completed branch.
+const CREATE_PREPARED_TABLE: &str = "CREATE TABLE IF NOT EXISTS dist_tx_prepared (\
+    xid BLOB PRIMARY KEY, state TEXT NOT NULL)";
+
+// In the data database: a marker per prepared branch that is written within the branch's
+// own transaction, so it exists if and only if the branch's changes were committed.
+const CREATE_MARKER_TABLE: &str = "CREATE TABLE IF NOT EXISTS dist_tx_marker (\
+    xid BLOB PRIMARY KEY)";
+
+const PREPARED: &str = "P";
+const HEURISTICALLY_COMMITTED: &str = "C";
+const HEURISTICALLY_ROLLED_BACK: &str = "R";
+
+/// Implementation of `ResourceManager` for a [`SQLite`](https://www.sqlite.org) database.
+///
+/// `SQLite` has no native support for XA, so this resource manager emulates it:
+///
+/// * `start()` opens a write transaction (`BEGIN IMMEDIATE`) on the data connection,
+///   which the application then uses for its changes
+/// * `prepare()` writes a marker for the branch into the open transaction of the data
+///   database, and, durably, a record into the table `dist_tx_prepared` of a separate
+///   side database; the write transaction stays open
+/// * `commit()` commits the write transaction and removes the record again
+/// * `recover()` reads the side table
+///
+/// A side database is needed because `SQLite` allows only one writer per database file,
+/// and this writer is busy with the open transaction.
+///
+/// `SQLite` cannot keep a transaction across a restart of the process. A branch that was
+/// prepared, but whose connection was lost before it was completed, is still reported by
+/// `recover()`; the marker in the data database then tells whether its changes survived,
+/// so that a subsequent `commit()` or `rollback()` either succeeds or reports the
+/// respective heuristic outcome (which needs to be cleared with `forget()`).
+///
+/// Only one transaction branch can be associated with a connection at a time.
+#[derive(Debug)]
+pub struct SqliteResourceManager {
+    conn: Arc<Mutex<Connection>>,
+    side: Connection,
+    branch: Option<(XaTransactionId, BranchState)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BranchState {
+    Active,
+    Suspended,
+    Idle,
+    RollbackOnly,
+    Prepared,
+}
+
+impl SqliteResourceManager {
+    /// Opens the database file at the given path, and the side database next to it
+    /// (with the suffix `-xa`).
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if one of the databases cannot be opened or initialized.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteResourceManager, RmError> {
+        let path = path.as_ref();
+        let mut side_path = path.as_os_str().to_owned();
+        side_path.push("-xa");
+        SqliteResourceManager::new(
+            Arc::new(Mutex::new(Connection::open(path).map_err(db_error)?)),
+            Connection::open(side_path).map_err(db_error)?,
+        )
+    }
+
+    /// Creates an instance from a connection to the data database and
+    /// a connection to a separate side database.
+    ///
+    /// The side database must be exclusively used by this resource manager.
+    ///
+    /// # Errors
+    ///
+    /// `RmError` if one of the databases cannot be initialized.
+    pub fn new(
+        conn: Arc<Mutex<Connection>>,
+        side: Connection,
+    ) -> Result<SqliteResourceManager, RmError> {
+        side.pragma_update(None, "synchronous", "FULL")
+            .map_err(db_error)?;
+        side.execute(CREATE_PREPARED_TABLE, []).map_err(db_error)?;
+        {
+            let conn = lock(&conn);
+            conn.execute(CREATE_MARKER_TABLE, []).map_err(db_error)?;
+            // markers of branches that are completed and forgotten are obsolete
+            let mut stmt = conn
+                .prepare("SELECT xid FROM dist_tx_marker")
+                .map_err(db_error)?;
+            let markers = stmt
+                .query_map([], |row| row.get::<_, Vec<u8>>(0))
+                .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+                .map_err(db_error)?;
+            for xid in markers {
+                if side_state(&side, &xid)?.is_none() {
+                    conn.execute("DELETE FROM dist_tx_marker WHERE xid = ?1", [&xid])
+                        .map_err(db_error)?;
+                }
+            }
+        }
+        Ok(SqliteResourceManager {
+            conn,
+            side,
+            branch: None,
+        })
+    }
+
+    /// Returns the connection to the data database, which the application uses for
+    /// its changes.
+    #[must_use]
+    pub fn connection(&self) -> Arc<Mutex<Connection>> {
+        Arc::clone(&self.conn)
+    }
+
+    fn execute(&self, sql: &str) -> Result<(), RmError> {
+        trace!("SqliteResourceManager: {sql}");
+        lock(&self.conn).execute_batch(sql).map_err(db_error)
+    }
+
+    fn is_current(&self, id: &XaTransactionId) -> bool {
+        self.branch.as_ref().is_some_and(|(xid, _)| xid == id)
+    }
+
+    // Changes the state of the current branch, if it is in the required state.
+    fn transition(
+        &mut self,
+        id: &XaTransactionId,
+        required: BranchState,
+        new: BranchState,
+    ) -> Result<ReturnCode, RmError> {
+        if !self.is_current(id) {
+            return Err(unknown(id));
+        }
+        match self.branch {
+            Some((_, ref mut state)) if *state == required => {
+                *state = new;
+                Ok(ReturnCode::Ok)
+            }
+            _ => Err(protocol_error(id, "is in the wrong state")),
+        }
+    }
+
+    fn current_state(&self, id: &XaTransactionId) -> Option<BranchState> {
+        if self.is_current(id) {
+            self.branch.as_ref().map(|(_, state)| *state)
+        } else {
+            None
+        }
+    }
+
+    fn set_side_state(&self, id: &XaTransactionId, state: &str) -> Result<(), RmError> {
+        self.side
+            .execute(
+                "UPDATE dist_tx_prepared SET state = ?2 WHERE xid = ?1",
+                params![id.as_bytes(false), state],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn remove_branch_records(&self, id: &XaTransactionId) -> Result<(), RmError> {
+        let xid = id.as_bytes(false);
+        self.side
+            .execute("DELETE FROM dist_tx_prepared WHERE xid = ?1", [&xid])
+            .map_err(db_error)?;
+        lock(&self.conn)
+            .execute("DELETE FROM dist_tx_marker WHERE xid = ?1", [&xid])
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn has_marker(&self, id: &XaTransactionId) -> Result<bool, RmError> {
+        lock(&self.conn)
+            .query_row(
+                "SELECT 1 FROM dist_tx_marker WHERE xid = ?1",
+                [id.as_bytes(false)],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|o| o.is_some())
+            .map_err(db_error)
+    }
+
+    // Completes a branch that was prepared in an earlier life of the data connection.
+    fn complete_recovered(
+        &mut self,
+        id: &XaTransactionId,
+        commit: bool,
+    ) -> Result<ReturnCode, RmError> {
+        match side_state(&self.side, &id.as_bytes(false))?.as_deref() {
+            Some(PREPARED) => {}
+            Some(_) => return Err(protocol_error(id, "was heuristically completed")),
+            None => return Err(unknown(id)),
+        }
+        let committed = self.has_marker(id)?;
+        if committed == commit {
+            self.remove_branch_records(id)?;
+            Ok(ReturnCode::Ok)
+        } else if committed {
+            self.set_side_state(id, HEURISTICALLY_COMMITTED)?;
+            Ok(ReturnCode::HeuristicallyCommitted)
+        } else {
+            self.set_side_state(id, HEURISTICALLY_ROLLED_BACK)?;
+            Ok(ReturnCode::HeuristicallyRolledBack)
+        }
+    }
+
+    fn recover_all(&self) -> Result<Vec<XaTransactionId>, RmError> {
+        let mut stmt = self
+            .side
+            .prepare("SELECT xid FROM dist_tx_prepared ORDER BY xid")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))
+            .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+            .map_err(db_error)?;
+        let mut result = Vec::with_capacity(rows.len());
+        for bytes in rows {
+            result.append(&mut XaTransactionId::parse(&bytes, 1, false).map_err(|e| {
+                RmError::new(ErrorCode::RmError, format!("corrupt side table: {e}")).with_source(e)
+            })?);
+        }
+        Ok(result)
+    }
+}
+
+impl ResourceManager for SqliteResourceManager {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        if self.branch.is_some() {
+            return Err(protocol_error(
+                id,
+                "cannot start, a branch is already in use",
+            ));
+        }
+        if side_state(&self.side, &id.as_bytes(false))?.is_some() {
+            return Err(RmError::new(
+                ErrorCode::DuplicateTransactionId,
+                format!("transaction branch {id:?} exists already"),
+            )
+            .with_xid(*id));
+        }
+        self.execute("BEGIN IMMEDIATE")?;
+        self.branch = Some((*id, BranchState::Active));
+        Ok(ReturnCode::Ok)
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Idle, BranchState::Active)
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Suspended, BranchState::Active)
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Active, BranchState::Idle)
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Active, BranchState::RollbackOnly)
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.transition(id, BranchState::Active, BranchState::Suspended)
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.current_state(id) {
+            Some(BranchState::Idle) => {}
+            Some(BranchState::RollbackOnly) => {
+                self.execute("ROLLBACK")?;
+                self.branch = None;
+                return Ok(ReturnCode::RollbackUnspecified);
+            }
+            Some(_) => return Err(protocol_error(id, "is not ended")),
+            None => return Err(unknown(id)),
+        }
+        let xid = id.as_bytes(false);
+        lock(&self.conn)
+            .execute("INSERT INTO dist_tx_marker (xid) VALUES (?1)", [&xid])
+            .map_err(db_error)?;
+        self.side
+            .execute(
+                "INSERT INTO dist_tx_prepared (xid, state) VALUES (?1, ?2)",
+                params![xid, PREPARED],
+            )
+            .map_err(db_error)?;
+        self.branch = Some((*id, BranchState::Prepared));
+        Ok(ReturnCode::Ok)
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.current_state(id) {
+            Some(BranchState::Prepared) => {
+                self.execute("COMMIT")?;
+                self.branch = None;
+                self.remove_branch_records(id)?;
+                Ok(ReturnCode::Ok)
+            }
+            Some(_) => Err(protocol_error(id, "is not prepared")),
+            None => self.complete_recovered(id, true),
+        }
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.current_state(id) {
+            Some(BranchState::Idle) => {
+                self.execute("COMMIT")?;
+                self.branch = None;
+                Ok(ReturnCode::Ok)
+            }
+            Some(BranchState::RollbackOnly) => {
+                self.execute("ROLLBACK")?;
+                self.branch = None;
+                Ok(ReturnCode::RollbackUnspecified)
+            }
+            Some(_) => Err(protocol_error(id, "is not ended")),
+            None => Err(unknown(id)),
+        }
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        match self.current_state(id) {
+            Some(state) => {
+                self.execute("ROLLBACK")?;
+                self.branch = None;
+                if state == BranchState::Prepared {
+                    self.remove_branch_records(id)?;
+                }
+                Ok(ReturnCode::Ok)
+            }
+            None => self.complete_recovered(id, false),
+        }
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        match side_state(&self.side, &id.as_bytes(false))?.as_deref() {
+            Some(HEURISTICALLY_COMMITTED | HEURISTICALLY_ROLLED_BACK) => {
+                self.remove_branch_records(id)?;
+                Ok(ReturnCode::Ok)
+            }
+            Some(_) => Err(protocol_error(id, "was not heuristically completed")),
+            None => Err(unknown(id)),
+        }
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all()
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.recover_all()
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        // the complete list is always returned with the start of the scan
+        Ok(Vec::new())
+    }
+}
+
+fn lock(conn: &Arc<Mutex<Connection>>) -> MutexGuard<'_, Connection> {
+    conn.lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn side_state(side: &Connection, xid: &[u8]) -> Result<Option<String>, RmError> {
+    side.query_row(
+        "SELECT state FROM dist_tx_prepared WHERE xid = ?1",
+        [xid],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(db_error)
+}
+
+fn db_error(e: rusqlite::Error) -> RmError {
+    RmError::new(ErrorCode::RmError, format!("SQLite error: {e}")).with_source(e)
+}
+
+fn protocol_error(id: &XaTransactionId, s: &str) -> RmError {
+    RmError::new(
+        ErrorCode::ProtocolError,
+        format!("transaction branch {id:?} {s}"),
+    )
+    .with_xid(*id)
+}
+
+fn unknown(id: &XaTransactionId) -> RmError {
+    RmError::new(
+        ErrorCode::InvalidTransactionId,
+        format!("unknown transaction branch {id:?}"),
+    )
+    .with_xid(*id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqliteResourceManager;
+    use crate::{
+        file_store::tests::test_dir,
+        sync::{
+            rm::ResourceManager,
+            tm::{SimpleTransactionManager, TransactionManager},
+        },
+        ReturnCode, XaTransactionId,
+    };
+
+    fn count(rm: &SqliteResourceManager) -> i64 {
+        rm.connection()
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_commit_with_two_databases() {
+        let dir = test_dir("sqlite");
+        std::fs::create_dir_all(&dir).unwrap();
+        let rm_a = SqliteResourceManager::open(dir.join("a.db")).unwrap();
+        let rm_b = SqliteResourceManager::open(dir.join("b.db")).unwrap();
+        let (conn_a, conn_b) = (rm_a.connection(), rm_b.connection());
+        for conn in [&conn_a, &conn_b] {
+            conn.lock()
+                .unwrap()
+                .execute("CREATE TABLE t (v TEXT)", [])
+                .unwrap();
+        }
+
+        let mut tm = SimpleTransactionManager::new("test_commit_with_two_databases");
+        tm.register(Box::new(rm_a), 1, true).unwrap();
+        tm.register(Box::new(rm_b), 2, true).unwrap();
+
+        tm.start_transaction().unwrap();
+        for conn in [&conn_a, &conn_b] {
+            conn.lock()
+                .unwrap()
+                .execute("INSERT INTO t VALUES ('x')", [])
+                .unwrap();
+        }
+        tm.commit_transaction().unwrap();
+
+        tm.start_transaction().unwrap();
+        for conn in [&conn_a, &conn_b] {
+            conn.lock()
+                .unwrap()
+                .execute("INSERT INTO t VALUES ('y')", [])
+                .unwrap();
+        }
+        tm.rollback_transaction().unwrap();
+        drop(tm);
+
+        // only the committed row is in each database
+        for name in ["a.db", "b.db"] {
+            let rm = SqliteResourceManager::open(dir.join(name)).unwrap();
+            assert_eq!(count(&rm), 1, "{name}");
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_recover_after_lost_connection() {
+        let dir = test_dir("sqlite");
+        std::fs::create_dir_all(&dir).unwrap();
+        let xid = XaTransactionId::try_new(1, vec![1], vec![2]).unwrap();
+        {
+            let mut rm = SqliteResourceManager::open(dir.join("a.db")).unwrap();
+            rm.connection()
+                .lock()
+                .unwrap()
+                .execute("CREATE TABLE t (v TEXT)", [])
+                .unwrap();
+            rm.start(&xid).unwrap();
+            rm.connection()
+                .lock()
+                .unwrap()
+                .execute("INSERT INTO t VALUES ('x')", [])
+                .unwrap();
+            rm.end_success(&xid).unwrap();
+            rm.prepare(&xid).unwrap();
+            // dropping the connection rolls the open transaction back
+        }
+
+        let mut rm = SqliteResourceManager::open(dir.join("a.db")).unwrap();
+        let recovered = rm.recover().unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert!(matches!(
+            rm.commit(&recovered[0]),
+            Ok(ReturnCode::HeuristicallyRolledBack)
+        ));
+        assert_eq!(rm.recover().unwrap().len(), 1);
+        rm.forget(&recovered[0]).unwrap();
+        assert!(rm.recover().unwrap().is_empty());
+        assert_eq!(count(&rm), 0);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
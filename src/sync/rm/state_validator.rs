@@ -0,0 +1,132 @@
+use super::ResourceManager;
+use crate::{
+    branch_state::BranchStates, BranchState, ReturnCode, RmError, RmMethod, ValidationMode,
+    XaTransactionId,
+};
+
+/// Wraps a `ResourceManager` and tracks the [`BranchState`] of each transaction branch,
+/// to detect call sequences that are illegal according to the XA specification,
+/// like a `commit` without `prepare`, or a `start` for an active branch.
+///
+/// Depending on the [`ValidationMode`], an illegal call is rejected with
+/// `ErrorCode::ProtocolError`, or logged and forwarded.
+/// The wrapper helps finding bugs in transaction managers and in resource managers;
+/// wrap a `CResourceManager` into `CRmWrapper` first to validate it.
+///
+/// ```rust
+/// use dist_tx::{CallLog, ValidationMode, XaTransactionId,
+///     sync::rm::{MockResourceManager, ResourceManager, StateValidator}};
+///
+/// let mut rm = StateValidator::new(MockResourceManager::new(&CallLog::new()), ValidationMode::Reject);
+/// let id = XaTransactionId::try_new(1, vec![1], vec![1]).unwrap();
+/// rm.start(&id).unwrap();
+/// assert!(rm.commit(&id).is_err());
+/// ```
+#[derive(Debug)]
+pub struct StateValidator<T: ResourceManager> {
+    inner: T,
+    mode: ValidationMode,
+    states: BranchStates,
+}
+impl<T: ResourceManager> StateValidator<T> {
+    /// Wraps the given resource manager.
+    pub fn new(inner: T, mode: ValidationMode) -> StateValidator<T> {
+        StateValidator {
+            inner,
+            mode,
+            states: BranchStates::default(),
+        }
+    }
+
+    /// Returns the current state of the given branch.
+    pub fn state(&self, id: &XaTransactionId) -> BranchState {
+        self.states.get(id)
+    }
+
+    /// Returns the wrapped resource manager.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn call<F>(
+        &mut self,
+        method: RmMethod,
+        id: &XaTransactionId,
+        f: F,
+    ) -> Result<ReturnCode, RmError>
+    where
+        F: FnOnce(&mut T) -> Result<ReturnCode, RmError>,
+    {
+        self.states.check(self.mode, method, id)?;
+        let result = f(&mut self.inner);
+        self.states.update(method, id, &result);
+        result
+    }
+
+    fn call_recover<F>(&mut self, f: F) -> Result<Vec<XaTransactionId>, RmError>
+    where
+        F: FnOnce(&mut T) -> Result<Vec<XaTransactionId>, RmError>,
+    {
+        let result = f(&mut self.inner);
+        self.states.recovered(&result);
+        result
+    }
+}
+
+impl<T: ResourceManager> ResourceManager for StateValidator<T> {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Start, id, |rm| rm.start(id))
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByJoining, id, |rm| rm.start_by_joining(id))
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByResuming, id, |rm| rm.start_by_resuming(id))
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuccess, id, |rm| rm.end_success(id))
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndFailure, id, |rm| rm.end_failure(id))
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuspend, id, |rm| rm.end_suspend(id))
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Prepare, id, |rm| rm.prepare(id))
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Commit, id, |rm| rm.commit(id))
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::CommitOnePhase, id, |rm| rm.commit_one_phase(id))
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Rollback, id, |rm| rm.rollback(id))
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Forget, id, |rm| rm.forget(id))
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(ResourceManager::recover)
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(ResourceManager::begin_recover)
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(ResourceManager::end_recover)
+    }
+}
@@ -0,0 +1,236 @@
+use super::ResourceManager;
+use crate::{
+    xa_span, CompletionOutcome, PrepareVote, ReturnCode, RmError, RmMethod, XaTransactionId,
+};
+use std::time::Instant;
+
+/// Wraps a `ResourceManager` and opens a [tracing](https://docs.rs/tracing) span
+/// for each XA call.
+///
+/// The span `xa_call` has the fields `method`, `rm_id`, `gtid` and `bqual` (both hex-encoded),
+/// `duration_us`, and, depending on the outcome, `return_code`, `error_code`, or,
+/// for the recover methods, `recovered`.
+/// `SimpleTransactionManager` opens a parent span `global_transaction` for each
+/// global transaction, so that the calls of all branches form a tree.
+#[derive(Debug)]
+pub struct TracingRm<T: ResourceManager> {
+    inner: T,
+    rm_id: u64,
+}
+impl<T: ResourceManager> TracingRm<T> {
+    /// Wraps the given resource manager; the `rm_id` is added to the spans.
+    pub fn new(inner: T, rm_id: u64) -> TracingRm<T> {
+        TracingRm { inner, rm_id }
+    }
+
+    /// Returns the wrapped resource manager.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn call<R, F>(&mut self, method: RmMethod, id: &XaTransactionId, f: F) -> Result<R, RmError>
+    where
+        R: Clone + Into<ReturnCode>,
+        F: FnOnce(&mut T) -> Result<R, RmError>,
+    {
+        let span = xa_span::xa_call(method, Some(id), self.rm_id);
+        let _entered = span.enter();
+        let start = Instant::now();
+        let result = f(&mut self.inner);
+        xa_span::record_result(&span, start, &result);
+        result
+    }
+
+    fn call_recover<F>(&mut self, method: RmMethod, f: F) -> Result<Vec<XaTransactionId>, RmError>
+    where
+        F: FnOnce(&mut T) -> Result<Vec<XaTransactionId>, RmError>,
+    {
+        let span = xa_span::xa_call(method, None, self.rm_id);
+        let _entered = span.enter();
+        let start = Instant::now();
+        let result = f(&mut self.inner);
+        xa_span::record_recover(&span, start, &result);
+        result
+    }
+}
+
+impl<T: ResourceManager> ResourceManager for TracingRm<T> {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Start, id, |rm| rm.start(id))
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByJoining, id, |rm| rm.start_by_joining(id))
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::StartByResuming, id, |rm| rm.start_by_resuming(id))
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuccess, id, |rm| rm.end_success(id))
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndFailure, id, |rm| rm.end_failure(id))
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::EndSuspend, id, |rm| rm.end_suspend(id))
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Prepare, id, |rm| rm.prepare(id))
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Commit, id, |rm| rm.commit(id))
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::CommitOnePhase, id, |rm| rm.commit_one_phase(id))
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Rollback, id, |rm| rm.rollback(id))
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.call(RmMethod::Forget, id, |rm| rm.forget(id))
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::Recover, ResourceManager::recover)
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::BeginRecover, ResourceManager::begin_recover)
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.call_recover(RmMethod::EndRecover, ResourceManager::end_recover)
+    }
+
+    fn prepare_vote(&mut self, id: &XaTransactionId) -> Result<PrepareVote, RmError> {
+        self.call(RmMethod::Prepare, id, |rm| rm.prepare_vote(id))
+    }
+
+    fn commit_outcome(&mut self, id: &XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        self.call(RmMethod::Commit, id, |rm| rm.commit_outcome(id))
+    }
+
+    fn commit_one_phase_outcome(
+        &mut self,
+        id: &XaTransactionId,
+    ) -> Result<CompletionOutcome, RmError> {
+        self.call(RmMethod::CommitOnePhase, id, |rm| {
+            rm.commit_one_phase_outcome(id)
+        })
+    }
+
+    fn rollback_outcome(&mut self, id: &XaTransactionId) -> Result<CompletionOutcome, RmError> {
+        self.call(RmMethod::Rollback, id, |rm| rm.rollback_outcome(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TracingRm;
+    use crate::{
+        sync::{
+            rm::MockResourceManager,
+            tm::{SimpleTransactionManager, TransactionManager},
+        },
+        CallLog,
+    };
+    use std::{
+        collections::BTreeMap,
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    #[derive(Debug, Default)]
+    struct RecordedSpan {
+        name: &'static str,
+        parent: Option<u64>,
+        fields: BTreeMap<&'static str, String>,
+    }
+    impl Visit for RecordedSpan {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.fields.insert(field.name(), format!("{value:?}"));
+        }
+    }
+
+    // Records all spans with their contextual parent; single-threaded use only.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+        stack: Arc<Mutex<Vec<u64>>>,
+    }
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut span = RecordedSpan {
+                name: attrs.metadata().name(),
+                parent: self.stack.lock().unwrap().last().copied(),
+                fields: BTreeMap::new(),
+            };
+            attrs.record(&mut span);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span);
+            Id::from_u64(spans.len() as u64)
+        }
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let idx = usize::try_from(span.into_u64()).unwrap() - 1;
+            values.record(&mut self.spans.lock().unwrap()[idx]);
+        }
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+        fn event(&self, _event: &Event<'_>) {}
+        fn enter(&self, span: &Id) {
+            self.stack.lock().unwrap().push(span.into_u64());
+        }
+        fn exit(&self, _span: &Id) {
+            self.stack.lock().unwrap().pop();
+        }
+    }
+
+    #[test]
+    fn test_spans_form_a_tree() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let log = CallLog::new();
+            let mut tm = SimpleTransactionManager::new("test_spans_form_a_tree");
+            for rm_id in 1..=2 {
+                let rm = TracingRm::new(MockResourceManager::new(&log), rm_id);
+                tm.register(Box::new(rm), rm_id, false).unwrap();
+            }
+            tm.start_transaction().unwrap();
+            tm.commit_transaction().unwrap();
+        });
+
+        let spans = recorder.spans.lock().unwrap();
+        let (parent, _) = spans
+            .iter()
+            .enumerate()
+            .find(|(_, span)| span.name == "global_transaction")
+            .unwrap();
+        let calls: Vec<&RecordedSpan> = spans
+            .iter()
+            .filter(|span| span.name == "xa_call" && span.parent == Some(parent as u64 + 1))
+            .collect();
+        // start, end, prepare, commit for both branches
+        assert_eq!(calls.len(), 8);
+        for call in calls {
+            assert_eq!(call.fields["return_code"], "Ok");
+            assert!(call.fields.contains_key("duration_us"));
+            assert!(call.fields.contains_key("bqual"));
+        }
+    }
+}
@@ -1,8 +1,23 @@
 //! The trait `TransactionManager` and a simple implementation.
+//!
+//! `simulate_crashes` and `simulate_crashes_without_log` verify the recovery
+//! of `SimpleTransactionManager`.
+//!
+//! With the feature `cli`, `Admin` lists and resolves in-doubt transaction branches;
+//! it is the basis of the command-line tool `dist_tx_admin`.
+#[cfg(feature = "cli")]
+mod admin;
+mod crash_simulation;
 mod simple_transaction_manager;
 mod transaction_manager;
 
 pub use self::{
-    simple_transaction_manager::SimpleTransactionManager, transaction_manager::Status,
+    crash_simulation::{simulate_crashes, simulate_crashes_without_log, CrashPoint, CrashReport},
+    simple_transaction_manager::SimpleTransactionManager,
+    transaction_manager::Status,
     transaction_manager::TransactionManager,
 };
+
+#[cfg(feature = "cli")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cli")))]
+pub use self::admin::{run_command, Admin, InDoubtBranch, Resolution};
@@ -0,0 +1,349 @@
+use crate::{
+    sync::rm::{CRmWrapper, FileResourceManager, ResourceManager},
+    FileStore, FileTmLog, JavaXid, ReturnCode, SimpleXid, TmLog, XaError, XaTransactionId,
+};
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+    path::Path,
+};
+
+/// The manual resolution of an in-doubt transaction branch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Commit the branch.
+    Commit,
+    /// Roll back the branch.
+    Rollback,
+    /// Forget the heuristically completed branch.
+    Forget,
+}
+
+/// A prepared transaction branch that a resource manager reports in its recovery.
+#[derive(Clone, Debug)]
+pub struct InDoubtBranch {
+    /// The resource manager that holds the branch.
+    pub rm_id: u64,
+    /// The id of the branch.
+    pub xid: XaTransactionId,
+    /// The parts of the id, if it was produced by a `SimpleTransactionManager`.
+    pub parts: Option<SimpleXid>,
+    /// The parts of the id, if it was produced by a Java transaction manager,
+    /// which is then responsible for the branch.
+    pub java_xid: Option<JavaXid>,
+    /// `true` if the loaded transaction manager log contains the decision to commit
+    /// the global transaction.
+    pub commit_logged: bool,
+}
+
+/// Lists the in-doubt transaction branches of a set of resource managers, and
+/// resolves them manually.
+///
+/// This is the library part of the administrative command-line tool `dist_tx_admin`.
+/// Applications whose resource managers are not supported by the tool's configuration
+/// (e.g., `PgResourceManager` with their own executor) can build their own tool by
+/// registering their resource managers here and passing the command-line to [`run_command`].
+///
+/// Use it only while the transaction manager is stopped.
+#[derive(Debug, Default)]
+pub struct Admin {
+    rms: BTreeMap<u64, Box<dyn ResourceManager>>,
+    pending: Vec<u64>,
+}
+impl Admin {
+    /// Creates an instance without resource managers.
+    #[must_use]
+    pub fn new() -> Admin {
+        Admin::default()
+    }
+
+    /// Creates an instance from a configuration file.
+    ///
+    /// Each line of the file is empty, a comment starting with `#`, or one of
+    ///
+    /// * `tm_log <path>`: the [`FileTmLog`] of the transaction manager
+    /// * `rm <rm_id> file <directory>`: a `FileResourceManager` on the [`FileStore`]
+    ///   in the directory
+    /// * `rm <rm_id> sqlite <path>`: a `SqliteResourceManager` on the database file
+    ///   (requires the feature `sqlite`)
+    ///
+    /// # Errors
+    ///
+    /// `XaError::UsageDetails` if the configuration is invalid,
+    /// `XaError::Log` if the log cannot be read,
+    /// `XaError::RmErrors` if a resource manager cannot be opened.
+    pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Admin, XaError> {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path)
+            .map_err(|e| XaError::UsageDetails(format!("cannot read {}: {e}", path.display())))?;
+        let mut admin = Admin::new();
+        for (no, line) in config.lines().enumerate() {
+            let invalid =
+                || XaError::UsageDetails(format!("{}:{}: invalid line", path.display(), no + 1));
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                [] => {}
+                [first, ..] if first.starts_with('#') => {}
+                ["tm_log", log_path] => {
+                    let log = FileTmLog::open(log_path).map_err(|e| XaError::Log(e.to_string()))?;
+                    admin.load_log(&log)?;
+                }
+                ["rm", rm_id, kind, rm_path] => {
+                    let rm_id = rm_id.parse().map_err(|_| invalid())?;
+                    let rm = open_rm(kind, rm_path).ok_or_else(invalid)??;
+                    admin.add_resource_manager(rm_id, rm);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(admin)
+    }
+
+    /// Adds a resource manager.
+    pub fn add_resource_manager(&mut self, rm_id: u64, rm: Box<dyn ResourceManager>) {
+        self.rms.insert(rm_id, rm);
+    }
+
+    /// Reads the pending commit decisions from the log of the transaction manager.
+    ///
+    /// # Errors
+    ///
+    /// `XaError::Log` if the log cannot be read.
+    pub fn load_log(&mut self, log: &dyn TmLog) -> Result<(), XaError> {
+        self.pending = log
+            .pending_commits()
+            .map_err(|e| XaError::Log(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Returns the prepared branches of all resource managers.
+    ///
+    /// # Errors
+    ///
+    /// `XaError::RmErrors` if resource managers failed to recover.
+    pub fn in_doubt(&mut self) -> Result<Vec<InDoubtBranch>, XaError> {
+        let mut branches = Vec::new();
+        let mut errors = Vec::new();
+        for (rm_id, rm) in &mut self.rms {
+            match rm.recover() {
+                Ok(xids) => branches.extend(xids.into_iter().map(|xid| {
+                    let parts = SimpleXid::decode(&xid);
+                    InDoubtBranch {
+                        rm_id: *rm_id,
+                        commit_logged: parts.is_some_and(|p| self.pending.contains(&p.global_tid)),
+                        parts,
+                        java_xid: JavaXid::decode(&xid),
+                        xid,
+                    }
+                })),
+                Err(e) => errors.push(e),
+            }
+        }
+        if errors.is_empty() {
+            Ok(branches)
+        } else {
+            Err(XaError::RmErrors(errors))
+        }
+    }
+
+    /// Resolves a branch of the given resource manager.
+    ///
+    /// # Errors
+    ///
+    /// `XaError::UsageDetails` if the resource manager is unknown,
+    /// `XaError::RmErrors` if the resource manager failed.
+    pub fn resolve(
+        &mut self,
+        rm_id: u64,
+        xid: &XaTransactionId,
+        resolution: Resolution,
+    ) -> Result<ReturnCode, XaError> {
+        let rm = self
+            .rms
+            .get_mut(&rm_id)
+            .ok_or_else(|| XaError::UsageDetails(format!("unknown resource manager {rm_id}")))?;
+        match resolution {
+            Resolution::Commit => rm.commit(xid),
+            Resolution::Rollback => rm.rollback(xid),
+            Resolution::Forget => rm.forget(xid),
+        }
+        .map_err(|e| XaError::RmErrors(vec![e]))
+    }
+}
+
+fn open_rm(kind: &str, path: &str) -> Option<Result<Box<dyn ResourceManager>, XaError>> {
+    match kind {
+        "file" => Some(
+            FileStore::open(path)
+                .map(|store| {
+                    Box::new(CRmWrapper(FileResourceManager::new(&store)))
+                        as Box<dyn ResourceManager>
+                })
+                .map_err(|e| XaError::UsageDetails(format!("cannot open {path}: {e}"))),
+        ),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Some(
+            crate::sync::rm::SqliteResourceManager::open(path)
+                .map(|rm| Box::new(rm) as Box<dyn ResourceManager>)
+                .map_err(|e| XaError::RmErrors(vec![e])),
+        ),
+        _ => None,
+    }
+}
+
+/// Runs a command of the administrative command-line tool.
+///
+/// The commands are
+///
+/// * `list`: lists the in-doubt branches of all resource managers
+/// * `commit <rm_id> <gid>`, `rollback <rm_id> <gid>`, `forget <rm_id> <gid>`:
+///   resolves the branch with the given id (in the format of
+///   [`XaTransactionId::to_gid`]) after a confirmation that is read from `input`;
+///   with the option `--yes`, no confirmation is requested
+///
+/// # Errors
+///
+/// `XaError::UsageDetails` if the command is invalid, or other errors of [`Admin`].
+pub fn run_command(
+    admin: &mut Admin,
+    args: &[String],
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), XaError> {
+    let yes = args.iter().any(|arg| arg == "--yes");
+    let args: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .filter(|arg| *arg != "--yes")
+        .collect();
+    let usage = || {
+        XaError::UsageDetails(
+            "usage: list | (commit|rollback|forget) <rm_id> <gid> [--yes]".to_string(),
+        )
+    };
+    let resolution = match args[..] {
+        ["list"] => {
+            for branch in admin.in_doubt()? {
+                writeln!(output, "{}", describe(&branch))?;
+            }
+            return Ok(());
+        }
+        ["commit", ..] => Resolution::Commit,
+        ["rollback", ..] => Resolution::Rollback,
+        ["forget", ..] => Resolution::Forget,
+        _ => return Err(usage()),
+    };
+    let ["commit" | "rollback" | "forget", rm_id, gid] = args[..] else {
+        return Err(usage());
+    };
+    let rm_id = rm_id.parse().map_err(|_| usage())?;
+    let xid = XaTransactionId::from_gid(gid)?;
+
+    if !yes {
+        let branch = admin
+            .in_doubt()?
+            .into_iter()
+            .find(|branch| branch.rm_id == rm_id && branch.xid == xid)
+            .ok_or_else(|| {
+                XaError::UsageDetails(format!("rm {rm_id} has no in-doubt branch {gid}"))
+            })?;
+        write!(output, "{resolution:?} {}? [y/N] ", describe(&branch))?;
+        output.flush()?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            writeln!(output, "cancelled")?;
+            return Ok(());
+        }
+    }
+    let rc = admin.resolve(rm_id, &xid, resolution)?;
+    writeln!(output, "{resolution:?}: {rc:?}")?;
+    Ok(())
+}
+
+fn describe(branch: &InDoubtBranch) -> String {
+    format!(
+        "rm {} {} ({}){}",
+        branch.rm_id,
+        branch.xid.to_gid(),
+        match (&branch.parts, &branch.java_xid) {
+            (Some(parts), _) => parts.to_string(),
+            (None, Some(java_xid)) => java_xid.to_string(),
+            (None, None) => "foreign format".to_string(),
+        },
+        if branch.commit_logged {
+            ", commit logged"
+        } else {
+            ""
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_command, Admin};
+    use crate::{
+        file_store::tests::test_dir,
+        sync::rm::{CRmWrapper, FileResourceManager, ResourceManager},
+        FileStore, FileTmLog, SimpleXid, TmLog, XaTransactionId,
+    };
+
+    fn run(admin: &mut Admin, args: &str, input: &str) -> String {
+        let args: Vec<String> = args.split(' ').map(str::to_string).collect();
+        let mut output = Vec::new();
+        run_command(admin, &args, &mut input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_in_doubt_branches() {
+        let dir = test_dir("admin");
+        let store = FileStore::open(dir.join("rm")).unwrap();
+        let mut rm = CRmWrapper(FileResourceManager::new(&store));
+        for global_tid in 1..=2 {
+            let xid = SimpleXid {
+                global_tid,
+                tm_id: 256,
+                rm_id: 1,
+            }
+            .encode();
+            rm.start(&xid).unwrap();
+            rm.end_success(&xid).unwrap();
+            rm.prepare(&xid).unwrap();
+        }
+        let atomikos = XaTransactionId::try_new(0x4154_4F4D, "node.tm4711", "node.tm1").unwrap();
+        rm.start(&atomikos).unwrap();
+        rm.end_success(&atomikos).unwrap();
+        rm.prepare(&atomikos).unwrap();
+        let mut log = FileTmLog::open(dir.join("tm.log")).unwrap();
+        log.log_commit(2, &[1]).unwrap();
+        std::fs::write(
+            dir.join("admin.conf"),
+            format!(
+                "# test\ntm_log {}\nrm 1 file {}\n",
+                dir.join("tm.log").display(),
+                dir.join("rm").display()
+            ),
+        )
+        .unwrap();
+
+        let mut admin = Admin::from_config(dir.join("admin.conf")).unwrap();
+        let list = run(&mut admin, "list", "");
+        assert_eq!(list.lines().count(), 3);
+        assert!(list.contains("(gtid 2, tm_id 256, rm_id 1), commit logged"));
+        assert!(list.contains("(Atomikos node node.tm, tx node.tm4711, branch node.tm1)"));
+        let gid = atomikos.to_gid();
+        run(&mut admin, &format!("rollback 1 {gid} --yes"), "");
+
+        let gid = admin.in_doubt().unwrap()[1].xid.to_gid();
+        let answer = run(&mut admin, &format!("commit 1 {gid}"), "n\n");
+        assert!(answer.ends_with("cancelled\n"));
+        assert_eq!(admin.in_doubt().unwrap().len(), 2);
+
+        let answer = run(&mut admin, &format!("commit 1 {gid}"), "y\n");
+        assert!(answer.ends_with("Commit: Ok\n"));
+        let gid = admin.in_doubt().unwrap()[0].xid.to_gid();
+        run(&mut admin, &format!("rollback 1 {gid} --yes"), "");
+        assert!(admin.in_doubt().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,562 @@
+use super::{SimpleTransactionManager, TransactionManager};
+use crate::{
+    fault::SplitMix64,
+    sync::rm::{CRmWrapper, FileResourceManager, ResourceManager},
+    ErrorCode, FileStore, FileTmLog, Flags, ReturnCode, RmError, TmEvent, TmLog, XaTransactionId,
+};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+const TM_NAME: &str = "crash simulation";
+const KEY: &str = "crash simulation";
+const LOG_FILE: &str = "tm.log";
+
+/// The outcome of [`simulate_crashes`].
+#[derive(Clone, Debug)]
+pub struct CrashReport {
+    /// The seed of the simulation.
+    pub seed: u64,
+    /// The number of resource managers that took part in the transaction.
+    pub rm_count: usize,
+    /// `true` if the transaction manager used a durable log.
+    pub with_log: bool,
+    /// The violated property of the run without crash, which must commit the transaction.
+    pub violation_without_crash: Option<String>,
+    /// The simulated crashes, two for each step of `commit_transaction()`.
+    pub crash_points: Vec<CrashPoint>,
+}
+impl CrashReport {
+    /// Returns `true` if the transaction was completed atomically after each crash.
+    #[must_use]
+    pub fn is_atomic(&self) -> bool {
+        self.violation_without_crash.is_none()
+            && self.crash_points.iter().all(|cp| cp.violation.is_none())
+    }
+}
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "seed {}, {} resource managers, {}",
+            self.seed,
+            self.rm_count,
+            if self.with_log {
+                "with log"
+            } else {
+                "without log"
+            }
+        )?;
+        writeln!(
+            f,
+            "no crash: {}",
+            self.violation_without_crash
+                .as_ref()
+                .map_or("committed".to_string(), |v| format!("VIOLATION: {v}"))
+        )?;
+        for cp in &self.crash_points {
+            writeln!(
+                f,
+                "crash {} step {} ({}): {}{}",
+                if cp.after_step { "after" } else { "before" },
+                cp.step,
+                cp.description,
+                if cp.committed {
+                    "committed"
+                } else {
+                    "rolled back"
+                },
+                cp.violation
+                    .as_ref()
+                    .map_or(String::new(), |v| format!(", VIOLATION: {v}"))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A simulated crash of the transaction manager, and the outcome after recovery.
+#[derive(Clone, Debug)]
+pub struct CrashPoint {
+    /// The number of the step in `commit_transaction()`, starting with 0.
+    pub step: usize,
+    /// The step, e.g. `"prepare(rm 2)"` or `"log_commit"`.
+    pub description: String,
+    /// `true` if the crash happened after the step took effect, `false` if before.
+    pub after_step: bool,
+    /// `true` if all branches were committed after recovery.
+    pub committed: bool,
+    /// The violated property, if the outcome is not correct.
+    pub violation: Option<String>,
+}
+
+/// Crashes a `SimpleTransactionManager` at every step of `commit_transaction()`,
+/// restarts it, runs recovery, and checks that the transaction was completed atomically.
+///
+/// The transaction manager uses a [`FileTmLog`] and `FileResourceManager`s, which are
+/// created in sub-directories of `dir`. Each step (every call of a resource manager
+/// and every write to the log) is crashed twice, once before and once after it takes effect.
+/// After a crash, no further call of the transaction manager reaches a resource manager
+/// or the log, and all state that was not written durably is lost.
+///
+/// After the restart, only the first resource manager is registered before `recover()`
+/// is called; the others are registered afterwards, with cleanup.
+///
+/// A correct outcome means that either all branches are committed or all are rolled back,
+/// that the transaction is committed exactly if the commit decision was logged,
+/// and that neither prepared branches nor commit decisions are left behind.
+/// Without a crash, the transaction must be committed.
+///
+/// The seed determines the number of resource managers and the written data;
+/// runs with the same seed are identical.
+///
+/// ```rust
+/// # use dist_tx::sync::tm::simulate_crashes;
+/// # let dir = std::env::temp_dir().join(format!("dist_tx_doc_crash_{}", std::process::id()));
+/// let report = simulate_crashes(&dir, 42).unwrap();
+/// assert!(report.is_atomic(), "{report}");
+/// # std::fs::remove_dir_all(&dir).ok();
+/// ```
+///
+/// # Errors
+///
+/// `std::io::Error` if the files of the simulation cannot be created.
+pub fn simulate_crashes<P: AsRef<Path>>(dir: P, seed: u64) -> std::io::Result<CrashReport> {
+    simulate(dir.as_ref(), seed, true)
+}
+
+/// Like [`simulate_crashes`], for a `SimpleTransactionManager` without a durable log.
+///
+/// Such a transaction manager keeps its commit decisions in memory, so it cannot survive
+/// a crash of its own during `commit_transaction()`. Here, only the resource managers crash;
+/// the transaction manager stays, unregisters them, and registers them again after their
+/// restart, the first one before `recover()` and the others with cleanup.
+/// The transaction must be committed exactly if the commit was decided.
+///
+/// # Errors
+///
+/// `std::io::Error` if the files of the simulation cannot be created.
+pub fn simulate_crashes_without_log<P: AsRef<Path>>(
+    dir: P,
+    seed: u64,
+) -> std::io::Result<CrashReport> {
+    simulate(dir.as_ref(), seed, false)
+}
+
+fn simulate(dir: &Path, seed: u64, with_log: bool) -> std::io::Result<CrashReport> {
+    let mut rng = SplitMix64(seed);
+    #[allow(clippy::cast_possible_truncation)]
+    let rm_count = 2 + (rng.next() % 3) as usize;
+    let values: Vec<String> = (0..rm_count)
+        .map(|_| format!("{:016x}", rng.next()))
+        .collect();
+
+    // a run without crash enumerates the steps
+    let Run {
+        steps,
+        violation: violation_without_crash,
+        ..
+    } = run(&dir.join("no_crash"), &values, None, with_log)?;
+
+    let mut crash_points = Vec::with_capacity(2 * steps.len());
+    for (step, description) in steps.iter().enumerate() {
+        for after_step in [false, true] {
+            let run_dir = dir.join(format!(
+                "{step}_{}",
+                if after_step { "after" } else { "before" }
+            ));
+            let run = run(&run_dir, &values, Some((step, after_step)), with_log)?;
+            crash_points.push(CrashPoint {
+                step,
+                description: description.clone(),
+                after_step,
+                committed: run.committed,
+                violation: run.violation,
+            });
+        }
+    }
+    Ok(CrashReport {
+        seed,
+        rm_count,
+        with_log,
+        violation_without_crash,
+        crash_points,
+    })
+}
+
+// The outcome of a single run.
+struct Run {
+    // the steps up to the crash
+    steps: Vec<String>,
+    committed: bool,
+    violation: Option<String>,
+}
+
+// Runs the transaction until the crash, restarts, recovers, and checks the outcome.
+fn run(
+    dir: &Path,
+    values: &[String],
+    crash_at: Option<(usize, bool)>,
+    with_log: bool,
+) -> std::io::Result<Run> {
+    std::fs::create_dir_all(dir)?;
+    let log_path = dir.join(LOG_FILE);
+    let switch = CrashSwitch::new(crash_at);
+
+    let survivor = run_until_crash(dir, values, &switch, with_log)?;
+
+    let (steps, commit_logged) = {
+        let switch = switch.lock();
+        (switch.steps.clone(), switch.commit_logged)
+    };
+
+    // restart and recover
+    let stores = (0..values.len())
+        .map(|i| FileStore::open(store_dir(dir, i)))
+        .collect::<std::io::Result<Vec<FileStore>>>()?;
+    let mut tm = match survivor {
+        Some(tm) => tm,
+        None => SimpleTransactionManager::with_log(TM_NAME, FileTmLog::open(&log_path)?),
+    };
+    let mut rms = (1..)
+        .zip(&stores)
+        .map(|(rm_id, store)| (rm_id, Box::new(CRmWrapper(FileResourceManager::new(store)))));
+    // only the first resource manager is back before recover(),
+    // the others are registered later, with cleanup
+    if let Some((rm_id, rm)) = rms.next() {
+        tm.register(rm, rm_id, false).map_err(other)?;
+    }
+    let recovery = tm.recover();
+    for (rm_id, rm) in rms {
+        tm.register(rm, rm_id, true).map_err(other)?;
+    }
+
+    let committed = stores
+        .iter()
+        .zip(values)
+        .filter(|(store, value)| store.get(KEY).as_deref() == Some(value.as_bytes()))
+        .count();
+    let prepared = stores
+        .iter()
+        .map(|store| {
+            store
+                .xa_recover(Flags::START_RECOVERY_SCAN)
+                .map_or(0, |v| v.len())
+        })
+        .sum::<usize>();
+    let pending = if with_log {
+        FileTmLog::open(&log_path)?.pending_commits()?.len()
+    } else {
+        tm.pending_commits().map_err(other)?.len()
+    };
+
+    let violation = if let Err(e) = recovery {
+        Some(format!("recovery failed with {e:?}"))
+    } else if committed != 0 && committed != values.len() {
+        Some(format!(
+            "{committed} of {} branches were committed",
+            values.len()
+        ))
+    } else if crash_at.is_none() && committed == 0 {
+        Some("the transaction was rolled back without a crash".to_string())
+    } else if (committed == values.len()) != commit_logged {
+        Some(if commit_logged {
+            "the commit was decided, but the transaction was rolled back".to_string()
+        } else {
+            "the transaction was committed without a commit decision".to_string()
+        })
+    } else if prepared > 0 {
+        Some(format!("{prepared} prepared branches are left"))
+    } else if pending > 0 {
+        Some(format!("{pending} commit decisions are left in the log"))
+    } else {
+        None
+    };
+    Ok(Run {
+        steps,
+        committed: committed == values.len(),
+        violation,
+    })
+}
+
+// Runs the transaction until the crash; returns the transaction manager if it survives.
+fn run_until_crash(
+    dir: &Path,
+    values: &[String],
+    switch: &CrashSwitch,
+    with_log: bool,
+) -> std::io::Result<Option<SimpleTransactionManager>> {
+    let stores = (0..values.len())
+        .map(|i| FileStore::open(store_dir(dir, i)))
+        .collect::<std::io::Result<Vec<FileStore>>>()?;
+    let mut tm = if with_log {
+        let log = CrashingLog {
+            inner: FileTmLog::open(dir.join(LOG_FILE))?,
+            switch: switch.clone(),
+        };
+        SimpleTransactionManager::with_log(TM_NAME, log)
+    } else {
+        // the decision is only kept in memory
+        let switch = switch.clone();
+        let mut tm = SimpleTransactionManager::new(TM_NAME);
+        tm.add_listener(move |event: &TmEvent| {
+            if let TmEvent::DecisionMade { commit: true, .. } = event {
+                switch.lock().commit_logged = true;
+            }
+        });
+        tm
+    };
+    for (rm_id, store) in (1..).zip(&stores) {
+        let rm = CrashingRm {
+            inner: CRmWrapper(FileResourceManager::new(store)),
+            rm_id,
+            switch: switch.clone(),
+        };
+        tm.register(Box::new(rm), rm_id, false).map_err(other)?;
+    }
+    tm.start_transaction().map_err(other)?;
+    for (store, value) in stores.iter().zip(values) {
+        store.put(KEY, value.as_bytes()).map_err(other)?;
+    }
+    switch.lock().counting = true;
+    // fails if the crash happens
+    tm.commit_transaction().ok();
+    if with_log {
+        // the process ends, with the transaction manager and the stores
+        Ok(None)
+    } else {
+        // the resource managers end, with the stores, but the transaction manager stays
+        for rm_id in (1..).take(values.len()) {
+            tm.unregister(rm_id).map_err(other)?;
+        }
+        Ok(Some(tm))
+    }
+}
+
+// The directory of the store of the i-th resource manager.
+fn store_dir(dir: &Path, i: usize) -> PathBuf {
+    dir.join(format!("rm{}", i + 1))
+}
+
+fn other<E: fmt::Debug>(e: E) -> std::io::Error {
+    std::io::Error::other(format!("{e:?}"))
+}
+
+// Decides which step crashes, and makes all later steps fail without effect.
+#[derive(Clone, Debug)]
+struct CrashSwitch(Arc<Mutex<SwitchState>>);
+
+#[derive(Debug)]
+struct SwitchState {
+    // the step to crash, and whether the crash happens after the step took effect
+    crash_at: Option<(usize, bool)>,
+    // only the steps of commit_transaction() are counted
+    counting: bool,
+    crashed: bool,
+    steps: Vec<String>,
+    // the commit decision was logged, or, without a log, made
+    commit_logged: bool,
+}
+
+impl CrashSwitch {
+    fn new(crash_at: Option<(usize, bool)>) -> CrashSwitch {
+        CrashSwitch(Arc::new(Mutex::new(SwitchState {
+            crash_at,
+            counting: false,
+            crashed: false,
+            steps: Vec::new(),
+            commit_logged: false,
+        })))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SwitchState> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    // Returns whether the step is to be executed, and whether it fails afterwards.
+    fn step(&self, description: String) -> (bool, bool) {
+        let mut state = self.lock();
+        if state.crashed {
+            return (false, true);
+        }
+        if !state.counting {
+            return (true, false);
+        }
+        let step = state.steps.len();
+        state.steps.push(description);
+        match state.crash_at {
+            Some((crash_step, after_step)) if crash_step == step => {
+                state.crashed = true;
+                (after_step, true)
+            }
+            _ => (true, false),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CrashingRm {
+    inner: CRmWrapper<FileResourceManager>,
+    rm_id: u64,
+    switch: CrashSwitch,
+}
+impl CrashingRm {
+    fn guarded<T, F>(&mut self, method: &str, f: F) -> Result<T, RmError>
+    where
+        F: FnOnce(&mut CRmWrapper<FileResourceManager>) -> Result<T, RmError>,
+    {
+        let crash = || RmError::new(ErrorCode::RmFailure, "simulated crash".to_string());
+        let (execute, fail) = self.switch.step(format!("{method}(rm {})", self.rm_id));
+        if !execute {
+            return Err(crash());
+        }
+        let result = f(&mut self.inner);
+        if fail {
+            Err(crash())
+        } else {
+            result
+        }
+    }
+}
+
+impl ResourceManager for CrashingRm {
+    fn start(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("start", |rm| rm.start(id))
+    }
+
+    fn start_by_joining(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("start_by_joining", |rm| rm.start_by_joining(id))
+    }
+
+    fn start_by_resuming(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("start_by_resuming", |rm| rm.start_by_resuming(id))
+    }
+
+    fn end_success(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("end_success", |rm| rm.end_success(id))
+    }
+
+    fn end_failure(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("end_failure", |rm| rm.end_failure(id))
+    }
+
+    fn end_suspend(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("end_suspend", |rm| rm.end_suspend(id))
+    }
+
+    fn prepare(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("prepare", |rm| rm.prepare(id))
+    }
+
+    fn commit(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("commit", |rm| rm.commit(id))
+    }
+
+    fn commit_one_phase(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("commit_one_phase", |rm| rm.commit_one_phase(id))
+    }
+
+    fn rollback(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("rollback", |rm| rm.rollback(id))
+    }
+
+    fn forget(&mut self, id: &XaTransactionId) -> Result<ReturnCode, RmError> {
+        self.guarded("forget", |rm| rm.forget(id))
+    }
+
+    fn recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.guarded("recover", ResourceManager::recover)
+    }
+
+    fn begin_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.guarded("begin_recover", ResourceManager::begin_recover)
+    }
+
+    fn end_recover(&mut self) -> Result<Vec<XaTransactionId>, RmError> {
+        self.guarded("end_recover", ResourceManager::end_recover)
+    }
+}
+
+#[derive(Debug)]
+struct CrashingLog {
+    inner: FileTmLog,
+    switch: CrashSwitch,
+}
+impl CrashingLog {
+    fn guarded<F>(&mut self, method: &str, f: F) -> std::io::Result<()>
+    where
+        F: FnOnce(&mut FileTmLog) -> std::io::Result<()>,
+    {
+        let crash = || std::io::Error::other("simulated crash");
+        let (execute, fail) = self.switch.step(method.to_string());
+        if !execute {
+            return Err(crash());
+        }
+        f(&mut self.inner)?;
+        if fail {
+            Err(crash())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl TmLog for CrashingLog {
+    fn log_commit(&mut self, global_tid: u64, rm_ids: &[u64]) -> std::io::Result<()> {
+        let switch = self.switch.clone();
+        self.guarded("log_commit", |log| {
+            log.log_commit(global_tid, rm_ids)?;
+            switch.lock().commit_logged = true;
+            Ok(())
+        })
+    }
+
+    fn log_end(&mut self, global_tid: u64) -> std::io::Result<()> {
+        self.guarded("log_end", |log| log.log_end(global_tid))
+    }
+
+    fn pending_commits(&self) -> std::io::Result<Vec<u64>> {
+        self.inner.pending_commits()
+    }
+
+    fn participants(&self, global_tid: u64) -> std::io::Result<Vec<u64>> {
+        self.inner.participants(global_tid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{simulate_crashes, simulate_crashes_without_log};
+    use crate::file_store::tests::test_dir;
+
+    #[test]
+    fn test_crash_points() {
+        let dir = test_dir("crash_simulation");
+        let report = simulate_crashes(dir.join("a"), 7).unwrap();
+        assert!(report.is_atomic(), "{report}");
+        // end, prepare and commit per resource manager, plus writing and ending the decision
+        assert_eq!(report.crash_points.len(), 2 * (3 * report.rm_count + 2));
+        assert!(report.crash_points.iter().any(|cp| cp.committed));
+        assert!(report.crash_points.iter().any(|cp| !cp.committed));
+
+        let again = simulate_crashes(dir.join("b"), 7).unwrap();
+        assert_eq!(report.to_string(), again.to_string());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_crash_points_without_log() {
+        let dir = test_dir("crash_simulation_without_log");
+        let report = simulate_crashes_without_log(&dir, 7).unwrap();
+        assert!(report.is_atomic(), "{report}");
+        // end, prepare and commit per resource manager
+        assert_eq!(report.crash_points.len(), 2 * 3 * report.rm_count);
+        assert!(report.crash_points.iter().any(|cp| cp.committed));
+        assert!(report.crash_points.iter().any(|cp| !cp.committed));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
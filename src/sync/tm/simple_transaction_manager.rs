@@ -1,17 +1,19 @@
 use super::{Status, TransactionManager};
-use crate::{sync::rm::ResourceManager, ReturnCode, RmError, XaError, XaTransactionId};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "audit")]
+use crate::AuditLog;
+#[cfg(feature = "metrics")]
+use crate::{metrics::TmMetrics, MetricsRecorder};
+use crate::{
+    sync::rm::ResourceManager, tm_event::Listeners, tm_log::MemoryTmLog, CompletionOutcome,
+    ErrorCode, HeuristicKind, JavaXid, PrepareVote, ReturnCode, RmError, RmMethod,
+    SimpleXidGenerator, TmEvent, TmEventListener, TmLog, XaError, XaTransactionId, XidGenerator,
+};
 use log::{debug, trace};
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
     hash::{Hash, Hasher},
-    io::Cursor,
-    u64,
 };
 
-/// The format id that is used for the `XaTransactionId`s of `SimpleTransactionManager`.
-const FORMAT_ID: i32 = 99;
-
 /// `SimpleTransactionManager`
 ///
 /// * identifies itself with a `u64` (hash of its name)
@@ -26,6 +28,9 @@ const FORMAT_ID: i32 = 99;
 /// * `global_tid`: u64 counter, starting from 0
 /// * `branch_qualifier`: `tm_id`: u64, `rm_id`: u64
 ///
+/// (see [`SimpleXid`](crate::SimpleXid)), unless another [`XidGenerator`] is used
+/// (see `with_xid_generator()`).
+///
 /// A minimal implementation of the `TransactionManager` interface.
 ///
 /// Is identified with an application-defined String, whose hash is used as
@@ -33,33 +38,195 @@ const FORMAT_ID: i32 = 99;
 ///
 /// No support is provided for multi-threading á la XA.
 ///
+/// The commit decisions are kept in a [`TmLog`], so that `recover()` can complete
+/// a commit that failed after the decision.
+/// Without a durable log (see `with_log()`), they are kept in memory, and
+/// a crash during `commit_transaction()` can leave the transaction incomplete.
+///
 #[derive(Debug)]
-pub struct SimpleTransactionManager {
+pub struct SimpleTransactionManager<G: XidGenerator = SimpleXidGenerator> {
     name: String,
+    #[cfg(feature = "audit")]
     id: u64,
-    rms: HashMap<u64, Box<dyn ResourceManager>>,
+    xid_generator: G,
+    rms: BTreeMap<u64, Box<dyn ResourceManager>>,
+    // the commit decisions; without a durable log, they are kept in memory
+    log: Box<dyn TmLog>,
+    // the resource managers whose branches of a pending commit are completed
+    resolved_rms: BTreeMap<u64, BTreeSet<u64>>,
+    // the resource managers that voted read-only on the current transaction
+    read_only: BTreeSet<u64>,
+    listeners: Listeners,
     last_gtid: u64,
     current_gtid: Option<u64>,
     status: Status,
+    #[cfg(feature = "metrics")]
+    metrics: TmMetrics,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 impl SimpleTransactionManager {
     /// Produces a new instance.
     #[must_use]
     pub fn new<S: AsRef<str>>(name: S) -> SimpleTransactionManager {
         trace!("new()");
+        let id = tm_id(name.as_ref());
+        SimpleTransactionManager::with_xid_generator(name, SimpleXidGenerator::new(id))
+    }
+
+    /// Produces a new instance that logs its commit decisions durably in the given log.
+    ///
+    /// After a restart, register the resource managers and call `recover()`
+    /// to complete the transactions that were interrupted.
+    #[must_use]
+    pub fn with_log<S: AsRef<str>, L: TmLog + 'static>(
+        name: S,
+        log: L,
+    ) -> SimpleTransactionManager {
+        SimpleTransactionManager::new(name).with_tm_log(log)
+    }
+}
+impl<G: XidGenerator> SimpleTransactionManager<G> {
+    /// Produces a new instance that uses the given generator for the ids
+    /// of the transaction branches.
+    #[must_use]
+    pub fn with_xid_generator<S: AsRef<str>>(name: S, xid_generator: G) -> Self {
         let name = name.as_ref().to_string();
-        let mut s = DefaultHasher::new();
-        name.hash(&mut s);
         SimpleTransactionManager {
+            #[cfg(feature = "audit")]
+            id: tm_id(&name),
             name,
-            id: s.finish() & (u64::max_value() - 0b_1111_1111_u64),
-            rms: HashMap::<u64, Box<dyn ResourceManager>>::new(),
+            xid_generator,
+            rms: BTreeMap::<u64, Box<dyn ResourceManager>>::new(),
+            log: Box::new(MemoryTmLog::default()),
+            resolved_rms: BTreeMap::new(),
+            read_only: BTreeSet::new(),
+            listeners: Listeners::default(),
             last_gtid: 0,
             current_gtid: None,
             status: Status::IDLE,
+            #[cfg(feature = "metrics")]
+            metrics: TmMetrics::default(),
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::none(),
+        }
+    }
+
+    /// Logs the commit decisions durably in the given log, like `with_log()`.
+    #[must_use]
+    pub fn with_tm_log<L: TmLog + 'static>(mut self, log: L) -> Self {
+        self.log = Box::new(log);
+        self
+    }
+
+    /// Reports the transaction counters and the number of in-doubt transactions
+    /// to the given recorder.
+    ///
+    /// Wrap the resource managers into `MeteredRm` to get also the metrics of the XA calls.
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    #[must_use]
+    pub fn with_metrics<M: MetricsRecorder + 'static>(mut self, recorder: M) -> Self {
+        self.metrics.set_recorder(Box::new(recorder));
+        self
+    }
+
+    /// Records the commit and rollback decisions in the given audit log.
+    ///
+    /// If the decision to commit cannot be recorded, the transaction is rolled back.
+    #[cfg(feature = "audit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "audit")))]
+    #[must_use]
+    pub fn with_audit_log(mut self, mut audit_log: AuditLog) -> Self {
+        audit_log.set_tm_id(self.id);
+        self.listeners.set_audit_log(audit_log);
+        self
+    }
+
+    /// Registers a listener that is informed about the events of the global transactions.
+    pub fn add_listener<L: TmEventListener + 'static>(&mut self, listener: L) {
+        self.listeners.add(Box::new(listener));
+    }
+
+    /// Completes the transactions that were interrupted by a crash.
+    ///
+    /// Each registered resource manager is asked for its prepared branches of this
+    /// transaction manager. Branches of transactions with a logged commit decision
+    /// are committed, all others are rolled back.
+    /// Branches of Java transaction managers (see [`JavaXid`]) are never touched.
+    /// A decision is removed from the log when the branches of all its resource managers
+    /// are completed, here or by a later `register()` with cleanup.
+    ///
+    /// # Errors
+    ///
+    /// `XaError::Log` if the log cannot be read or written,
+    /// `XaError::RmErrors` if resource managers failed.
+    pub fn recover(&mut self) -> Result<(), XaError> {
+        trace!("recover()");
+        self.validate_and_set_status(
+            Status::IDLE | Status::COMMITTED | Status::ROLLEDBACK,
+            Status::IDLE,
+        )?;
+        let pending = self.pending_commits()?;
+
+        let mut errors = Vec::<RmError>::new();
+        let mut resolved = Vec::new();
+        for (rm_id, rm) in &mut self.rms {
+            match resolve_branches(
+                &mut **rm,
+                &self.xid_generator,
+                *rm_id,
+                &pending,
+                &mut self.listeners,
+            ) {
+                Ok(max_gtid) => {
+                    self.last_gtid = self.last_gtid.max(max_gtid);
+                    resolved.push(*rm_id);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+        for rm_id in resolved {
+            self.mark_resolved(rm_id)?;
+        }
+        #[cfg(feature = "metrics")]
+        {
+            let in_doubt = self.pending_commits()?;
+            self.metrics.set_in_doubt(&in_doubt);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(XaError::RmErrors(errors))
         }
     }
 
+    // Records that the branches of the pending commits at the resource manager are completed,
+    // and ends the decisions whose resource managers are all completed.
+    fn mark_resolved(&mut self, rm_id: u64) -> Result<(), XaError> {
+        let log = &mut self.log;
+        for global_tid in log.pending_commits().map_err(log_error)? {
+            let resolved = self.resolved_rms.entry(global_tid).or_default();
+            resolved.insert(rm_id);
+            let participants = log.participants(global_tid).map_err(log_error)?;
+            if participants.iter().all(|rm_id| resolved.contains(rm_id)) {
+                trace!("recovery: all branches of {global_tid} are completed");
+                log.log_end(global_tid).map_err(log_error)?;
+                self.resolved_rms.remove(&global_tid);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn pending_commits(&mut self) -> Result<Vec<u64>, XaError> {
+        let pending = self.log.pending_commits().map_err(log_error)?;
+        if let Some(max) = pending.iter().max() {
+            self.last_gtid = self.last_gtid.max(*max);
+        }
+        Ok(pending)
+    }
+
     /// Returns the global transaction id that is currently used
     /// by this `SimpleTransactionManager`.
     pub fn get_gtid(&mut self) -> Option<u64> {
@@ -96,15 +263,45 @@ impl SimpleTransactionManager {
         &self.name
     }
 
-    fn rm_action<F>(&mut self, action: F, global_tid: u64) -> Result<(), XaError>
+    // Calls the action on the branches of the resource managers, and reports the results
+    // to the listeners. The failed calls and the results that `check` rejects are collected.
+    // Read-only voters are skipped, their branches are already completed.
+    fn rm_action<T, F, C>(
+        &mut self,
+        method: RmMethod,
+        action: F,
+        mut check: C,
+        global_tid: u64,
+    ) -> Result<(), XaError>
     where
-        F: Fn(&mut Box<dyn ResourceManager>, &XaTransactionId) -> Result<ReturnCode, RmError>,
+        T: Clone + Into<ReturnCode>,
+        F: Fn(&mut Box<dyn ResourceManager>, &XaTransactionId) -> Result<T, RmError>,
+        C: FnMut(u64, T) -> Result<(), &'static str>,
     {
         let mut errors = Vec::<RmError>::new();
         for (rm_id, rm) in &mut self.rms {
-            let xatid = new_xatid(global_tid, self.id, *rm_id);
-            if let Err(e) = action(rm, &xatid) {
-                errors.push(e);
+            if self.read_only.contains(rm_id) {
+                continue;
+            }
+            let xatid = self.xid_generator.branch_xid(global_tid, *rm_id);
+            let result = action(rm, &xatid);
+            self.listeners
+                .branch_result(method, global_tid, *rm_id, &result);
+            match result {
+                Ok(outcome) => {
+                    let rc: ReturnCode = outcome.clone().into();
+                    if let Err(what) = check(*rm_id, outcome) {
+                        errors.push(
+                            RmError::new(
+                                ErrorCode::RmError,
+                                format!("transaction branch {what} on {method:?} ({rc})"),
+                            )
+                            .with_rm_id(*rm_id)
+                            .with_xid(xatid),
+                        );
+                    }
+                }
+                Err(e) => errors.push(e.with_rm_id(*rm_id).with_xid(xatid)),
             }
         }
         if errors.is_empty() {
@@ -115,7 +312,13 @@ impl SimpleTransactionManager {
     }
 
     fn rm_start(&mut self, global_tid: u64) -> Result<(), XaError> {
-        self.rm_action(|rm, xatid| ((**rm).start(xatid)), global_tid)
+        self.read_only.clear();
+        self.rm_action(
+            RmMethod::Start,
+            |rm, xatid| (**rm).start(xatid),
+            accept,
+            global_tid,
+        )
     }
 
     // fn rm_join(&mut self, global_tid: &u64) -> Result<(),XaError> {
@@ -131,27 +334,67 @@ impl SimpleTransactionManager {
     // }
 
     fn rm_end_success(&mut self, global_tid: u64) -> Result<(), XaError> {
-        self.rm_action(|rm, xatid| ((**rm).end_success(xatid)), global_tid)
+        self.rm_action(
+            RmMethod::EndSuccess,
+            |rm, xatid| (**rm).end_success(xatid),
+            accept,
+            global_tid,
+        )
     }
 
     fn rm_end_failure(&mut self, global_tid: u64) -> Result<(), XaError> {
-        self.rm_action(|rm, xatid| ((**rm).end_failure(xatid)), global_tid)
+        self.rm_action(
+            RmMethod::EndFailure,
+            |rm, xatid| (**rm).end_failure(xatid),
+            accept,
+            global_tid,
+        )
     }
 
     fn rm_prepare(&mut self, global_tid: u64) -> Result<(), XaError> {
-        self.rm_action(|rm, xatid| ((**rm).prepare(xatid)), global_tid)
+        let mut read_only = BTreeSet::new();
+        let result = self.rm_action(
+            RmMethod::Prepare,
+            |rm, xatid| (**rm).prepare_vote(xatid),
+            |rm_id, vote| match vote {
+                PrepareVote::Ok => Ok(()),
+                PrepareVote::ReadOnly => {
+                    read_only.insert(rm_id);
+                    Ok(())
+                }
+                PrepareVote::Rollback(_) => Err("was rolled back"),
+            },
+            global_tid,
+        );
+        self.read_only = read_only;
+        result
     }
 
     fn rm_commit(&mut self, global_tid: u64) -> Result<(), XaError> {
-        self.rm_action(|rm, xatid| ((**rm).commit(xatid)), global_tid)
+        self.rm_action(
+            RmMethod::Commit,
+            |rm, xatid| (**rm).commit_outcome(xatid),
+            check_commit,
+            global_tid,
+        )
     }
 
     fn rm_commit_one_phase(&mut self, global_tid: u64) -> Result<(), XaError> {
-        self.rm_action(|rm, xatid| ((**rm).commit_one_phase(xatid)), global_tid)
+        self.rm_action(
+            RmMethod::CommitOnePhase,
+            |rm, xatid| (**rm).commit_one_phase_outcome(xatid),
+            check_commit,
+            global_tid,
+        )
     }
 
     fn rm_rollback(&mut self, global_tid: u64) -> Result<(), XaError> {
-        self.rm_action(|rm, xatid| ((**rm).rollback(xatid)), global_tid)
+        self.rm_action(
+            RmMethod::Rollback,
+            |rm, xatid| (**rm).rollback_outcome(xatid),
+            accept,
+            global_tid,
+        )
     }
 
     // fn rm_forget(&mut self, global_tid: &u64) -> Result<(),XaError> {
@@ -162,108 +405,148 @@ impl SimpleTransactionManager {
     //     panic!("not yet implemented")
     // }
 
+    // Records the outcome of the global transaction in the audit log,
+    // and informs the listeners about it.
+    fn decide(&mut self, global_tid: u64, commit: bool) -> Result<(), XaError> {
+        self.listeners.decide(global_tid, commit).map_err(log_error)
+    }
+
+    // Rolls back all branches after a failed step, and returns the error to report.
     fn try_rollback_after(
         &mut self,
         current_gtid: u64,
         method: &'static str,
-    ) -> Result<(), XaError> {
+        error: XaError,
+    ) -> XaError {
+        trace_error(&error, current_gtid, method);
+        if let Err(e) = self.decide(current_gtid, false) {
+            debug!("recording the rollback of {current_gtid} failed with {e:?}");
+        }
         self.status = Status::ROLLINGBACK;
         let result = self.rm_rollback(current_gtid);
-        if let Err(ref e) = result {
-            trace_error(e, current_gtid, "error in rm_rollback");
-        }
-        self.status = Status::IDLE;
+        self.status = Status::ROLLEDBACK;
+        #[cfg(feature = "metrics")]
+        self.metrics.rolled_back();
         match result {
-            Err(e) => {
-                if let XaError::RmErrors(v) = e {
-                    Err(XaError::Inconsistency(
-                        format!("rm_rollback() failed after a failed {method}()"),
-                        v,
-                    ))
-                } else {
-                    Err(e)
-                }
+            Ok(()) => error,
+            Err(XaError::RmErrors(v)) => {
+                trace!("rm_rollback() failed after a failed {method}()");
+                XaError::Inconsistency(format!("rm_rollback() failed after a failed {method}()"), v)
             }
-            Ok(()) => Ok(()),
+            Err(e) => e,
         }
     }
+}
 
-    // fn is_my_xid(&self, xid: &XaTransactionId) -> bool {
-    //     if xid.get_format_id() != FORMAT_ID {
-    //         return false;
-    //     }
-
-    //     let bq: Vec<u8> = xid.get_branch_qualifier();
-    //     if bq.len() != 16 {
-    //         return false;
-    //     };
-    //     let mut rdr = Cursor::new(bq);
-
-    //     rdr.read_u64::<LittleEndian>().unwrap() == self.id
-    // }
-
-    fn is_my_xid_and_rm(&self, xid: &XaTransactionId, rm_id: u64) -> bool {
-        if xid.get_format_id() != FORMAT_ID {
-            return false;
+// Commits the prepared branches of the given TM at the given RM if their commit is pending,
+// and rolls back the others.
+// Returns the highest global transaction id that was found.
+fn resolve_branches(
+    rm: &mut dyn ResourceManager,
+    xid_generator: &dyn XidGenerator,
+    rm_id: u64,
+    pending: &[u64],
+    listeners: &mut Listeners,
+) -> Result<u64, RmError> {
+    let mut max_gtid = 0;
+    let mut result = Ok(());
+    for xid in rm.recover().map_err(|e| e.with_rm_id(rm_id))? {
+        if let Some(java_xid) = JavaXid::decode(&xid) {
+            trace!("recovery: skipping branch of {java_xid}");
+            continue;
         }
-
-        let bq = xid.get_branch_qualifier();
-        if bq.len() != 16 {
-            return false;
+        let Some(global_tid) = xid_generator
+            .decode(&xid)
+            .filter(|(_, xid_rm_id)| *xid_rm_id == rm_id)
+            .map(|(global_tid, _)| global_tid)
+        else {
+            continue;
         };
-        let mut rdr = Cursor::new(bq);
-
-        if rdr.read_u64::<LittleEndian>().unwrap() != self.id {
-            return false;
+        max_gtid = max_gtid.max(global_tid);
+        let commit = pending.contains(&global_tid);
+        let outcome = if commit {
+            trace!("recovery: committing {xid:?}");
+            rm.commit(&xid)
+        } else {
+            trace!("recovery: rolling back {xid:?}");
+            rm.rollback(&xid)
+        };
+        listeners.recovery_resolved(global_tid, rm_id, commit, &outcome);
+        match outcome {
+            Ok(ReturnCode::Ok) => {}
+            Ok(rc) => debug!("recovery of {xid:?} returned {rc:?}"),
+            Err(e) => result = Err(e.with_rm_id(rm_id).with_xid(xid)),
         }
-        rdr.read_u64::<LittleEndian>().unwrap() == rm_id
     }
+    result.map(|()| max_gtid)
+}
+
+// Accepts every result of a call.
+#[allow(clippy::unnecessary_wraps)]
+fn accept<T>(_rm_id: u64, _outcome: T) -> Result<(), &'static str> {
+    Ok(())
+}
+
+// Accepts a branch that was committed, if need be heuristically.
+fn check_commit(_rm_id: u64, outcome: CompletionOutcome) -> Result<(), &'static str> {
+    match outcome {
+        CompletionOutcome::Done | CompletionOutcome::Heuristic(HeuristicKind::Committed) => Ok(()),
+        CompletionOutcome::RolledBack(_) => Err("was rolled back"),
+        CompletionOutcome::Heuristic(_) => Err("was completed heuristically"),
+    }
+}
+
+// The id of a transaction manager with the given name.
+fn tm_id(name: &str) -> u64 {
+    let mut s = DefaultHasher::new();
+    name.hash(&mut s);
+    s.finish() & (u64::MAX - 0b_1111_1111_u64)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn log_error(e: std::io::Error) -> XaError {
+    XaError::Log(e.to_string())
 }
 
 fn trace_error(e: &XaError, gtid: u64, method_name: &'static str) {
     if let XaError::RmErrors(ref vec_rmerr) = *e {
         for rm in vec_rmerr {
-            trace!("{}({}) failed due to {:?}", method_name, gtid, rm);
+            trace!("{method_name}({gtid}) failed due to {rm:?}");
         }
     } else {
-        trace!("error in {}: {}", method_name, e);
+        trace!("error in {method_name}: {e}");
     }
 }
 
-#[allow(clippy::similar_names)]
-fn new_xatid(global_tid: u64, tm_id: u64, rm_id: u64) -> XaTransactionId {
-    let mut v_gt = Vec::<u8>::with_capacity(64);
-    v_gt.write_u64::<LittleEndian>(global_tid).unwrap();
-
-    let mut v_bq = Vec::<u8>::with_capacity(128);
-    v_bq.write_u64::<LittleEndian>(tm_id).unwrap();
-    v_bq.write_u64::<LittleEndian>(rm_id).unwrap();
-
-    XaTransactionId::try_new(FORMAT_ID, v_gt, v_bq).unwrap()
-}
-
-impl TransactionManager for SimpleTransactionManager {
+impl<G: XidGenerator> TransactionManager for SimpleTransactionManager<G> {
     fn register(
         &mut self,
         mut rm: Box<dyn ResourceManager>,
         rm_id: u64,
         cleanup: bool,
     ) -> Result<(), XaError> {
-        trace!("register(rm_id = {})", rm_id);
-        if self.rms.get(&rm_id).is_some() {
+        trace!("register(rm_id = {rm_id})");
+        if self.rms.contains_key(&rm_id) {
             let errmsg = "cannot register with given rm_id, which is already in use";
-            debug!("{}", errmsg);
+            debug!("{errmsg}");
             return Err(XaError::Usage(errmsg));
         }
 
         if cleanup {
-            trace!("register(rm_id = {}) -> starting cleanup", rm_id);
-            for xid in &(*rm).recover().unwrap_or_default() {
-                trace!("found xid {:?}", xid);
-                if self.is_my_xid_and_rm(xid, rm_id) {
-                    trace!("trying to forget {:?}", xid);
-                    (*rm).forget(xid).unwrap_or(ReturnCode::Ok);
+            trace!("register(rm_id = {rm_id}) -> starting cleanup");
+            let pending = self.pending_commits()?;
+            match resolve_branches(
+                &mut *rm,
+                &self.xid_generator,
+                rm_id,
+                &pending,
+                &mut self.listeners,
+            ) {
+                Ok(max_gtid) => {
+                    self.last_gtid = self.last_gtid.max(max_gtid);
+                    self.mark_resolved(rm_id)?;
                 }
+                Err(e) => debug!("cleanup of rm {rm_id} failed with {e:?}"),
             }
         }
 
@@ -290,56 +573,49 @@ impl TransactionManager for SimpleTransactionManager {
         )?;
 
         let global_tid = self.next_global_tid();
+        #[cfg(feature = "tracing")]
+        let _entered = {
+            self.span = crate::xa_span::global_transaction(&self.name, global_tid);
+            self.span.clone().entered()
+        };
 
-        trace!("start_transaction() -> rm_start({})", global_tid);
+        self.listeners
+            .notify(&TmEvent::TransactionStarted { global_tid });
+        trace!("start_transaction() -> rm_start({global_tid})");
         match self.rm_start(global_tid) {
             Ok(()) => {
                 self.current_gtid = Some(global_tid);
                 self.status = Status::ACTIVE;
+                #[cfg(feature = "metrics")]
+                self.metrics.started();
                 return Ok(());
             }
             Err(e) => {
-                trace!(
-                    "start_transaction() -> rm_start({}) failed with {:?}",
-                    global_tid,
-                    e
-                );
+                trace!("start_transaction() -> rm_start({global_tid}) failed with {e:?}");
 
-                trace!("start_transaction() -> rm_end_failure({})", global_tid);
+                trace!("start_transaction() -> rm_end_failure({global_tid})");
                 if let Err(XaError::RmErrors(v)) = self.rm_end_failure(global_tid) {
-                    trace!(
-                        "start_transaction() -> rm_end_failure({}) failed with {:?}",
-                        global_tid,
-                        v
-                    );
+                    trace!("start_transaction() -> rm_end_failure({global_tid}) failed with {v:?}");
                 }
 
-                trace!("start_transaction() -> rm_rollback({})", global_tid);
+                trace!("start_transaction() -> rm_rollback({global_tid})");
                 if let Err(XaError::RmErrors(v)) = self.rm_rollback(global_tid) {
-                    trace!(
-                        "start_transaction() -> rm_rollback({}) failed with {:?}",
-                        global_tid,
-                        v
-                    );
+                    trace!("start_transaction() -> rm_rollback({global_tid}) failed with {v:?}");
                 }
             }
         }
 
-        trace!(
-            "start_transaction() -> rm_start({}), second attempt after cleanup",
-            global_tid
-        );
+        trace!("start_transaction() -> rm_start({global_tid}), second attempt after cleanup");
         match self.rm_start(global_tid) {
             Ok(()) => {
                 self.current_gtid = Some(global_tid);
                 self.status = Status::ACTIVE;
+                #[cfg(feature = "metrics")]
+                self.metrics.started();
                 Ok(())
             }
             Err(e) => {
-                trace!(
-                    "start_transaction() -> rm_start({}), second attempt failed, too",
-                    global_tid
-                );
+                trace!("start_transaction() -> rm_start({global_tid}), second attempt failed, too");
                 self.status = Status::IDLE;
                 Err(e)
             }
@@ -354,52 +630,117 @@ impl TransactionManager for SimpleTransactionManager {
     // otherwise to `TmStatus::Failed` or `TmStatus::RolledBack`.
     fn commit_transaction(&mut self) -> Result<(), XaError> {
         trace!("commit()");
+        #[cfg(feature = "tracing")]
+        let _entered = self.span.clone().entered();
         let current_gtid = self.get_current_gtid()?;
         self.validate_and_set_status(Status::ACTIVE, Status::COMMITTING)?;
 
         // shortcut, if possible
         if self.rms.len() < 2 {
+            // the branch must be ended before it can be committed
+            trace!("commit() -> rm_end_success()");
+            if let Err(e) = self.rm_end_success(current_gtid) {
+                return Err(self.try_rollback_after(current_gtid, "rm_end_success", e));
+            }
             trace!("commit() -> rm_commit_one_phase()");
-            self.rm_commit_one_phase(current_gtid)?;
+            let result = self.rm_commit_one_phase(current_gtid);
+            // the resource manager decides the outcome, it can only be recorded afterwards
+            if let Err(e) = self.listeners.decided(current_gtid, result.is_ok()) {
+                debug!("recording the outcome of {current_gtid} failed with {e:?}");
+            }
+            if let Err(e) = result {
+                trace_error(&e, current_gtid, "rm_commit_one_phase");
+                // a failed one-phase commit leaves nothing to roll back
+                self.status = Status::ROLLEDBACK;
+                #[cfg(feature = "metrics")]
+                self.metrics.rolled_back();
+                return Err(e);
+            }
         } else {
             // 1. end_success()
             trace!("commit() -> rm_end_success()");
             if let Err(e) = self.rm_end_success(current_gtid) {
-                trace_error(&e, current_gtid, "rm_end_success");
-                self.try_rollback_after(current_gtid, "rm_end_success")?;
+                return Err(self.try_rollback_after(current_gtid, "rm_end_success", e));
             }
 
             // 2. prepare()
             trace!("commit() -> rm_prepare()");
+            self.status = Status::PREPARING;
             if let Err(e) = self.rm_prepare(current_gtid) {
-                trace_error(&e, current_gtid, "rm_prepare");
-                self.try_rollback_after(current_gtid, "rm_prepare")?;
+                return Err(self.try_rollback_after(current_gtid, "rm_prepare", e));
             }
+            self.status = Status::PREPARED;
 
-            // 3. commit()
+            // 3. the decision to commit must be durable before the first branch is committed;
+            // it is audited first, so that a failed audit leaves nothing to undo
+            if let Err(e) = self.decide(current_gtid, true) {
+                return Err(self.try_rollback_after(current_gtid, "decide", e));
+            }
+            trace!("commit() -> log_commit()");
+            let rm_ids: Vec<u64> = self
+                .rms
+                .keys()
+                .filter(|rm_id| !self.read_only.contains(rm_id))
+                .copied()
+                .collect();
+            if let Err(e) = self.log.log_commit(current_gtid, &rm_ids) {
+                return Err(self.try_rollback_after(current_gtid, "log_commit", log_error(e)));
+            }
+
+            // 4. commit()
             trace!("commit() -> rm_commit()");
+            self.status = Status::COMMITTING;
             if let Err(e) = self.rm_commit(current_gtid) {
                 trace_error(&e, current_gtid, "rm_commit");
-                self.try_rollback_after(current_gtid, "rm_commit")?;
+                #[cfg(feature = "metrics")]
+                self.metrics.add_in_doubt(&[current_gtid]);
+                // the decision is logged; recover() completes the commit
+                self.status = Status::IDLE;
+                return Err(match e {
+                    XaError::RmErrors(v) => XaError::Inconsistency(
+                        "rm_commit() failed after a successful rm_prepare()".to_string(),
+                        v,
+                    ),
+                    e => e,
+                });
+            }
+            trace!("commit() -> log_end()");
+            if let Err(e) = self.log.log_end(current_gtid) {
+                // harmless, the next recovery finds nothing to commit
+                debug!("log_end() failed with {e:?}");
             }
         }
         self.status = Status::COMMITTED;
+        #[cfg(feature = "metrics")]
+        self.metrics.committed();
 
         Ok(())
     }
 
     fn rollback_transaction(&mut self) -> Result<(), XaError> {
         trace!("rollback()");
+        #[cfg(feature = "tracing")]
+        let _entered = self.span.clone().entered();
         let current_gtid = self.get_current_gtid()?;
         match self.status {
             Status::ACTIVE => {
                 trace!("rollback() ACTIVE -> rm_end_failure()");
+                if let Err(e) = self.decide(current_gtid, false) {
+                    debug!("recording the rollback of {current_gtid} failed with {e:?}");
+                }
                 self.rm_end_failure(current_gtid)?;
                 self.rm_rollback(current_gtid)?;
+                #[cfg(feature = "metrics")]
+                self.metrics.rolled_back();
             }
             Status::PREPARED | Status::ROLLBACK_ONLY => {
                 trace!("rollback() PREPARED or ROLLBACK_ONLY -> rm_rollback()");
+                if let Err(e) = self.decide(current_gtid, false) {
+                    debug!("recording the rollback of {current_gtid} failed with {e:?}");
+                }
                 self.rm_rollback(current_gtid)?;
+                #[cfg(feature = "metrics")]
+                self.metrics.rolled_back();
             }
             _ => {}
         }
@@ -419,7 +760,7 @@ impl TransactionManager for SimpleTransactionManager {
     }
 }
 
-impl Drop for SimpleTransactionManager {
+impl<G: XidGenerator> Drop for SimpleTransactionManager<G> {
     fn drop(&mut self) {
         trace!("Drop of SimpleTransactionManager");
         if (Status::ACTIVATING
@@ -435,3 +776,386 @@ impl Drop for SimpleTransactionManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SimpleTransactionManager, Status};
+    use crate::{
+        sync::{
+            rm::{MockResourceManager, StateValidator},
+            tm::TransactionManager,
+        },
+        CallLog, ErrorCode, ReturnCode, RmError, RmMethod, SimpleXid, TmEvent, ValidationMode,
+        XaError, XaTransactionId, XidGenerator,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_one_phase_commit() {
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::new("test_one_phase_commit");
+        tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+            .unwrap();
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+        log.assert_methods(&[
+            RmMethod::Start,
+            RmMethod::EndSuccess,
+            RmMethod::CommitOnePhase,
+        ]);
+    }
+
+    #[test]
+    fn test_two_phase_commit() {
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::new("test_two_phase_commit");
+        for rm_id in 1..=2 {
+            tm.register(Box::new(MockResourceManager::new(&log)), rm_id, false)
+                .unwrap();
+        }
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+
+        let methods = log.methods();
+        assert_eq!(methods.len(), 8);
+        for (phase, method) in [
+            RmMethod::Start,
+            RmMethod::EndSuccess,
+            RmMethod::Prepare,
+            RmMethod::Commit,
+        ]
+        .iter()
+        .enumerate()
+        {
+            assert_eq!(&methods[2 * phase..2 * phase + 2], &[*method, *method]);
+        }
+        log.assert_called_before(RmMethod::EndSuccess, RmMethod::Prepare);
+        log.assert_called_before(RmMethod::Prepare, RmMethod::Commit);
+    }
+
+    #[test]
+    fn test_calls_follow_state_table() {
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::new("test_calls_follow_state_table");
+        for rm_id in 1..=2 {
+            let rm = StateValidator::new(MockResourceManager::new(&log), ValidationMode::Reject);
+            tm.register(Box::new(rm), rm_id, false).unwrap();
+        }
+        for _ in 0..2 {
+            tm.start_transaction().unwrap();
+            tm.commit_transaction().unwrap();
+            tm.start_transaction().unwrap();
+            tm.rollback_transaction().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_failed_prepare_rolls_back() {
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::new("test_failed_prepare_rolls_back");
+        tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+            .unwrap();
+        let rm = MockResourceManager::new(&log)
+            .returning(RmMethod::Prepare, Ok(ReturnCode::RollbackDeadlock));
+        tm.register(Box::new(rm), 2, false).unwrap();
+
+        tm.start_transaction().unwrap();
+        assert!(tm.commit_transaction().is_err());
+        assert_eq!(tm.get_status().unwrap(), Status::ROLLEDBACK);
+        log.assert_not_called(RmMethod::Commit);
+        log.assert_no_calls_after(RmMethod::Rollback);
+    }
+
+    #[test]
+    fn test_failed_one_phase_commit() {
+        let log = CallLog::new();
+        let rm = MockResourceManager::new(&log).returning(
+            RmMethod::EndSuccess,
+            Err(RmError::new(ErrorCode::RmFailure, "gone".to_string())),
+        );
+        let mut tm = SimpleTransactionManager::new("test_failed_one_phase_commit");
+        tm.register(Box::new(rm), 1, false).unwrap();
+        tm.start_transaction().unwrap();
+        assert!(tm.commit_transaction().is_err());
+        assert_eq!(tm.get_status().unwrap(), Status::ROLLEDBACK);
+        log.assert_not_called(RmMethod::CommitOnePhase);
+        log.assert_no_calls_after(RmMethod::Rollback);
+
+        let log = CallLog::new();
+        let rm = MockResourceManager::new(&log)
+            .returning(RmMethod::CommitOnePhase, Ok(ReturnCode::RollbackDeadlock));
+        let mut tm = SimpleTransactionManager::new("test_failed_one_phase_commit");
+        tm.register(Box::new(rm), 1, false).unwrap();
+        tm.start_transaction().unwrap();
+        assert!(tm.commit_transaction().is_err());
+        assert_eq!(tm.get_status().unwrap(), Status::ROLLEDBACK);
+        // the transaction manager is ready for the next transaction
+        tm.start_transaction().unwrap();
+    }
+
+    #[test]
+    fn test_events() {
+        let events = Arc::new(Mutex::new(Vec::<String>::new()));
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::new("test_events");
+        let events2 = Arc::clone(&events);
+        tm.add_listener(move |event: &TmEvent| {
+            let s = match event {
+                TmEvent::TransactionStarted { global_tid } => format!("started {global_tid}"),
+                TmEvent::BranchStarted { rm_id, .. } => format!("branch {rm_id}"),
+                TmEvent::PrepareVote { rm_id, vote, .. } => format!("vote {rm_id} {vote:?}"),
+                TmEvent::DecisionMade { commit, .. } => format!("commit {commit}"),
+                TmEvent::BranchCompleted {
+                    rm_id, committed, ..
+                } => format!("completed {rm_id} {committed}"),
+                TmEvent::Heuristic { rm_id, .. } => format!("heuristic {rm_id}"),
+                TmEvent::RecoveryResolved { rm_id, .. } => format!("resolved {rm_id}"),
+            };
+            events2.lock().unwrap().push(s);
+        });
+        tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+            .unwrap();
+        let rm = MockResourceManager::new(&log)
+            .returning(RmMethod::Commit, Ok(ReturnCode::HeuristicallyCommitted));
+        tm.register(Box::new(rm), 2, false).unwrap();
+
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "started 1",
+                "branch 1",
+                "branch 2",
+                "vote 1 Ok(Ok)",
+                "vote 2 Ok(Ok)",
+                "commit true",
+                "completed 1 true",
+                "completed 2 true",
+                "heuristic 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rollback() {
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::new("test_rollback");
+        tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+            .unwrap();
+        tm.start_transaction().unwrap();
+        tm.rollback_transaction().unwrap();
+        log.assert_methods(&[RmMethod::Start, RmMethod::EndFailure, RmMethod::Rollback]);
+        log.assert_no_calls_after(RmMethod::Rollback);
+    }
+
+    #[test]
+    fn test_start_is_retried_after_cleanup() {
+        let log = CallLog::new();
+        let rm = MockResourceManager::new(&log).returning(
+            RmMethod::Start,
+            Err(RmError::new(ErrorCode::RmFailure, "busy".to_string())),
+        );
+        let mut tm = SimpleTransactionManager::new("test_start_is_retried_after_cleanup");
+        tm.register(Box::new(rm), 1, false).unwrap();
+        tm.start_transaction().unwrap();
+        log.assert_methods(&[
+            RmMethod::Start,
+            RmMethod::EndFailure,
+            RmMethod::Rollback,
+            RmMethod::Start,
+        ]);
+    }
+
+    #[test]
+    fn test_return_codes_are_checked() {
+        // a rollback on a one-phase commit is no success
+        let log = CallLog::new();
+        let rm = MockResourceManager::new(&log)
+            .returning(RmMethod::CommitOnePhase, Ok(ReturnCode::RollbackDeadlock));
+        let mut tm = SimpleTransactionManager::new("test_return_codes_are_checked");
+        tm.register(Box::new(rm), 1, false).unwrap();
+        tm.start_transaction().unwrap();
+        assert!(tm.commit_transaction().is_err());
+
+        // a heuristic outcome is no valid vote
+        let log = CallLog::new();
+        let rm = MockResourceManager::new(&log)
+            .returning(RmMethod::Prepare, Ok(ReturnCode::HeuristicallyCommitted));
+        let mut tm = SimpleTransactionManager::new("test_return_codes_are_checked");
+        tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+            .unwrap();
+        tm.register(Box::new(rm), 2, false).unwrap();
+        tm.start_transaction().unwrap();
+        assert!(tm.commit_transaction().is_err());
+        assert_eq!(tm.get_status().unwrap(), Status::ROLLEDBACK);
+        log.assert_not_called(RmMethod::Commit);
+    }
+
+    #[test]
+    fn test_typed_results() {
+        // a read-only voter takes no part in phase two
+        let log1 = CallLog::new();
+        let log2 = CallLog::new();
+        let rm1 = MockResourceManager::new(&log1)
+            .returning(RmMethod::Prepare, Ok(ReturnCode::ReadOnlyCommitted));
+        let mut tm = SimpleTransactionManager::new("test_typed_results");
+        tm.register(Box::new(rm1), 1, false).unwrap();
+        tm.register(Box::new(MockResourceManager::new(&log2)), 2, false)
+            .unwrap();
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+        log1.assert_no_calls_after(RmMethod::Prepare);
+        log2.assert_called_before(RmMethod::Prepare, RmMethod::Commit);
+
+        // nor in the rollback
+        let log1 = CallLog::new();
+        let log2 = CallLog::new();
+        let rm1 = MockResourceManager::new(&log1)
+            .returning(RmMethod::Prepare, Ok(ReturnCode::ReadOnlyCommitted));
+        let rm2 = MockResourceManager::new(&log2)
+            .returning(RmMethod::Prepare, Ok(ReturnCode::RollbackDeadlock));
+        let mut tm = SimpleTransactionManager::new("test_typed_results");
+        tm.register(Box::new(rm1), 1, false).unwrap();
+        tm.register(Box::new(rm2), 2, false).unwrap();
+        tm.start_transaction().unwrap();
+        assert!(tm.commit_transaction().is_err());
+        log1.assert_no_calls_after(RmMethod::Prepare);
+        log2.assert_called_before(RmMethod::Prepare, RmMethod::Rollback);
+
+        // of the heuristic outcomes, only a heuristic commit counts as committed
+        for (rc, committed) in [
+            (ReturnCode::HeuristicallyCommitted, true),
+            (ReturnCode::HeuristicallyRolledBack, false),
+            (ReturnCode::HeuristicallyMessedUp, false),
+            (ReturnCode::HeuristicallyCompleted, false),
+        ] {
+            let log = CallLog::new();
+            let rm = MockResourceManager::new(&log).returning(RmMethod::Commit, Ok(rc));
+            let mut tm = SimpleTransactionManager::new("test_typed_results");
+            tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+                .unwrap();
+            tm.register(Box::new(rm), 2, false).unwrap();
+            tm.start_transaction().unwrap();
+            assert_eq!(tm.commit_transaction().is_ok(), committed);
+        }
+    }
+
+    #[test]
+    fn test_errors_carry_context() {
+        let log = CallLog::new();
+        let rm = MockResourceManager::new(&log).returning(
+            RmMethod::Prepare,
+            Err(RmError::new(ErrorCode::RmFailure, "gone".to_string())),
+        );
+        let mut tm = SimpleTransactionManager::new("test_errors_carry_context");
+        tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+            .unwrap();
+        tm.register(Box::new(rm), 2, false).unwrap();
+        tm.start_transaction().unwrap();
+        match tm.commit_transaction() {
+            Err(XaError::RmErrors(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].rm_id(), Some(2));
+                let parts = SimpleXid::decode(errors[0].xid().unwrap()).unwrap();
+                assert_eq!((parts.global_tid, parts.rm_id), (1, 2));
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    // A sloppy generator that ignores the format id.
+    #[derive(Debug)]
+    struct TestXids;
+    impl XidGenerator for TestXids {
+        fn branch_xid(&self, global_tid: u64, rm_id: u64) -> XaTransactionId {
+            XaTransactionId::try_new(7, global_tid.to_be_bytes(), rm_id.to_be_bytes()).unwrap()
+        }
+
+        fn decode(&self, xid: &XaTransactionId) -> Option<(u64, u64)> {
+            Some((
+                u64::from_be_bytes(xid.get_global_tid().try_into().ok()?),
+                u64::from_be_bytes(xid.get_branch_qualifier().try_into().ok()?),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_xid_generator() {
+        let log = CallLog::new();
+        let foreign = SimpleXid {
+            global_tid: 9,
+            tm_id: 256,
+            rm_id: 1,
+        }
+        .encode();
+        let atomikos =
+            XaTransactionId::try_new(0x4154_4F4D, "node.tm4", 1_u64.to_be_bytes()).unwrap();
+        let rm = MockResourceManager::new(&log).recovering(vec![
+            TestXids.branch_xid(4, 1),
+            TestXids.branch_xid(5, 2),
+            foreign,
+            atomikos,
+        ]);
+        let mut tm = SimpleTransactionManager::with_xid_generator("test_xid_generator", TestXids);
+        tm.register(Box::new(rm), 1, true).unwrap();
+        // only the own branch of this rm is rolled back, never that of a Java TM
+        let rollbacks: Vec<_> = log
+            .calls()
+            .into_iter()
+            .filter(|call| call.method == RmMethod::Rollback)
+            .map(|call| call.xid.unwrap())
+            .collect();
+        assert_eq!(rollbacks, [TestXids.branch_xid(4, 1)]);
+
+        log.clear();
+        tm.start_transaction().unwrap();
+        tm.commit_transaction().unwrap();
+        assert_eq!(log.calls()[0].xid, Some(TestXids.branch_xid(5, 1)));
+    }
+
+    #[test]
+    fn test_cleanup_completes_the_branches() {
+        let log = CallLog::new();
+        let mut tm = SimpleTransactionManager::with_xid_generator(
+            "test_cleanup_completes_the_branches",
+            TestXids,
+        );
+        tm.register(Box::new(MockResourceManager::new(&log)), 1, false)
+            .unwrap();
+        let rm = MockResourceManager::new(&log).returning(
+            RmMethod::Commit,
+            Err(RmError::new(ErrorCode::RmFailure, "gone".to_string())),
+        );
+        tm.register(Box::new(rm), 2, false).unwrap();
+        tm.start_transaction().unwrap();
+        assert!(tm.commit_transaction().is_err());
+        // without a log, the decision is kept in memory
+        assert_eq!(tm.pending_commits().unwrap(), [1]);
+
+        // the resource manager is back, with the branch of the decided commit
+        // and one of an unknown transaction
+        log.clear();
+        tm.unregister(2).unwrap();
+        let rm = MockResourceManager::new(&log)
+            .recovering(vec![TestXids.branch_xid(1, 2), TestXids.branch_xid(3, 2)]);
+        tm.register(Box::new(rm), 2, true).unwrap();
+        let completed: Vec<_> = log
+            .calls()
+            .into_iter()
+            .filter_map(|call| Some((call.method, call.xid?)))
+            .collect();
+        assert_eq!(
+            completed,
+            [
+                (RmMethod::Commit, TestXids.branch_xid(1, 2)),
+                (RmMethod::Rollback, TestXids.branch_xid(3, 2))
+            ]
+        );
+        log.assert_not_called(RmMethod::Forget);
+        // the decision ends when the other resource manager is checked, too
+        assert_eq!(tm.pending_commits().unwrap(), [1]);
+        tm.recover().unwrap();
+        assert!(tm.pending_commits().unwrap().is_empty());
+    }
+}
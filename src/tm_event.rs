@@ -0,0 +1,246 @@
+#[cfg(feature = "audit")]
+use crate::AuditLog;
+use crate::{ErrorCode, ReturnCode, RmError, RmMethod};
+
+/// An event in the life of the global transactions of a `SimpleTransactionManager`.
+///
+/// `global_tid` is the number of the global transaction, `rm_id` the id under which
+/// the resource manager was registered.
+#[derive(Clone, Debug)]
+pub enum TmEvent {
+    /// A global transaction is started; the `BranchStarted` events of its resource managers follow.
+    TransactionStarted {
+        /// The global transaction.
+        global_tid: u64,
+    },
+    /// A resource manager started its branch of the global transaction.
+    BranchStarted {
+        /// The global transaction.
+        global_tid: u64,
+        /// The resource manager.
+        rm_id: u64,
+    },
+    /// A resource manager answered the prepare request.
+    PrepareVote {
+        /// The global transaction.
+        global_tid: u64,
+        /// The resource manager.
+        rm_id: u64,
+        /// The vote, or the error code if the prepare request failed.
+        vote: Result<ReturnCode, ErrorCode>,
+    },
+    /// The transaction manager decided the outcome of the global transaction.
+    DecisionMade {
+        /// The global transaction.
+        global_tid: u64,
+        /// `true` if the transaction is committed, `false` if it is rolled back.
+        commit: bool,
+    },
+    /// A resource manager completed its branch by committing or rolling it back.
+    BranchCompleted {
+        /// The global transaction.
+        global_tid: u64,
+        /// The resource manager.
+        rm_id: u64,
+        /// `true` if the branch was committed, `false` if it was rolled back.
+        committed: bool,
+        /// The return code, or the error code if the call failed.
+        result: Result<ReturnCode, ErrorCode>,
+    },
+    /// A resource manager reported a heuristic outcome for its branch.
+    Heuristic {
+        /// The global transaction.
+        global_tid: u64,
+        /// The resource manager.
+        rm_id: u64,
+        /// The heuristic return code.
+        return_code: ReturnCode,
+    },
+    /// A branch that was found during recovery was committed or rolled back.
+    RecoveryResolved {
+        /// The global transaction.
+        global_tid: u64,
+        /// The resource manager.
+        rm_id: u64,
+        /// `true` if the branch was committed, `false` if it was rolled back.
+        committed: bool,
+        /// The return code, or the error code if the call failed.
+        result: Result<ReturnCode, ErrorCode>,
+    },
+}
+
+/// Receives the [`TmEvent`]s of a `SimpleTransactionManager`.
+///
+/// Listeners are called synchronously, in the order of the events,
+/// and should return quickly. Closures `FnMut(&TmEvent)` are listeners;
+/// to process the events elsewhere, send clones to a channel.
+///
+/// ```rust
+/// # #[cfg(feature = "sync")]
+/// # {
+/// use dist_tx::{sync::tm::SimpleTransactionManager, TmEvent};
+/// use std::sync::mpsc::channel;
+///
+/// let (sender, receiver) = channel::<TmEvent>();
+/// let mut tm = SimpleTransactionManager::new("example");
+/// tm.add_listener(move |event: &TmEvent| sender.send(event.clone()).unwrap_or(()));
+/// # }
+/// ```
+pub trait TmEventListener: Send {
+    /// Is called for each event.
+    fn on_event(&mut self, event: &TmEvent);
+}
+impl<F: FnMut(&TmEvent) + Send> TmEventListener for F {
+    fn on_event(&mut self, event: &TmEvent) {
+        self(event);
+    }
+}
+
+// The registered listeners of a transaction manager, and its audit log.
+#[derive(Default)]
+pub(crate) struct Listeners {
+    listeners: Vec<Box<dyn TmEventListener>>,
+    #[cfg(feature = "audit")]
+    audit: Option<AuditLog>,
+}
+impl Listeners {
+    pub(crate) fn add(&mut self, listener: Box<dyn TmEventListener>) {
+        self.listeners.push(listener);
+    }
+
+    #[cfg(feature = "audit")]
+    pub(crate) fn set_audit_log(&mut self, audit: AuditLog) {
+        self.audit = Some(audit);
+    }
+
+    pub(crate) fn notify(&mut self, event: &TmEvent) {
+        #[cfg(feature = "audit")]
+        if let Some(ref mut audit) = self.audit {
+            audit.observe(event);
+        }
+        for listener in &mut self.listeners {
+            listener.on_event(event);
+        }
+    }
+
+    // Records the decision in the audit log, and informs about it.
+    // A commit is only reported if it could be recorded, as it is not executed otherwise;
+    // a rollback is executed anyway.
+    pub(crate) fn decide(&mut self, global_tid: u64, commit: bool) -> std::io::Result<()> {
+        let recorded = self.record_decision(global_tid, commit);
+        if recorded.is_ok() || !commit {
+            self.notify(&TmEvent::DecisionMade { global_tid, commit });
+        }
+        recorded
+    }
+
+    // Like `decide`, for an outcome that is already executed, like that of a one-phase commit.
+    pub(crate) fn decided(&mut self, global_tid: u64, commit: bool) -> std::io::Result<()> {
+        let recorded = self.record_decision(global_tid, commit);
+        self.notify(&TmEvent::DecisionMade { global_tid, commit });
+        recorded
+    }
+
+    #[cfg(feature = "audit")]
+    fn record_decision(&mut self, global_tid: u64, commit: bool) -> std::io::Result<()> {
+        match self.audit {
+            Some(ref mut audit) => audit.record_decision(global_tid, commit),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "audit"))]
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    fn record_decision(&mut self, _global_tid: u64, _commit: bool) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        #[cfg(feature = "audit")]
+        if self.audit.is_some() {
+            return false;
+        }
+        self.listeners.is_empty()
+    }
+
+    // Reports the result of a call to a resource manager, if it is of interest.
+    pub(crate) fn branch_result<T: Clone + Into<ReturnCode>>(
+        &mut self,
+        method: RmMethod,
+        global_tid: u64,
+        rm_id: u64,
+        result: &Result<T, RmError>,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let result = outcome(result);
+        let event = match method {
+            RmMethod::Start => match result {
+                Ok(_) => TmEvent::BranchStarted { global_tid, rm_id },
+                Err(_) => return,
+            },
+            RmMethod::Prepare => TmEvent::PrepareVote {
+                global_tid,
+                rm_id,
+                vote: result.clone(),
+            },
+            RmMethod::Commit | RmMethod::CommitOnePhase | RmMethod::Rollback => {
+                TmEvent::BranchCompleted {
+                    global_tid,
+                    rm_id,
+                    committed: method != RmMethod::Rollback,
+                    result: result.clone(),
+                }
+            }
+            _ => return,
+        };
+        self.notify(&event);
+        self.heuristic(global_tid, rm_id, &result);
+    }
+
+    // Reports the resolution of a branch during recovery.
+    pub(crate) fn recovery_resolved(
+        &mut self,
+        global_tid: u64,
+        rm_id: u64,
+        committed: bool,
+        result: &Result<ReturnCode, RmError>,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let result = outcome(result);
+        self.notify(&TmEvent::RecoveryResolved {
+            global_tid,
+            rm_id,
+            committed,
+            result: result.clone(),
+        });
+        self.heuristic(global_tid, rm_id, &result);
+    }
+
+    fn heuristic(&mut self, global_tid: u64, rm_id: u64, result: &Result<ReturnCode, ErrorCode>) {
+        if let Some(rc) = result.as_ref().ok().filter(|rc| rc.is_heuristic()) {
+            self.notify(&TmEvent::Heuristic {
+                global_tid,
+                rm_id,
+                return_code: rc.clone(),
+            });
+        }
+    }
+}
+impl std::fmt::Debug for Listeners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Listeners({})", self.listeners.len())
+    }
+}
+
+fn outcome<T: Clone + Into<ReturnCode>>(
+    result: &Result<T, RmError>,
+) -> Result<ReturnCode, ErrorCode> {
+    match result {
+        Ok(t) => Ok(t.clone().into()),
+        Err(e) => Err(e.get_code()),
+    }
+}
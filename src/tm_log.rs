@@ -0,0 +1,234 @@
+use crate::file_store::write_atomically;
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// A durable log of the commit decisions of a transaction manager.
+///
+/// A transaction manager with a log
+/// (see [`sync::tm::SimpleTransactionManager::with_log`](crate::sync::tm::SimpleTransactionManager::with_log)
+/// and [`a_sync::tm::SimpleTransactionManager::with_log`](crate::a_sync::tm::SimpleTransactionManager::with_log))
+/// logs the decision to commit a global transaction after all branches were prepared,
+/// and before the first branch is committed.
+/// After a crash, the recovery commits the prepared branches of the logged transactions,
+/// and rolls back all others ("presumed abort").
+pub trait TmLog: std::fmt::Debug + Send {
+    /// Durably records the decision to commit the global transaction,
+    /// together with the ids of the resource managers that take part in it.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the decision could not be recorded.
+    fn log_commit(&mut self, global_tid: u64, rm_ids: &[u64]) -> std::io::Result<()>;
+
+    /// Records that all branches of the global transaction are committed,
+    /// so that its decision is no longer needed.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the end could not be recorded.
+    fn log_end(&mut self, global_tid: u64) -> std::io::Result<()>;
+
+    /// Returns the global transactions whose commit decision was logged, but not yet ended.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the log could not be read.
+    fn pending_commits(&self) -> std::io::Result<Vec<u64>>;
+
+    /// Returns the ids of the resource managers that take part in the global transaction
+    /// with a pending commit decision.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the log could not be read.
+    fn participants(&self, global_tid: u64) -> std::io::Result<Vec<u64>>;
+}
+
+/// A `TmLog` in a file.
+///
+/// Each record is appended as a line and synced to disk before the call returns.
+/// A record that was torn by a crash is discarded when the log is opened again,
+/// and the log is compacted to the pending decisions.
+#[derive(Debug)]
+pub struct FileTmLog {
+    path: PathBuf,
+    file: File,
+    // the pending decisions, with their resource managers
+    pending: BTreeMap<u64, Vec<u64>>,
+}
+impl FileTmLog {
+    /// Opens the log in the given file, and creates it if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// `std::io::Error` if the file cannot be read or written,
+    /// or if it contains records that are not understood.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<FileTmLog> {
+        let path = path.as_ref().to_path_buf();
+        let mut content = String::new();
+        if path.exists() {
+            File::open(&path)?.read_to_string(&mut content)?;
+        }
+
+        let mut pending = BTreeMap::new();
+        // a line without newline was torn by a crash and is ignored
+        for line in content.split_inclusive('\n').filter(|l| l.ends_with('\n')) {
+            match parse_record(line.trim_end()) {
+                Some(('C', gtid, rm_ids)) => pending.insert(gtid, rm_ids),
+                Some(('E', gtid, _)) => pending.remove(&gtid),
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("unexpected record in {}: {line:?}", path.display()),
+                    ))
+                }
+            };
+        }
+
+        write_atomically(
+            &path,
+            pending
+                .iter()
+                .fold(String::new(), |s, (gtid, rm_ids)| {
+                    s + &record('C', *gtid, rm_ids)
+                })
+                .as_bytes(),
+        )?;
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(FileTmLog {
+            path,
+            file,
+            pending,
+        })
+    }
+
+    /// Returns the path of the log file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn append(&mut self, kind: char, global_tid: u64, rm_ids: &[u64]) -> std::io::Result<()> {
+        self.file
+            .write_all(record(kind, global_tid, rm_ids).as_bytes())?;
+        self.file.sync_data()
+    }
+}
+
+// A record has the form `<kind> <global_tid>[ <rm_id>,<rm_id>,...]`.
+fn record(kind: char, global_tid: u64, rm_ids: &[u64]) -> String {
+    if rm_ids.is_empty() {
+        format!("{kind} {global_tid}\n")
+    } else {
+        let rm_ids: Vec<String> = rm_ids.iter().map(ToString::to_string).collect();
+        format!("{kind} {global_tid} {}\n", rm_ids.join(","))
+    }
+}
+
+fn parse_record(line: &str) -> Option<(char, u64, Vec<u64>)> {
+    let mut fields = line.split(' ');
+    let mut chars = fields.next()?.chars();
+    let (Some(kind), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    let gtid = fields.next()?.parse().ok()?;
+    let rm_ids = match fields.next() {
+        Some(rm_ids) => rm_ids
+            .split(',')
+            .map(|rm_id| rm_id.parse().ok())
+            .collect::<Option<Vec<u64>>>()?,
+        None => Vec::new(),
+    };
+    match fields.next() {
+        Some(_) => None,
+        None => Some((kind, gtid, rm_ids)),
+    }
+}
+
+impl TmLog for FileTmLog {
+    fn log_commit(&mut self, global_tid: u64, rm_ids: &[u64]) -> std::io::Result<()> {
+        self.append('C', global_tid, rm_ids)?;
+        self.pending.insert(global_tid, rm_ids.to_vec());
+        Ok(())
+    }
+
+    fn log_end(&mut self, global_tid: u64) -> std::io::Result<()> {
+        self.append('E', global_tid, &[])?;
+        self.pending.remove(&global_tid);
+        Ok(())
+    }
+
+    fn pending_commits(&self) -> std::io::Result<Vec<u64>> {
+        Ok(self.pending.keys().copied().collect())
+    }
+
+    fn participants(&self, global_tid: u64) -> std::io::Result<Vec<u64>> {
+        Ok(self.pending.get(&global_tid).cloned().unwrap_or_default())
+    }
+}
+
+// Keeps the commit decisions of a transaction manager without a durable log in memory.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryTmLog(BTreeMap<u64, Vec<u64>>);
+impl TmLog for MemoryTmLog {
+    fn log_commit(&mut self, global_tid: u64, rm_ids: &[u64]) -> std::io::Result<()> {
+        self.0.insert(global_tid, rm_ids.to_vec());
+        Ok(())
+    }
+
+    fn log_end(&mut self, global_tid: u64) -> std::io::Result<()> {
+        self.0.remove(&global_tid);
+        Ok(())
+    }
+
+    fn pending_commits(&self) -> std::io::Result<Vec<u64>> {
+        Ok(self.0.keys().copied().collect())
+    }
+
+    fn participants(&self, global_tid: u64) -> std::io::Result<Vec<u64>> {
+        Ok(self.0.get(&global_tid).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileTmLog, TmLog};
+    use crate::file_store::tests::test_dir;
+    use std::io::Write;
+
+    #[test]
+    fn test_pending_commits_survive_reopen() {
+        let dir = test_dir("tm_log");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tm.log");
+        {
+            let mut log = FileTmLog::open(&path).unwrap();
+            log.log_commit(1, &[1, 2]).unwrap();
+            log.log_commit(2, &[1, 3]).unwrap();
+            log.log_end(1).unwrap();
+        }
+        // a torn record is discarded
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"C 3")
+            .unwrap();
+
+        let mut log = FileTmLog::open(&path).unwrap();
+        assert_eq!(log.pending_commits().unwrap(), vec![2]);
+        assert_eq!(log.participants(2).unwrap(), vec![1, 3]);
+        log.log_end(2).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "C 2 1,3\nE 2\n");
+        assert!(FileTmLog::open(&path)
+            .unwrap()
+            .pending_commits()
+            .unwrap()
+            .is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
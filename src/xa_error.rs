@@ -20,6 +20,9 @@ pub enum XaError {
     /// Reading an `XaTransactionId` from a byte stream failed.
     #[error("Reading an XaTransactionId from a byte stream failed")]
     ReadXid(String),
+    /// Writing or reading the log of the transaction manager failed.
+    #[error("Writing or reading the transaction log failed: {0}")]
+    Log(String),
 }
 impl From<std::io::Error> for XaError {
     fn from(e: std::io::Error) -> XaError {
@@ -0,0 +1,238 @@
+use crate::ReturnCode;
+#[cfg(any(feature = "sync", feature = "async"))]
+use crate::{ErrorCode, RmError, RmMethod, XaTransactionId};
+
+/// The reason why a transaction branch was rolled back (the `XA_RB*` return codes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RollbackReason {
+    /// `XA_RBROLLBACK`: an unspecified reason.
+    Unspecified,
+    /// `XA_RBCOMMFAIL`: a communication failure.
+    CommunicationFailure,
+    /// `XA_RBDEADLOCK`: a deadlock was detected.
+    Deadlock,
+    /// `XA_RBINTEGRITY`: a condition that violates the integrity of the resources.
+    Integrity,
+    /// `XA_RBOTHER`: a reason not on this list.
+    Other,
+    /// `XA_RBPROTO`: a protocol error in the resource manager.
+    Protocol,
+    /// `XA_RBTIMEOUT`: the transaction branch took too long.
+    Timeout,
+    /// `XA_RBTRANSIENT`: a transient issue, a retry with this transaction branch may work.
+    Transient,
+}
+
+/// The kind of a heuristic completion of a transaction branch (the `XA_HEUR*` return codes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HeuristicKind {
+    /// `XA_HEURHAZ`: the branch may have been heuristically completed.
+    Hazard,
+    /// `XA_HEURCOM`: the branch was heuristically committed.
+    Committed,
+    /// `XA_HEURRB`: the branch was heuristically rolled back.
+    RolledBack,
+    /// `XA_HEURMIX`: the branch was partly committed and partly rolled back.
+    Mixed,
+}
+
+/// The answer of a resource manager to `prepare()`, as returned by `prepare_vote()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PrepareVote {
+    /// The branch is prepared and can be committed.
+    Ok,
+    /// The branch was read-only and has been committed; it takes no part in phase two.
+    ReadOnly,
+    /// The branch was rolled back.
+    Rollback(RollbackReason),
+}
+
+/// The outcome of `commit()`, `commit_one_phase()` or `rollback()`, as returned by
+/// `commit_outcome()`, `commit_one_phase_outcome()` and `rollback_outcome()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CompletionOutcome {
+    /// The branch was completed as requested.
+    Done,
+    /// The branch was rolled back; for `commit_one_phase()` this means that
+    /// the commit failed.
+    RolledBack(RollbackReason),
+    /// The branch had been completed heuristically; the resource manager keeps
+    /// it until `forget()` is called.
+    Heuristic(HeuristicKind),
+}
+
+impl TryFrom<ReturnCode> for RollbackReason {
+    type Error = ReturnCode;
+
+    fn try_from(rc: ReturnCode) -> Result<RollbackReason, ReturnCode> {
+        Ok(match rc {
+            ReturnCode::RollbackUnspecified => RollbackReason::Unspecified,
+            ReturnCode::RollbackCommunicationFailure => RollbackReason::CommunicationFailure,
+            ReturnCode::RollbackDeadlock => RollbackReason::Deadlock,
+            ReturnCode::RollbackIntegrity => RollbackReason::Integrity,
+            ReturnCode::RollbackOther => RollbackReason::Other,
+            ReturnCode::RollbackProtocol => RollbackReason::Protocol,
+            ReturnCode::RollbackTimeout => RollbackReason::Timeout,
+            ReturnCode::RollbackTransient => RollbackReason::Transient,
+            rc => return Err(rc),
+        })
+    }
+}
+impl From<RollbackReason> for ReturnCode {
+    fn from(reason: RollbackReason) -> ReturnCode {
+        match reason {
+            RollbackReason::Unspecified => ReturnCode::RollbackUnspecified,
+            RollbackReason::CommunicationFailure => ReturnCode::RollbackCommunicationFailure,
+            RollbackReason::Deadlock => ReturnCode::RollbackDeadlock,
+            RollbackReason::Integrity => ReturnCode::RollbackIntegrity,
+            RollbackReason::Other => ReturnCode::RollbackOther,
+            RollbackReason::Protocol => ReturnCode::RollbackProtocol,
+            RollbackReason::Timeout => ReturnCode::RollbackTimeout,
+            RollbackReason::Transient => ReturnCode::RollbackTransient,
+        }
+    }
+}
+
+impl TryFrom<ReturnCode> for HeuristicKind {
+    type Error = ReturnCode;
+
+    fn try_from(rc: ReturnCode) -> Result<HeuristicKind, ReturnCode> {
+        Ok(match rc {
+            ReturnCode::HeuristicallyCompleted => HeuristicKind::Hazard,
+            ReturnCode::HeuristicallyCommitted => HeuristicKind::Committed,
+            ReturnCode::HeuristicallyRolledBack => HeuristicKind::RolledBack,
+            ReturnCode::HeuristicallyMessedUp => HeuristicKind::Mixed,
+            rc => return Err(rc),
+        })
+    }
+}
+impl From<HeuristicKind> for ReturnCode {
+    fn from(kind: HeuristicKind) -> ReturnCode {
+        match kind {
+            HeuristicKind::Hazard => ReturnCode::HeuristicallyCompleted,
+            HeuristicKind::Committed => ReturnCode::HeuristicallyCommitted,
+            HeuristicKind::RolledBack => ReturnCode::HeuristicallyRolledBack,
+            HeuristicKind::Mixed => ReturnCode::HeuristicallyMessedUp,
+        }
+    }
+}
+
+/// Fails with the return code if it is not a valid answer to `prepare()`.
+impl TryFrom<ReturnCode> for PrepareVote {
+    type Error = ReturnCode;
+
+    fn try_from(rc: ReturnCode) -> Result<PrepareVote, ReturnCode> {
+        match rc {
+            ReturnCode::Ok => Ok(PrepareVote::Ok),
+            ReturnCode::ReadOnlyCommitted => Ok(PrepareVote::ReadOnly),
+            rc => RollbackReason::try_from(rc).map(PrepareVote::Rollback),
+        }
+    }
+}
+impl From<PrepareVote> for ReturnCode {
+    fn from(vote: PrepareVote) -> ReturnCode {
+        match vote {
+            PrepareVote::Ok => ReturnCode::Ok,
+            PrepareVote::ReadOnly => ReturnCode::ReadOnlyCommitted,
+            PrepareVote::Rollback(reason) => reason.into(),
+        }
+    }
+}
+
+/// Fails with the return code if it is not a valid outcome of a completion.
+///
+/// `XA_RDONLY` is taken as `Done`, because some resource managers return it
+/// for read-only branches also from `commit_one_phase()`.
+impl TryFrom<ReturnCode> for CompletionOutcome {
+    type Error = ReturnCode;
+
+    fn try_from(rc: ReturnCode) -> Result<CompletionOutcome, ReturnCode> {
+        match rc {
+            ReturnCode::Ok | ReturnCode::ReadOnlyCommitted => Ok(CompletionOutcome::Done),
+            rc => HeuristicKind::try_from(rc)
+                .map(CompletionOutcome::Heuristic)
+                .or_else(|rc| RollbackReason::try_from(rc).map(CompletionOutcome::RolledBack)),
+        }
+    }
+}
+impl From<CompletionOutcome> for ReturnCode {
+    fn from(outcome: CompletionOutcome) -> ReturnCode {
+        match outcome {
+            CompletionOutcome::Done => ReturnCode::Ok,
+            CompletionOutcome::RolledBack(reason) => reason.into(),
+            CompletionOutcome::Heuristic(kind) => kind.into(),
+        }
+    }
+}
+
+// Converts the return code of a method into the typed result,
+// and reports invalid return codes as `RmError`.
+#[cfg(any(feature = "sync", feature = "async"))]
+pub(crate) fn typed<T: TryFrom<ReturnCode, Error = ReturnCode>>(
+    method: RmMethod,
+    id: &XaTransactionId,
+    result: Result<ReturnCode, RmError>,
+) -> Result<T, RmError> {
+    T::try_from(result?).map_err(|rc| {
+        RmError::new(
+            ErrorCode::RmError,
+            format!("unexpected return code {rc} of {method:?}"),
+        )
+        .with_xid(*id)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompletionOutcome, HeuristicKind, PrepareVote, RollbackReason};
+    use crate::ReturnCode;
+
+    #[test]
+    fn test_conversions() {
+        for i in (-1..=10).chain(99..=108) {
+            let rc = ReturnCode::from_i32(i);
+            if let Ok(vote) = PrepareVote::try_from(rc.clone()) {
+                assert_eq!(ReturnCode::from(vote), rc);
+            }
+            if let Ok(outcome) = CompletionOutcome::try_from(rc.clone()) {
+                assert!(ReturnCode::from(outcome) == rc || rc == ReturnCode::ReadOnlyCommitted);
+            }
+        }
+        assert_eq!(
+            PrepareVote::try_from(ReturnCode::RollbackDeadlock),
+            Ok(PrepareVote::Rollback(RollbackReason::Deadlock))
+        );
+        assert_eq!(
+            PrepareVote::try_from(ReturnCode::HeuristicallyCommitted),
+            Err(ReturnCode::HeuristicallyCommitted)
+        );
+        assert_eq!(
+            CompletionOutcome::try_from(ReturnCode::HeuristicallyMessedUp),
+            Ok(CompletionOutcome::Heuristic(HeuristicKind::Mixed))
+        );
+        assert_eq!(
+            CompletionOutcome::try_from(ReturnCode::Retry),
+            Err(ReturnCode::Retry)
+        );
+    }
+
+    #[cfg(any(feature = "sync", feature = "async"))]
+    #[test]
+    fn test_typed() {
+        use super::typed;
+        use crate::{ErrorCode, RmMethod, XaTransactionId};
+
+        let xid = XaTransactionId::null_ta();
+        let e = typed::<PrepareVote>(RmMethod::Prepare, &xid, Ok(ReturnCode::Retry)).unwrap_err();
+        assert_eq!(e.code(), &ErrorCode::RmError);
+        assert_eq!(
+            e.description(),
+            "unexpected return code XA_RETRY of Prepare"
+        );
+        assert_eq!(e.xid(), Some(&xid));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{ReturnCode, RmError, RmMethod, XaTransactionId};
+use std::time::Instant;
+use tracing::{field, info_span, Span};
+
+// Opens the span of an XA call.
+pub(crate) fn xa_call(method: RmMethod, id: Option<&XaTransactionId>, rm_id: u64) -> Span {
+    info_span!(
+        "xa_call",
+        method = ?method,
+        rm_id,
+        gtid = id.map(|id| hex(id.get_global_tid())),
+        bqual = id.map(|id| hex(id.get_branch_qualifier())),
+        duration_us = field::Empty,
+        return_code = field::Empty,
+        error_code = field::Empty,
+        recovered = field::Empty,
+    )
+}
+
+// Opens the parent span of the calls for a global transaction.
+pub(crate) fn global_transaction(tm_name: &str, global_tid: u64) -> Span {
+    info_span!("global_transaction", tm = tm_name, gtid = global_tid)
+}
+
+pub(crate) fn record_result<R: Clone + Into<ReturnCode>>(
+    span: &Span,
+    start: Instant,
+    result: &Result<R, RmError>,
+) {
+    record_duration(span, start);
+    match result {
+        Ok(outcome) => span.record("return_code", field::debug(outcome.clone().into())),
+        Err(e) => span.record("error_code", field::debug(e.get_code())),
+    };
+}
+
+pub(crate) fn record_recover(
+    span: &Span,
+    start: Instant,
+    result: &Result<Vec<XaTransactionId>, RmError>,
+) {
+    record_duration(span, start);
+    match result {
+        Ok(ids) => span.record("recovered", ids.len()),
+        Err(e) => span.record("error_code", field::debug(e.get_code())),
+    };
+}
+
+fn record_duration(span: &Span, start: Instant) {
+    span.record(
+        "duration_us",
+        u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX),
+    );
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(2 * bytes.len()), |s, b| {
+            s + &format!("{b:02x}")
+        })
+}
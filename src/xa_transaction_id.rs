@@ -1,24 +1,40 @@
-use crate::XaError;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::convert::TryInto;
-use std::io::{Read, Write};
-use std::iter::repeat;
+use crate::{XaError, XidCodec, XidDecoder, XidLayout};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 
 /// The ID of a distributed transaction, in analogy to the
 /// [X/Open XA standard](http://pubs.opengroup.org/onlinepubs/009680699/toc.pdf).
 ///
-#[derive(Clone)]
+/// Ids are compared and ordered by `format_id`, global transaction id,
+/// and branch qualifier.
+///
+/// `Display` produces the canonical form `formatId:hex(gtrid):hex(bqual)`,
+/// e.g. `99:0700000000000000:CDAB`, which is read back with `FromStr`.
+///
+/// The binary fields are stored inline, so that ids are `Copy` and never allocate.
+#[derive(Clone, Copy)]
 pub struct XaTransactionId {
     format_id: i32,
-    global_tid: Vec<u8>,       // do it with u64
-    branch_qualifier: Vec<u8>, // do it with u64
+    global_tid_len: u8,
+    branch_qualifier_len: u8,
+    global_tid: [u8; MAX_BYTES_GLOBAL_TRANSACTION_ID],
+    branch_qualifier: [u8; MAX_BYTES_BRANCH_QUALIFIER],
 }
 
 // Maximum size in bytes of `XaTransactionId::global_tid`
-const MAX_BYTES_GLOBAL_TRANSACTION_ID: usize = 64;
+pub(crate) const MAX_BYTES_GLOBAL_TRANSACTION_ID: usize = 64;
 
 // Maximum size in bytes of `XaTransactionId::branch_qualifier`
-const MAX_BYTES_BRANCH_QUALIFIER: usize = 64;
+pub(crate) const MAX_BYTES_BRANCH_QUALIFIER: usize = 64;
+
+// Separator of the three parts of a gid
+const GID_SEPARATOR: char = '.';
+
+// Maximum size in bytes of the compact serialization
+#[cfg(feature = "serde")]
+const COMPACT_MAX_SIZE: usize = 12 + MAX_BYTES_GLOBAL_TRANSACTION_ID + MAX_BYTES_BRANCH_QUALIFIER;
+
+// Separator of the three parts of the canonical string representation
+const DISPLAY_SEPARATOR: char = ':';
 
 impl XaTransactionId {
     /// Creates an instance of `XaTransactionId` from the three components
@@ -33,11 +49,13 @@ impl XaTransactionId {
     /// # Errors
     ///
     /// `XaError::Usage` if one of the parameters is ill-formed.
-    pub fn try_new(
+    pub fn try_new<G: AsRef<[u8]>, B: AsRef<[u8]>>(
         format_id: i32,
-        global_tid: Vec<u8>,
-        branch_qualifier: Vec<u8>,
+        global_tid: G,
+        branch_qualifier: B,
     ) -> Result<XaTransactionId, XaError> {
+        let global_tid = global_tid.as_ref();
+        let branch_qualifier = branch_qualifier.as_ref();
         if format_id < -1 {
             Err(XaError::Usage("Bad XA transaction id: invalid format-id"))
         } else if global_tid.len() > MAX_BYTES_GLOBAL_TRANSACTION_ID {
@@ -45,11 +63,17 @@ impl XaTransactionId {
         } else if branch_qualifier.len() > MAX_BYTES_BRANCH_QUALIFIER {
             Err(XaError::Usage("Invalid branch_qualifier (too long)"))
         } else {
-            Ok(XaTransactionId {
-                format_id,
-                global_tid,
-                branch_qualifier,
-            })
+            let mut xid = XaTransactionId::null_ta();
+            xid.format_id = format_id;
+            xid.global_tid[..global_tid.len()].copy_from_slice(global_tid);
+            xid.branch_qualifier[..branch_qualifier.len()].copy_from_slice(branch_qualifier);
+            // the lengths are at most 64
+            #[allow(clippy::cast_possible_truncation)]
+            {
+                xid.global_tid_len = global_tid.len() as u8;
+                xid.branch_qualifier_len = branch_qualifier.len() as u8;
+            }
+            Ok(xid)
         }
     }
 
@@ -58,8 +82,10 @@ impl XaTransactionId {
     pub fn null_ta() -> XaTransactionId {
         XaTransactionId {
             format_id: -1,
-            global_tid: vec![],
-            branch_qualifier: vec![],
+            global_tid_len: 0,
+            branch_qualifier_len: 0,
+            global_tid: [0; MAX_BYTES_GLOBAL_TRANSACTION_ID],
+            branch_qualifier: [0; MAX_BYTES_BRANCH_QUALIFIER],
         }
     }
 
@@ -71,38 +97,33 @@ impl XaTransactionId {
 
     /// Returns a reference to the global transaction id.
     #[must_use]
-    pub fn get_global_tid(&self) -> &Vec<u8> {
-        &self.global_tid
+    pub fn get_global_tid(&self) -> &[u8] {
+        &self.global_tid[..usize::from(self.global_tid_len)]
     }
 
     /// Returns a reference to the branch qualifier.
     #[must_use]
-    pub fn get_branch_qualifier(&self) -> &Vec<u8> {
-        &self.branch_qualifier
+    pub fn get_branch_qualifier(&self) -> &[u8] {
+        &self.branch_qualifier[..usize::from(self.branch_qualifier_len)]
+    }
+
+    fn key(&self) -> (i32, &[u8], &[u8]) {
+        (
+            self.format_id,
+            self.get_global_tid(),
+            self.get_branch_qualifier(),
+        )
     }
 
     /// Provides a binary representation.
     /// If padding is true, and the combined length of the binary fields is
     /// below 128 bytes, the missing number of zero bytes are appended to
     /// make the byte pattern compatible with the XA structure in C.
-    #[allow(clippy::cast_possible_wrap)]
-    #[allow(clippy::cast_possible_truncation)]
+    ///
+    /// For other byte orders and layouts, see [`XidLayout`].
     #[must_use]
     pub fn as_bytes(&self, padding: bool) -> Vec<u8> {
-        let mut s = Vec::<u8>::with_capacity(128);
-        s.write_i32::<LittleEndian>(self.format_id).ok();
-        s.write_i32::<LittleEndian>(self.global_tid.len() as i32)
-            .ok();
-        s.write_i32::<LittleEndian>(self.branch_qualifier.len() as i32)
-            .ok();
-        s.write_all(&self.global_tid).ok();
-        s.write_all(&self.branch_qualifier).ok();
-        if padding {
-            for _ in 0..(128 - self.branch_qualifier.len() - self.global_tid.len()) {
-                s.write_u8(0).ok();
-            }
-        }
-        s
+        layout(padding).encode(self)
     }
 
     /// Reads a Vec of instances from a binary representation.
@@ -110,44 +131,205 @@ impl XaTransactionId {
     /// below 128 bytes, the missing number of bytes are skipped to make
     /// the byte pattern compatible with the XA structure in C.
     ///
+    /// To read an unknown number of instances, or from a stream, use [`XidDecoder`].
+    ///
     /// # Errors
     ///
-    /// `XaError::ReadXid`
+    /// `XaError::ReadXid` if the bytes do not contain `count` valid instances.
     pub fn parse(bytes: &[u8], count: u64, padding: bool) -> Result<Vec<XaTransactionId>, XaError> {
-        let mut rdr = std::io::Cursor::new(bytes);
+        let mut decoder = XidDecoder::new(bytes, padding);
         let mut result = Vec::<XaTransactionId>::new();
+        for i in 0..count {
+            match decoder.next() {
+                Some(xid) => result.push(xid?),
+                None => {
+                    return Err(XaError::ReadXid(format!(
+                        "expected {count} XA transaction ids, found {i}"
+                    )))
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Reads an instance from exactly the bytes produced by
+    /// [`as_bytes`](XaTransactionId::as_bytes) with the same value of `padding`.
+    ///
+    /// # Errors
+    ///
+    /// `XaError::ReadXid` if the bytes are not a valid instance, or are followed by more bytes.
+    pub fn from_bytes(bytes: &[u8], padding: bool) -> Result<XaTransactionId, XaError> {
+        layout(padding).decode(bytes)
+    }
+
+    /// Provides a representation as a string of at most 185 ASCII characters,
+    /// as it is used e.g. for the transaction identifiers of `PostgreSQL`'s
+    /// `PREPARE TRANSACTION`.
+    ///
+    /// The string consists of the decimal `format_id`, the global transaction id,
+    /// and the branch qualifier, separated by dots, where the binary fields are encoded
+    /// with the URL-safe base64 alphabet without padding.
+    #[must_use]
+    pub fn to_gid(&self) -> String {
+        format!(
+            "{}{GID_SEPARATOR}{}{GID_SEPARATOR}{}",
+            self.format_id,
+            URL_SAFE_NO_PAD.encode(self.get_global_tid()),
+            URL_SAFE_NO_PAD.encode(self.get_branch_qualifier())
+        )
+    }
+
+    /// Reads an instance from the string representation produced by
+    /// [`to_gid`](XaTransactionId::to_gid).
+    ///
+    /// # Errors
+    ///
+    /// `XaError::ReadXid` if the string is not a valid gid,
+    /// `XaError::Usage` if the represented fields are ill-formed.
+    pub fn from_gid(gid: &str) -> Result<XaTransactionId, XaError> {
+        let bad_gid = || XaError::ReadXid(format!("not a valid gid: {gid}"));
+        let mut parts = gid.split(GID_SEPARATOR);
+        let (Some(format_id), Some(global_tid), Some(branch_qualifier), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(bad_gid());
+        };
+        XaTransactionId::try_new(
+            format_id.parse().map_err(|_| bad_gid())?,
+            URL_SAFE_NO_PAD.decode(global_tid).map_err(|_| bad_gid())?,
+            URL_SAFE_NO_PAD
+                .decode(branch_qualifier)
+                .map_err(|_| bad_gid())?,
+        )
+    }
+}
+
+impl PartialEq for XaTransactionId {
+    fn eq(&self, other: &XaTransactionId) -> bool {
+        self.key() == other.key()
+    }
+}
+impl Eq for XaTransactionId {}
+
+impl std::hash::Hash for XaTransactionId {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl PartialOrd for XaTransactionId {
+    fn partial_cmp(&self, other: &XaTransactionId) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for XaTransactionId {
+    fn cmp(&self, other: &XaTransactionId) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+fn layout(padding: bool) -> XidLayout {
+    if padding {
+        XidLayout::LITTLE_ENDIAN_PADDED
+    } else {
+        XidLayout::LITTLE_ENDIAN
+    }
+}
+
+impl std::fmt::Display for XaTransactionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{DISPLAY_SEPARATOR}{}{DISPLAY_SEPARATOR}{}",
+            self.format_id,
+            hex(self.get_global_tid()),
+            hex(self.get_branch_qualifier())
+        )
+    }
+}
 
-        for _ in 0..count {
-            let format_id: i32 = rdr.read_i32::<LittleEndian>()?;
-            let global_tid_len: usize = rdr
-                .read_i32::<LittleEndian>()?
-                .try_into()
-                .map_err(|_| XaError::ReadXid("Negative global tid length".to_owned()))?;
-            let branch_qualifier_len: usize = rdr
-                .read_i32::<LittleEndian>()?
-                .try_into()
-                .map_err(|_| XaError::ReadXid("Negative branch qualifier length".to_owned()))?;
-
-            let mut global_tid: Vec<u8> = repeat(0_u8).take(global_tid_len).collect();
-            rdr.read_exact(&mut global_tid)?;
-
-            let mut branch_qualifier: Vec<u8> = repeat(0_u8).take(branch_qualifier_len).collect();
-            rdr.read_exact(&mut branch_qualifier)?;
-
-            if padding {
-                let missing = 128 - branch_qualifier_len - global_tid_len;
-                for _ in 0..missing {
-                    rdr.read_u8()?;
+impl std::str::FromStr for XaTransactionId {
+    type Err = XaError;
+
+    /// Reads an instance from the canonical representation produced by `Display`;
+    /// the hex digits can be upper or lower case.
+    fn from_str(s: &str) -> Result<XaTransactionId, XaError> {
+        let bad_xid = || XaError::ReadXid(format!("not a valid xid: {s}"));
+        let mut parts = s.split(DISPLAY_SEPARATOR);
+        let (Some(format_id), Some(global_tid), Some(branch_qualifier), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(bad_xid());
+        };
+        XaTransactionId::try_new(
+            format_id.parse().map_err(|_| bad_xid())?,
+            unhex(global_tid).ok_or_else(bad_xid)?,
+            unhex(branch_qualifier).ok_or_else(bad_xid)?,
+        )
+    }
+}
+
+/// With the feature `serde`, human-readable formats get the canonical string of
+/// `Display`, compact formats the bytes of [`as_bytes(false)`](XaTransactionId::as_bytes).
+#[cfg(feature = "serde")]
+impl serde::Serialize for XaTransactionId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_bytes(&self.as_bytes(false))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for XaTransactionId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = XaTransactionId;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "an XA transaction id")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<XaTransactionId, E> {
+                s.parse().map_err(de_error)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, b: &[u8]) -> Result<XaTransactionId, E> {
+                XaTransactionId::from_bytes(b, false).map_err(de_error)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<XaTransactionId, A::Error> {
+                let mut bytes = Vec::new();
+                while let Some(b) = seq.next_element()? {
+                    if bytes.len() == COMPACT_MAX_SIZE {
+                        return Err(serde::de::Error::custom("XA transaction id is too long"));
+                    }
+                    bytes.push(b);
                 }
+                self.visit_bytes(&bytes)
             }
+        }
 
-            result.push(XaTransactionId::try_new(
-                format_id,
-                global_tid,
-                branch_qualifier,
-            )?);
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Visitor)
+        } else {
+            deserializer.deserialize_bytes(Visitor)
         }
-        Ok(result)
+    }
+}
+
+#[cfg(feature = "serde")]
+fn de_error<E: serde::de::Error>(e: XaError) -> E {
+    match e {
+        XaError::ReadXid(s) | XaError::UsageDetails(s) => E::custom(s),
+        XaError::Usage(s) => E::custom(s),
+        e => E::custom(e),
     }
 }
 
@@ -159,21 +341,151 @@ impl std::fmt::Debug for XaTransactionId {
             write!(
                 f,
                 "XaTransactionId {{format_id: {}, global_tid: {:?}, branch_qualifier: {:?} }}",
-                self.format_id, self.global_tid, self.branch_qualifier
+                self.format_id,
+                self.get_global_tid(),
+                self.get_branch_qualifier()
             )
         }
     }
 }
 
+// Returns the bytes as upper-case hex digits.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(2 * bytes.len()), |s, b| {
+            s + &format!("{b:02X}")
+        })
+}
+
+// Decodes a string of hex digits, or returns `None` if it is not one.
+pub(crate) fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::XaTransactionId;
+    use crate::XaError;
     use byteorder::{LittleEndian, WriteBytesExt};
 
     #[test]
     fn test_xa_transaction_id() {
         let xa_tid = new_xatid(255_u64, 255_u64, 255_u64);
-        println!("xa:tid: {:?}", xa_tid);
+        println!("xa:tid: {xa_tid:?}");
+    }
+
+    #[test]
+    fn test_bytes() {
+        let xa_tid = new_xatid(255_u64, 255_u64, 255_u64);
+        for padding in [false, true] {
+            let bytes = xa_tid.as_bytes(padding);
+            assert_eq!(
+                XaTransactionId::from_bytes(&bytes, padding).unwrap(),
+                xa_tid
+            );
+            assert!(XaTransactionId::from_bytes(&bytes[..bytes.len() - 1], padding).is_err());
+        }
+        let mut bytes = xa_tid.as_bytes(false);
+        bytes.push(0);
+        assert!(XaTransactionId::from_bytes(&bytes, false).is_err());
+        assert!(matches!(
+            XaTransactionId::parse(&bytes, 2, false),
+            Err(XaError::ReadXid(_))
+        ));
+    }
+
+    #[test]
+    fn test_gid() {
+        let xa_tid = new_xatid(255_u64, 255_u64, 255_u64);
+        let gid = xa_tid.to_gid();
+        let xa_tid2 = XaTransactionId::from_gid(&gid).unwrap();
+        assert_eq!(xa_tid.as_bytes(false), xa_tid2.as_bytes(false));
+
+        let long = XaTransactionId::try_new(i32::MAX, vec![255; 64], vec![255; 64]).unwrap();
+        assert!(long.to_gid().len() <= 200);
+
+        for bad in ["", "99", "99.AA", "x.AA.AA", "99.AA.AA.AA", "99.A'.AA"] {
+            assert!(XaTransactionId::from_gid(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_display_and_ordering() {
+        let xa_tid = XaTransactionId::try_new(99, vec![0x07, 0xAB], vec![0xCD]).unwrap();
+        assert_eq!(xa_tid.to_string(), "99:07AB:CD");
+        assert_eq!("99:07ab:CD".parse::<XaTransactionId>().unwrap(), xa_tid);
+        assert_eq!(
+            XaTransactionId::null_ta()
+                .to_string()
+                .parse::<XaTransactionId>()
+                .unwrap(),
+            XaTransactionId::null_ta()
+        );
+        for bad in [
+            "",
+            "99",
+            "99:AB",
+            "x:AB:CD",
+            "99:AB:CD:EF",
+            "99:ABC:CD",
+            "99:XY:CD",
+        ] {
+            assert!(bad.parse::<XaTransactionId>().is_err(), "{bad}");
+        }
+
+        let mut ids = vec![
+            new_xatid(2, 1, 1),
+            new_xatid(1, 1, 2),
+            new_xatid(1, 1, 1),
+            new_xatid(1, 1, 2),
+        ];
+        ids.sort();
+        ids.dedup();
+        assert_eq!(
+            ids,
+            vec![new_xatid(1, 1, 1), new_xatid(1, 1, 2), new_xatid(2, 1, 1)]
+        );
+        let set: std::collections::HashSet<XaTransactionId> = ids.into_iter().collect();
+        assert!(set.contains(&new_xatid(1, 1, 2)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde_test::{
+            assert_de_tokens_error, assert_tokens, Compact, Configure, Readable, Token,
+        };
+
+        let xa_tid = XaTransactionId::try_new(99, vec![0x07, 0xAB], vec![0xCD]).unwrap();
+        assert_tokens(&xa_tid.readable(), &[Token::Str("99:07AB:CD")]);
+        assert_tokens(
+            &xa_tid.compact(),
+            &[Token::Bytes(&[
+                99, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0x07, 0xAB, 0xCD,
+            ])],
+        );
+
+        // the limits of try_new apply
+        let long: &'static str = Box::leak(format!("99:{}:", "00".repeat(65)).into_boxed_str());
+        assert_de_tokens_error::<Readable<XaTransactionId>>(
+            &[Token::Str(long)],
+            "Invalid global ta id (too long)",
+        );
+        assert_de_tokens_error::<Compact<XaTransactionId>>(
+            &[Token::Bytes(&[99, 0, 0, 0, 65, 0, 0, 0, 0, 0, 0, 0])],
+            "invalid length 65 (max 64) at byte 4",
+        );
+        assert_de_tokens_error::<Compact<XaTransactionId>>(
+            &[Token::Bytes(&[99, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0x07])],
+            "unexpected end of input at byte 13",
+        );
     }
 
     fn new_xatid(global_tid: u64, transman_id: u64, resman_id: u64) -> XaTransactionId {
@@ -0,0 +1,248 @@
+use crate::{
+    xa_transaction_id::{MAX_BYTES_BRANCH_QUALIFIER, MAX_BYTES_GLOBAL_TRANSACTION_ID},
+    XaError, XaTransactionId, XidDecoder,
+};
+use std::fmt::Debug;
+
+/// Converts `XaTransactionId`s to and from a binary wire format.
+///
+/// [`XidLayout`] provides the common layouts; implement this trait for other formats,
+/// e.g. those of a Java serialization.
+pub trait XidCodec: Debug + Send + Sync {
+    /// Returns the binary representation of the id.
+    fn encode(&self, xid: &XaTransactionId) -> Vec<u8>;
+
+    /// Reads an id from exactly the given bytes.
+    ///
+    /// # Errors
+    ///
+    /// `XaError::ReadXid` if the bytes are not a valid id in this format.
+    fn decode(&self, bytes: &[u8]) -> Result<XaTransactionId, XaError>;
+}
+
+/// The byte order of the integer fields of an [`XidLayout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first ("network byte order").
+    Big,
+}
+
+/// A binary layout of `XaTransactionId`s: the format id and the lengths of the
+/// global transaction id and the branch qualifier as integers,
+/// followed by the two binary fields and, optionally, the padding to 128 data bytes.
+///
+/// The layouts are [`XidCodec`]s, and [`XidDecoder::with_layout`] reads them from streams.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XidLayout {
+    endianness: Endianness,
+    format_id_size: usize,
+    length_size: usize,
+    padding: bool,
+}
+impl XidLayout {
+    /// Little-endian 4-byte integers, without padding;
+    /// the layout of [`XaTransactionId::as_bytes`] without padding.
+    pub const LITTLE_ENDIAN: XidLayout = XidLayout::new(Endianness::Little, 4, 4, false);
+
+    /// Little-endian 4-byte integers, with padding;
+    /// the layout of [`XaTransactionId::as_bytes`] with padding.
+    pub const LITTLE_ENDIAN_PADDED: XidLayout = XidLayout::new(Endianness::Little, 4, 4, true);
+
+    /// Big-endian 4-byte integers, without padding.
+    pub const BIG_ENDIAN: XidLayout = XidLayout::new(Endianness::Big, 4, 4, false);
+
+    /// Big-endian 4-byte integers, with padding.
+    pub const BIG_ENDIAN_PADDED: XidLayout = XidLayout::new(Endianness::Big, 4, 4, true);
+
+    /// The exact memory layout of the C structure `xid_t` of the XA specification on
+    /// the current platform, whose integer fields are of type `long`.
+    pub const C_XID_T: XidLayout = XidLayout::new(
+        if cfg!(target_endian = "big") {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        },
+        std::mem::size_of::<std::os::raw::c_long>(),
+        std::mem::size_of::<std::os::raw::c_long>(),
+        true,
+    );
+
+    /// A compact form: the format id as big-endian 4-byte integer,
+    /// and both lengths as single bytes, without padding.
+    pub const COMPACT: XidLayout = XidLayout::new(Endianness::Big, 4, 1, false);
+
+    const fn new(
+        endianness: Endianness,
+        format_id_size: usize,
+        length_size: usize,
+        padding: bool,
+    ) -> XidLayout {
+        XidLayout {
+            endianness,
+            format_id_size,
+            length_size,
+            padding,
+        }
+    }
+
+    /// Returns the byte order of the integer fields.
+    #[must_use]
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Returns `true` if the data bytes are padded to 128 bytes.
+    #[must_use]
+    pub fn padding(&self) -> bool {
+        self.padding
+    }
+
+    // Size of the integer fields.
+    pub(crate) fn header_size(&self) -> usize {
+        self.format_id_size + 2 * self.length_size
+    }
+
+    // Reads the format id and the lengths, and returns them with the offset of the
+    // offending field if they are out of range.
+    pub(crate) fn read_header(
+        &self,
+        header: &[u8],
+    ) -> Result<(i32, usize, usize), (usize, String)> {
+        let format_id = self.read_int(&header[..self.format_id_size]);
+        let format_id = i32::try_from(format_id)
+            .ok()
+            .filter(|id| *id >= -1)
+            .ok_or_else(|| (0, format!("invalid format id {format_id}")))?;
+        let length = |offset: usize, max: usize| {
+            let value = self.read_int(&header[offset..offset + self.length_size]);
+            usize::try_from(value)
+                .ok()
+                .filter(|len| *len <= max)
+                .ok_or_else(|| (offset, format!("invalid length {value} (max {max})")))
+        };
+        let global_tid_len = length(self.format_id_size, MAX_BYTES_GLOBAL_TRANSACTION_ID)?;
+        let branch_qualifier_len = length(
+            self.format_id_size + self.length_size,
+            MAX_BYTES_BRANCH_QUALIFIER,
+        )?;
+        Ok((format_id, global_tid_len, branch_qualifier_len))
+    }
+
+    // Reads a signed integer; single bytes are unsigned.
+    fn read_int(&self, bytes: &[u8]) -> i64 {
+        let mut buf = [0_u8; 8];
+        let n = bytes.len();
+        match self.endianness {
+            Endianness::Little => {
+                buf[..n].copy_from_slice(bytes);
+                if n > 1 && bytes[n - 1] & 0x80 != 0 {
+                    buf[n..].fill(0xFF);
+                }
+                i64::from_le_bytes(buf)
+            }
+            Endianness::Big => {
+                buf[8 - n..].copy_from_slice(bytes);
+                if n > 1 && bytes[0] & 0x80 != 0 {
+                    buf[..8 - n].fill(0xFF);
+                }
+                i64::from_be_bytes(buf)
+            }
+        }
+    }
+
+    fn write_int(&self, out: &mut Vec<u8>, value: i64, size: usize) {
+        match self.endianness {
+            Endianness::Little => out.extend_from_slice(&value.to_le_bytes()[..size]),
+            Endianness::Big => out.extend_from_slice(&value.to_be_bytes()[8 - size..]),
+        }
+    }
+}
+impl XidCodec for XidLayout {
+    #[allow(clippy::cast_possible_wrap)]
+    fn encode(&self, xid: &XaTransactionId) -> Vec<u8> {
+        let global_tid = xid.get_global_tid();
+        let branch_qualifier = xid.get_branch_qualifier();
+        let data_size = if self.padding {
+            MAX_BYTES_GLOBAL_TRANSACTION_ID + MAX_BYTES_BRANCH_QUALIFIER
+        } else {
+            global_tid.len() + branch_qualifier.len()
+        };
+        let mut out = Vec::with_capacity(self.header_size() + data_size);
+        self.write_int(&mut out, xid.get_format_id().into(), self.format_id_size);
+        // the lengths are at most 64
+        for len in [global_tid.len(), branch_qualifier.len()] {
+            self.write_int(&mut out, len as i64, self.length_size);
+        }
+        out.extend_from_slice(global_tid);
+        out.extend_from_slice(branch_qualifier);
+        out.resize(self.header_size() + data_size, 0);
+        out
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<XaTransactionId, XaError> {
+        let mut decoder = XidDecoder::with_layout(bytes, *self);
+        match decoder.next() {
+            Some(Ok(_)) if decoder.position() < bytes.len() as u64 => Err(XaError::ReadXid(
+                format!("unexpected data at byte {}", decoder.position()),
+            )),
+            Some(result) => result,
+            None => Err(XaError::ReadXid("no XA transaction id".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{XidCodec, XidLayout};
+    use crate::XaTransactionId;
+
+    #[test]
+    fn test_layouts() {
+        let xid = XaTransactionId::try_new(0x0102, vec![0xAA; 3], vec![0xBB; 2]).unwrap();
+
+        let codecs: [&dyn XidCodec; 6] = [
+            &XidLayout::LITTLE_ENDIAN,
+            &XidLayout::LITTLE_ENDIAN_PADDED,
+            &XidLayout::BIG_ENDIAN,
+            &XidLayout::BIG_ENDIAN_PADDED,
+            &XidLayout::C_XID_T,
+            &XidLayout::COMPACT,
+        ];
+        for codec in codecs {
+            let bytes = codec.encode(&xid);
+            assert_eq!(codec.decode(&bytes).unwrap(), xid, "{codec:?}");
+            assert!(codec.decode(&bytes[..bytes.len() - 1]).is_err());
+            let null = XaTransactionId::null_ta();
+            assert_eq!(codec.decode(&codec.encode(&null)).unwrap(), null);
+        }
+
+        assert_eq!(XidLayout::LITTLE_ENDIAN.encode(&xid), xid.as_bytes(false),);
+        assert_eq!(
+            XidLayout::LITTLE_ENDIAN_PADDED.encode(&xid),
+            xid.as_bytes(true),
+        );
+        assert_eq!(
+            XidLayout::BIG_ENDIAN.encode(&xid),
+            [0, 0, 1, 2, 0, 0, 0, 3, 0, 0, 0, 2, 0xAA, 0xAA, 0xAA, 0xBB, 0xBB]
+        );
+        assert_eq!(
+            XidLayout::COMPACT.encode(&xid),
+            [0, 0, 1, 2, 3, 2, 0xAA, 0xAA, 0xAA, 0xBB, 0xBB]
+        );
+        assert_eq!(
+            XidLayout::C_XID_T.encode(&xid).len(),
+            3 * std::mem::size_of::<std::os::raw::c_long>() + 128
+        );
+
+        // a format id beyond the range of i32 is rejected
+        let mut c_bytes = XidLayout::BIG_ENDIAN.encode(&xid);
+        c_bytes[0] = 0x80;
+        assert!(XidLayout::BIG_ENDIAN.decode(&c_bytes).is_err());
+        // lengths above 64 are rejected, also in the compact form
+        let mut compact = XidLayout::COMPACT.encode(&xid);
+        compact[4] = 200;
+        assert!(XidLayout::COMPACT.decode(&compact).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{
+    xa_transaction_id::{MAX_BYTES_BRANCH_QUALIFIER, MAX_BYTES_GLOBAL_TRANSACTION_ID},
+    XaError, XaTransactionId, XidLayout,
+};
+use std::io::{ErrorKind, Read};
+
+// Maximum size of the integer fields of an `XidLayout`.
+const MAX_HEADER_SIZE: usize = 24;
+
+// Number of data bytes of the XID structure in C.
+const C_DATA_SIZE: usize = MAX_BYTES_GLOBAL_TRANSACTION_ID + MAX_BYTES_BRANCH_QUALIFIER;
+
+/// Reads `XaTransactionId`s in the binary representation of
+/// [`XaTransactionId::as_bytes`], or in another [`XidLayout`], from a byte stream.
+///
+/// The lengths are validated before anything is allocated, so corrupt or hostile input
+/// cannot trigger large allocations. With `padding`, the padding bytes must be zero.
+///
+/// The decoder is an iterator that ends at the end of the input, if that is reached
+/// between two ids. Errors are `XaError::ReadXid` with the offset of the offending byte;
+/// after an error, the iterator ends.
+///
+/// ```rust
+/// use dist_tx::{XaTransactionId, XidDecoder};
+///
+/// let xid = XaTransactionId::try_new(99, vec![1; 8], vec![2; 16]).unwrap();
+/// let mut bytes = xid.as_bytes(true);
+/// bytes.extend(xid.as_bytes(true));
+///
+/// let xids = XidDecoder::new(&bytes[..], true)
+///     .collect::<Result<Vec<XaTransactionId>, _>>()
+///     .unwrap();
+/// assert_eq!(xids, vec![xid, xid]);
+/// ```
+#[derive(Debug)]
+pub struct XidDecoder<R> {
+    reader: R,
+    layout: XidLayout,
+    position: u64,
+    failed: bool,
+}
+impl<R: Read> XidDecoder<R> {
+    /// Creates a decoder for ids with or without padding to the XID structure in C.
+    pub fn new(reader: R, padding: bool) -> XidDecoder<R> {
+        XidDecoder::with_layout(
+            reader,
+            if padding {
+                XidLayout::LITTLE_ENDIAN_PADDED
+            } else {
+                XidLayout::LITTLE_ENDIAN
+            },
+        )
+    }
+
+    /// Creates a decoder for ids in the given layout.
+    pub fn with_layout(reader: R, layout: XidLayout) -> XidDecoder<R> {
+        XidDecoder {
+            reader,
+            layout,
+            position: 0,
+            failed: false,
+        }
+    }
+
+    /// Returns the number of bytes that were consumed so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_xid(&mut self) -> Result<Option<XaTransactionId>, XaError> {
+        let start = self.position;
+        let mut header = [0_u8; MAX_HEADER_SIZE];
+        let header = &mut header[..self.layout.header_size()];
+        if self.fill(header)? == 0 {
+            return Ok(None);
+        }
+        self.check_complete(header.len(), start)?;
+        let (format_id, global_tid_len, branch_qualifier_len) = self
+            .layout
+            .read_header(header)
+            .map_err(|(offset, msg)| error(start + offset as u64, &msg))?;
+
+        let mut global_tid = [0_u8; MAX_BYTES_GLOBAL_TRANSACTION_ID];
+        let global_tid = &mut global_tid[..global_tid_len];
+        self.read_exact(global_tid)?;
+        let mut branch_qualifier = [0_u8; MAX_BYTES_BRANCH_QUALIFIER];
+        let branch_qualifier = &mut branch_qualifier[..branch_qualifier_len];
+        self.read_exact(branch_qualifier)?;
+
+        if self.layout.padding() {
+            let mut padding = [0_u8; C_DATA_SIZE];
+            let padding = &mut padding[..C_DATA_SIZE - global_tid_len - branch_qualifier_len];
+            let pad_start = self.position;
+            self.read_exact(padding)?;
+            if let Some(i) = padding.iter().position(|b| *b != 0) {
+                return Err(error(pad_start + i as u64, "non-zero padding byte"));
+            }
+        }
+        XaTransactionId::try_new(format_id, &global_tid, &branch_qualifier)
+            .map(Some)
+            .map_err(|_| error(start, "invalid XA transaction id"))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), XaError> {
+        let start = self.position;
+        self.fill(buf)?;
+        self.check_complete(buf.len(), start)
+    }
+
+    fn check_complete(&self, expected: usize, start: u64) -> Result<(), XaError> {
+        if self.position - start == expected as u64 {
+            Ok(())
+        } else {
+            Err(error(self.position, "unexpected end of input"))
+        }
+    }
+
+    // Reads until the buffer is full or the input ends, and returns the number of bytes read.
+    fn fill(&mut self, buf: &mut [u8]) -> Result<usize, XaError> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    filled += n;
+                    self.position += n as u64;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(error(self.position, &e.to_string())),
+            }
+        }
+        Ok(filled)
+    }
+}
+impl<R: Read> Iterator for XidDecoder<R> {
+    type Item = Result<XaTransactionId, XaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.read_xid() {
+            Ok(xid) => xid.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn error(position: u64, msg: &str) -> XaError {
+    XaError::ReadXid(format!("{msg} at byte {position}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XidDecoder;
+    use crate::{XaError, XaTransactionId};
+
+    fn error_of(bytes: &[u8], padding: bool) -> String {
+        let mut decoder = XidDecoder::new(bytes, padding);
+        let result = decoder.by_ref().find_map(Result::err);
+        assert!(decoder.next().is_none());
+        match result {
+            Some(XaError::ReadXid(s)) => s,
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_decoder() {
+        let xids = [
+            XaTransactionId::try_new(99, vec![1; 8], vec![2; 16]).unwrap(),
+            XaTransactionId::try_new(7, vec![3; 64], vec![4; 64]).unwrap(),
+            XaTransactionId::null_ta(),
+        ];
+        for padding in [false, true] {
+            let bytes: Vec<u8> = xids.iter().flat_map(|x| x.as_bytes(padding)).collect();
+            let mut decoder = XidDecoder::new(&bytes[..], padding);
+            let result = decoder.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(result, xids);
+            assert_eq!(decoder.position(), bytes.len() as u64);
+        }
+
+        let bytes = xids[0].as_bytes(true);
+        // a huge length is rejected before anything is allocated
+        let mut huge = bytes.clone();
+        huge[4..8].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert_eq!(
+            error_of(&huge, true),
+            "invalid length 2147483647 (max 64) at byte 4"
+        );
+        let mut negative = bytes.clone();
+        negative[8..12].copy_from_slice(&(-1_i32).to_le_bytes());
+        assert_eq!(
+            error_of(&negative, true),
+            "invalid length -1 (max 64) at byte 8"
+        );
+
+        let mut padded = bytes.clone();
+        padded[100] = 1;
+        assert_eq!(error_of(&padded, true), "non-zero padding byte at byte 100");
+
+        assert_eq!(
+            error_of(&bytes[..20], true),
+            "unexpected end of input at byte 20"
+        );
+        assert_eq!(
+            error_of(&bytes[..5], false),
+            "unexpected end of input at byte 5"
+        );
+        assert_eq!(
+            error_of(&[0xFE, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0], false),
+            "invalid format id -2 at byte 0"
+        );
+    }
+}
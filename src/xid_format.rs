@@ -0,0 +1,184 @@
+use crate::{
+    xa_transaction_id::{hex, unhex},
+    XaError, XaTransactionId,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+// Size of the XID structure in C: three 4-byte integers and 128 data bytes.
+const C_STRUCT_SIZE: usize = 140;
+
+/// The textual representations of an `XaTransactionId` that are supported by the
+/// command-line tool `dist_tx_xid`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XidFormat {
+    /// Hex digits of the 140 bytes of the XID structure in C,
+    /// see [`XaTransactionId::as_bytes`] with padding.
+    Struct,
+    /// Hex digits of the binary representation without padding.
+    Hex,
+    /// Standard base64 encoding of the binary representation without padding.
+    Base64,
+    /// The gid, as it is used for `PostgreSQL`, see [`XaTransactionId::to_gid`].
+    Gid,
+    /// The xid syntax of the XA statements of `MySQL`: `X'gtrid',X'bqual',formatID`.
+    MySql,
+}
+impl XidFormat {
+    /// All formats.
+    pub const ALL: [XidFormat; 5] = [
+        XidFormat::Struct,
+        XidFormat::Hex,
+        XidFormat::Base64,
+        XidFormat::Gid,
+        XidFormat::MySql,
+    ];
+
+    /// Returns the name of the format, as it is used on the command line.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            XidFormat::Struct => "struct",
+            XidFormat::Hex => "hex",
+            XidFormat::Base64 => "base64",
+            XidFormat::Gid => "gid",
+            XidFormat::MySql => "mysql",
+        }
+    }
+
+    /// Returns the format with the given name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<XidFormat> {
+        XidFormat::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    /// Guesses the format of the given string.
+    ///
+    /// Strings of hex digits are taken as [`XidFormat::Struct`] if they have its length,
+    /// and as [`XidFormat::Hex`] otherwise.
+    #[must_use]
+    pub fn detect(s: &str) -> XidFormat {
+        let s = s.trim();
+        if s.starts_with("X'") || s.starts_with("x'") {
+            XidFormat::MySql
+        } else if s.contains('.') {
+            XidFormat::Gid
+        } else {
+            match unhex(strip_0x(s)) {
+                Some(bytes) if bytes.len() == C_STRUCT_SIZE => XidFormat::Struct,
+                Some(_) => XidFormat::Hex,
+                None => XidFormat::Base64,
+            }
+        }
+    }
+
+    /// Returns the representation of the id in this format.
+    #[must_use]
+    pub fn encode(self, xid: &XaTransactionId) -> String {
+        match self {
+            XidFormat::Struct => hex(&xid.as_bytes(true)),
+            XidFormat::Hex => hex(&xid.as_bytes(false)),
+            XidFormat::Base64 => STANDARD.encode(xid.as_bytes(false)),
+            XidFormat::Gid => xid.to_gid(),
+            XidFormat::MySql => crate::mysql::xid_literal(xid),
+        }
+    }
+
+    /// Reads an id from its representation in this format.
+    ///
+    /// # Errors
+    ///
+    /// `XaError::ReadXid` if the string is not a valid representation,
+    /// `XaError::Usage` if the represented fields are ill-formed.
+    pub fn decode(self, s: &str) -> Result<XaTransactionId, XaError> {
+        let s = s.trim();
+        let bad = || XaError::ReadXid(format!("not a valid {} xid: {s}", self.name()));
+        match self {
+            XidFormat::Struct => {
+                XaTransactionId::from_bytes(&unhex(strip_0x(s)).ok_or_else(bad)?, true)
+            }
+            XidFormat::Hex => {
+                XaTransactionId::from_bytes(&unhex(strip_0x(s)).ok_or_else(bad)?, false)
+            }
+            XidFormat::Base64 => {
+                XaTransactionId::from_bytes(&STANDARD.decode(s).map_err(|_| bad())?, false)
+            }
+            XidFormat::Gid => XaTransactionId::from_gid(s),
+            XidFormat::MySql => {
+                // formatID and bqual are optional, as in the XA statements
+                let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+                let literal = |part: &str| {
+                    part.strip_prefix("X'")
+                        .or_else(|| part.strip_prefix("x'"))
+                        .and_then(|part| part.strip_suffix('\''))
+                        .and_then(unhex)
+                        .ok_or_else(bad)
+                };
+                let (gtrid, bqual, format_id) = match parts[..] {
+                    [gtrid] => (literal(gtrid)?, vec![], 1),
+                    [gtrid, bqual] => (literal(gtrid)?, literal(bqual)?, 1),
+                    [gtrid, bqual, format_id] => (
+                        literal(gtrid)?,
+                        literal(bqual)?,
+                        format_id.parse().map_err(|_| bad())?,
+                    ),
+                    _ => return Err(bad()),
+                };
+                XaTransactionId::try_new(format_id, gtrid, bqual)
+            }
+        }
+    }
+}
+impl std::fmt::Display for XidFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+fn strip_0x(s: &str) -> &str {
+    s.strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XidFormat;
+    use crate::{SimpleXid, XaTransactionId};
+
+    #[test]
+    fn test_round_trips() {
+        let xid = SimpleXid {
+            global_tid: 7,
+            tm_id: 0xABCD,
+            rm_id: 3,
+        }
+        .encode();
+        for format in XidFormat::ALL {
+            let s = format.encode(&xid);
+            assert_eq!(XidFormat::detect(&s), format, "{s}");
+            let decoded = format.decode(&s).unwrap();
+            assert_eq!(SimpleXid::decode(&decoded), SimpleXid::decode(&xid));
+            assert_eq!(XidFormat::from_name(&format.to_string()), Some(format));
+        }
+        assert_eq!(XidFormat::Hex.encode(&xid).len(), 2 * (12 + 8 + 16));
+        assert_eq!(XidFormat::Struct.encode(&xid).len(), 2 * 140);
+    }
+
+    #[test]
+    fn test_mysql() {
+        let xid = XidFormat::MySql.decode("X'01AB'").unwrap();
+        assert_eq!(xid.get_format_id(), 1);
+        assert_eq!(xid.get_global_tid(), &vec![0x01, 0xAB]);
+        assert!(xid.get_branch_qualifier().is_empty());
+
+        let xid = XidFormat::MySql.decode("x'01ab', X'FF', 99").unwrap();
+        assert_eq!(
+            xid.as_bytes(false),
+            XaTransactionId::try_new(99, vec![0x01, 0xAB], vec![0xFF])
+                .unwrap()
+                .as_bytes(false)
+        );
+        assert!(XidFormat::MySql.decode("X'01AB',99").is_err());
+        assert!(XidFormat::Hex.decode("0x6300000001000000").is_err());
+    }
+}
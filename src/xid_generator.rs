@@ -0,0 +1,89 @@
+use crate::{SimpleXid, XaTransactionId};
+use std::fmt::Debug;
+
+/// Produces the `XaTransactionId`s of the transaction branches of a `SimpleTransactionManager`
+/// ([`sync::tm::SimpleTransactionManager`](crate::sync::tm::SimpleTransactionManager) or
+/// [`a_sync::tm::SimpleTransactionManager`](crate::a_sync::tm::SimpleTransactionManager)),
+/// and recognizes them during recovery.
+///
+/// The transaction manager numbers its global transactions with a `u64` counter, and
+/// identifies each resource manager with the `rm_id` of its registration.
+/// [`decode`](XidGenerator::decode) must be the inverse of
+/// [`branch_xid`](XidGenerator::branch_xid), and must return `None` for all ids that
+/// were not produced by this generator, because recovery commits or rolls back
+/// the branches that it recognizes.
+///
+/// The default is [`SimpleXidGenerator`]. A generator with an own format id, and a
+/// global transaction id that is unique across transaction manager instances:
+///
+/// ```rust
+/// use dist_tx::{XaTransactionId, XidGenerator};
+///
+/// #[derive(Debug)]
+/// struct CompanyXids {
+///     instance: [u8; 16], // e.g. a UUID of this instance
+/// }
+/// impl XidGenerator for CompanyXids {
+///     fn branch_xid(&self, global_tid: u64, rm_id: u64) -> XaTransactionId {
+///         let mut gtrid = self.instance.to_vec();
+///         gtrid.extend_from_slice(&global_tid.to_be_bytes());
+///         XaTransactionId::try_new(0x4163_6D65, gtrid, rm_id.to_be_bytes()).unwrap()
+///     }
+///
+///     fn decode(&self, xid: &XaTransactionId) -> Option<(u64, u64)> {
+///         let gtrid = xid.get_global_tid();
+///         if xid.get_format_id() != 0x4163_6D65 || gtrid.len() != 24 || gtrid[..16] != self.instance {
+///             return None;
+///         }
+///         Some((
+///             u64::from_be_bytes(gtrid[16..].try_into().ok()?),
+///             u64::from_be_bytes(xid.get_branch_qualifier().try_into().ok()?),
+///         ))
+///     }
+/// }
+/// ```
+pub trait XidGenerator: Debug + Send + Sync {
+    /// Returns the id of the branch of the resource manager `rm_id`
+    /// in the global transaction `global_tid`.
+    fn branch_xid(&self, global_tid: u64, rm_id: u64) -> XaTransactionId;
+
+    /// Returns the global transaction and the resource manager of an id that was
+    /// produced by [`branch_xid`](XidGenerator::branch_xid), or `None` for all other ids.
+    fn decode(&self, xid: &XaTransactionId) -> Option<(u64, u64)>;
+}
+
+/// The default [`XidGenerator`], which produces [`SimpleXid`]s
+/// with the `tm_id` of the transaction manager.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimpleXidGenerator {
+    tm_id: u64,
+}
+impl SimpleXidGenerator {
+    /// Creates a generator for the transaction manager with the given id.
+    #[must_use]
+    pub fn new(tm_id: u64) -> SimpleXidGenerator {
+        SimpleXidGenerator { tm_id }
+    }
+
+    /// Returns the id of the transaction manager.
+    #[must_use]
+    pub fn tm_id(&self) -> u64 {
+        self.tm_id
+    }
+}
+impl XidGenerator for SimpleXidGenerator {
+    fn branch_xid(&self, global_tid: u64, rm_id: u64) -> XaTransactionId {
+        SimpleXid {
+            global_tid,
+            tm_id: self.tm_id,
+            rm_id,
+        }
+        .encode()
+    }
+
+    fn decode(&self, xid: &XaTransactionId) -> Option<(u64, u64)> {
+        SimpleXid::decode(xid)
+            .filter(|parts| parts.tm_id == self.tm_id)
+            .map(|parts| (parts.global_tid, parts.rm_id))
+    }
+}